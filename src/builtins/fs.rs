@@ -0,0 +1,211 @@
+use std::cell::RefCell;
+use std::fs;
+use std::io::Write;
+use std::rc::Rc;
+
+use crate::builtins::{define_native, expect_string};
+use crate::environment::Environment;
+use crate::function::NativeFunction;
+use crate::interpreter::{Interpreter, RuntimeError};
+use crate::token::{Token, TokenKind};
+
+/// Fills in the globals of the `fs` module
+pub fn define_module(globals: &mut Environment) {
+    define_native(globals, NativeFunction::new("read_text", 1..=1, read_text));
+    define_native(
+        globals,
+        NativeFunction::new("write_text", 2..=2, write_text),
+    );
+    define_native(
+        globals,
+        NativeFunction::new("append_text", 2..=2, append_text),
+    );
+    define_native(
+        globals,
+        NativeFunction::new("read_lines", 1..=1, read_lines),
+    );
+    define_native(
+        globals,
+        NativeFunction::new("read_bytes", 1..=1, read_bytes),
+    );
+    define_native(
+        globals,
+        NativeFunction::new("write_bytes", 2..=2, write_bytes),
+    );
+    define_native(globals, NativeFunction::new("exists", 1..=1, exists));
+    define_native(globals, NativeFunction::new("list_dir", 1..=1, list_dir));
+    define_native(globals, NativeFunction::new("mkdir", 1..=1, mkdir));
+    define_native(
+        globals,
+        NativeFunction::new("remove", 1..=2, remove).with_params(&["path", "recursive"]),
+    );
+    define_native(globals, NativeFunction::new("rename", 2..=2, rename));
+}
+
+fn io_error(message: String) -> RuntimeError {
+    RuntimeError::new(format!("IO error: {}.", message))
+}
+
+fn none() -> Token {
+    Token::from(TokenKind::None)
+}
+
+fn string_list(values: Vec<String>) -> Token {
+    let values = values
+        .into_iter()
+        .map(|value| Token::from(TokenKind::String(value)))
+        .collect();
+
+    Token::from(TokenKind::List(Rc::new(RefCell::new(values))))
+}
+
+/// Reads a whole file, reporting failures in the same way as when running a script
+fn read(func: &str, path: &Token) -> Result<String, RuntimeError> {
+    let path = expect_string(func, path)?;
+
+    let mut file = match fs::File::open(&path) {
+        Ok(file) => file,
+        Err(..) => return Err(io_error(format!("could not open file '{}'", path))),
+    };
+
+    let mut contents = String::new();
+
+    match std::io::Read::read_to_string(&mut file, &mut contents) {
+        Ok(..) => Ok(contents),
+        Err(..) => Err(io_error(format!("failed to read from file '{}'", path))),
+    }
+}
+
+/// `read_text(path)`, the contents of a file
+fn read_text(_: &mut Interpreter, args: Vec<Token>) -> Result<Token, RuntimeError> {
+    Ok(Token::from(TokenKind::String(read("read_text", &args[0])?)))
+}
+
+/// `read_lines(path)`, the lines of a file without their line endings
+fn read_lines(_: &mut Interpreter, args: Vec<Token>) -> Result<Token, RuntimeError> {
+    let contents = read("read_lines", &args[0])?;
+    Ok(string_list(contents.lines().map(String::from).collect()))
+}
+
+/// `write_text(path, text)`, replacing the contents of a file, which is created if needed
+fn write_text(_: &mut Interpreter, args: Vec<Token>) -> Result<Token, RuntimeError> {
+    let path = expect_string("write_text", &args[0])?;
+    let text = expect_string("write_text", &args[1])?;
+
+    match fs::write(&path, text) {
+        Ok(()) => Ok(none()),
+        Err(..) => Err(io_error(format!("could not write to file '{}'", path))),
+    }
+}
+
+/// `append_text(path, text)`, adding to the end of a file, which is created if needed
+fn append_text(_: &mut Interpreter, args: Vec<Token>) -> Result<Token, RuntimeError> {
+    let path = expect_string("append_text", &args[0])?;
+    let text = expect_string("append_text", &args[1])?;
+
+    let mut file = match fs::OpenOptions::new().append(true).create(true).open(&path) {
+        Ok(file) => file,
+        Err(..) => return Err(io_error(format!("could not open file '{}'", path))),
+    };
+
+    match file.write_all(text.as_bytes()) {
+        Ok(()) => Ok(none()),
+        Err(..) => Err(io_error(format!("could not write to file '{}'", path))),
+    }
+}
+
+/// `read_bytes(path)`, the contents of a file as bytes, for files that aren't text
+fn read_bytes(_: &mut Interpreter, args: Vec<Token>) -> Result<Token, RuntimeError> {
+    let path = expect_string("read_bytes", &args[0])?;
+
+    match fs::read(&path) {
+        Ok(bytes) => Ok(Token::from(TokenKind::Bytes(Rc::new(bytes)))),
+        Err(..) => Err(io_error(format!("could not open file '{}'", path))),
+    }
+}
+
+/// `write_bytes(path, bytes)`, replacing the contents of a file with some bytes, creating it if
+/// needed
+fn write_bytes(_: &mut Interpreter, args: Vec<Token>) -> Result<Token, RuntimeError> {
+    let path = expect_string("write_bytes", &args[0])?;
+
+    let bytes = match &args[1].kind {
+        TokenKind::Bytes(bytes) => Rc::clone(bytes),
+        kind => {
+            return Err(RuntimeError::new(format!(
+                "Type error: write_bytes expected Bytes but got {:?}.",
+                kind
+            )));
+        }
+    };
+
+    match fs::write(&path, bytes.as_slice()) {
+        Ok(()) => Ok(none()),
+        Err(..) => Err(io_error(format!("could not write to file '{}'", path))),
+    }
+}
+
+/// `exists(path)`, whether there is a file or directory at the path
+fn exists(_: &mut Interpreter, args: Vec<Token>) -> Result<Token, RuntimeError> {
+    let path = expect_string("exists", &args[0])?;
+    Ok(Token::from(TokenKind::Bool(fs::metadata(path).is_ok())))
+}
+
+/// `list_dir(path)`, the names of the entries in a directory in alphabetical order
+fn list_dir(_: &mut Interpreter, args: Vec<Token>) -> Result<Token, RuntimeError> {
+    let path = expect_string("list_dir", &args[0])?;
+    let error = || io_error(format!("could not read directory '{}'", path));
+
+    let mut names = Vec::new();
+
+    for entry in fs::read_dir(&path).map_err(|_| error())? {
+        let entry = entry.map_err(|_| error())?;
+        names.push(entry.file_name().to_string_lossy().into_owned());
+    }
+
+    names.sort();
+    Ok(string_list(names))
+}
+
+/// `mkdir(path)`, creating a directory along with any missing parents
+fn mkdir(_: &mut Interpreter, args: Vec<Token>) -> Result<Token, RuntimeError> {
+    let path = expect_string("mkdir", &args[0])?;
+
+    match fs::create_dir_all(&path) {
+        Ok(()) => Ok(none()),
+        Err(..) => Err(io_error(format!("could not create directory '{}'", path))),
+    }
+}
+
+/// `remove(path, recursive)`, deleting a file or an empty directory. A directory's contents are
+/// only deleted along with it if `recursive` is truthy.
+fn remove(interpreter: &mut Interpreter, args: Vec<Token>) -> Result<Token, RuntimeError> {
+    let path = expect_string("remove", &args[0])?;
+
+    let recursive = match args.get(1) {
+        Some(recursive) => interpreter.truthy(recursive)?,
+        None => false,
+    };
+
+    let result = match fs::symlink_metadata(&path) {
+        Ok(metadata) if metadata.is_dir() && recursive => fs::remove_dir_all(&path),
+        Ok(metadata) if metadata.is_dir() => fs::remove_dir(&path),
+        _ => fs::remove_file(&path),
+    };
+
+    match result {
+        Ok(()) => Ok(none()),
+        Err(..) => Err(io_error(format!("could not remove '{}'", path))),
+    }
+}
+
+/// `rename(from, to)`, moving a file or directory
+fn rename(_: &mut Interpreter, args: Vec<Token>) -> Result<Token, RuntimeError> {
+    let from = expect_string("rename", &args[0])?;
+    let to = expect_string("rename", &args[1])?;
+
+    match fs::rename(&from, &to) {
+        Ok(()) => Ok(none()),
+        Err(..) => Err(io_error(format!("could not rename '{}' to '{}'", from, to))),
+    }
+}
@@ -0,0 +1,380 @@
+use std::cell::RefCell;
+use std::cmp::Ordering;
+use std::rc::Rc;
+
+use crate::builtins::{define_native, expect_number};
+use crate::environment::Environment;
+use crate::function::NativeFunction;
+use crate::interpreter::{Interpreter, RuntimeError};
+use crate::token::{Token, TokenKind};
+
+/// Adds the functions for working with whole collections to the global environment. They take
+/// anything a `for` loop can step through, and give back lists.
+pub fn define_globals(globals: &mut Environment) {
+    let natives = [
+        NativeFunction::new("map", 2..=2, map),
+        NativeFunction::new("filter", 2..=2, filter),
+        NativeFunction::new("reduce", 2..=3, reduce),
+        NativeFunction::new("sorted", 1..=3, sorted).with_params(&["values", "key", "reverse"]),
+        NativeFunction::new("zip", 0..=usize::MAX, zip),
+        NativeFunction::new("enumerate", 1..=2, enumerate).with_params(&["values", "start"]),
+        NativeFunction::new("any", 1..=1, any),
+        NativeFunction::new("all", 1..=1, all),
+        NativeFunction::new("sum", 1..=2, sum).with_params(&["values", "start"]),
+        NativeFunction::new("min", 1..=usize::MAX, min).with_params(&["values", "key"]),
+        NativeFunction::new("max", 1..=usize::MAX, max).with_params(&["values", "key"]),
+    ];
+
+    for func in natives {
+        define_native(globals, func);
+    }
+}
+
+fn list(elements: Vec<Token>) -> Token {
+    Token::from(TokenKind::List(Rc::new(RefCell::new(elements))))
+}
+
+/// Steps through a value, collecting everything it gives
+fn collect(interpreter: &mut Interpreter, value: &Token) -> Result<Vec<Token>, RuntimeError> {
+    let mut iterator = interpreter.iterate(value.clone())?;
+    let mut values = Vec::new();
+
+    while let Some(value) = iterator.next(interpreter)? {
+        values.push(value);
+    }
+
+    Ok(values)
+}
+
+/// An optional argument, treating none the same as leaving it out
+fn optional(args: &[Token], index: usize) -> Option<&Token> {
+    args.get(index)
+        .filter(|arg| !matches!(arg.kind, TokenKind::None))
+}
+
+/// Orders two values: numbers and strings by value, lists element by element, and instances by
+/// their `__lt__` method. NaN comes after every other number.
+fn compare(
+    interpreter: &mut Interpreter,
+    func: &str,
+    lhs: &Token,
+    rhs: &Token,
+) -> Result<Ordering, RuntimeError> {
+    match (&lhs.kind, &rhs.kind) {
+        (TokenKind::Number(lval), TokenKind::Number(rval)) => Ok(lval
+            .partial_cmp(rval)
+            .unwrap_or_else(|| lval.is_nan().cmp(&rval.is_nan()))),
+        (TokenKind::String(lval), TokenKind::String(rval)) => Ok(lval.cmp(rval)),
+
+        (TokenKind::List(lval), TokenKind::List(rval)) => {
+            let (lval, rval) = (lval.borrow().clone(), rval.borrow().clone());
+
+            for (lhs, rhs) in lval.iter().zip(rval.iter()) {
+                match compare(interpreter, func, lhs, rhs)? {
+                    Ordering::Equal => {}
+                    ordering => return Ok(ordering),
+                }
+            }
+
+            Ok(lval.len().cmp(&rval.len()))
+        }
+
+        _ => match interpreter.call_operator(&TokenKind::Less, lhs, rhs)? {
+            Some(less) if interpreter.truthy(&less)? => Ok(Ordering::Less),
+            Some(..) => match interpreter.call_operator(&TokenKind::Less, rhs, lhs)? {
+                Some(greater) if interpreter.truthy(&greater)? => Ok(Ordering::Greater),
+                _ => Ok(Ordering::Equal),
+            },
+
+            None => Err(RuntimeError::new(format!(
+                "Type error: {} could not compare {:?} with {:?}.",
+                func, lhs.kind, rhs.kind
+            ))),
+        },
+    }
+}
+
+/// Sorts stably with a comparison that may fail, stopping at its first error. Unlike the standard
+/// library's sorts, this copes with scripts whose comparisons aren't a consistent order.
+fn merge_sort<F>(items: &mut [usize], compare: &mut F) -> Result<(), RuntimeError>
+where
+    F: FnMut(usize, usize) -> Result<Ordering, RuntimeError>,
+{
+    if items.len() <= 1 {
+        return Ok(());
+    }
+
+    let middle = items.len() / 2;
+    merge_sort(&mut items[..middle], compare)?;
+    merge_sort(&mut items[middle..], compare)?;
+
+    let (left, right) = (items[..middle].to_vec(), items[middle..].to_vec());
+    let (mut i, mut j) = (0, 0);
+
+    for slot in items.iter_mut() {
+        // Taking from the left unless the right is strictly smaller keeps equal items in order
+        let take_left =
+            j == right.len() || (i < left.len() && compare(right[j], left[i])? != Ordering::Less);
+
+        if take_left {
+            *slot = left[i];
+            i += 1;
+        } else {
+            *slot = right[j];
+            j += 1;
+        }
+    }
+
+    Ok(())
+}
+
+/// `map(func, values)`, a list of the results of calling the function on each value
+fn map(interpreter: &mut Interpreter, args: Vec<Token>) -> Result<Token, RuntimeError> {
+    let values = collect(interpreter, &args[1])?;
+
+    let results = values
+        .into_iter()
+        .map(|value| interpreter.call(args[0].clone(), vec![value]))
+        .collect::<Result<Vec<Token>, RuntimeError>>()?;
+
+    Ok(list(results))
+}
+
+/// `filter(func, values)`, a list of the values the function is truthy for. If the function is
+/// none, the values which are themselves truthy.
+fn filter(interpreter: &mut Interpreter, args: Vec<Token>) -> Result<Token, RuntimeError> {
+    let values = collect(interpreter, &args[1])?;
+    let mut kept = Vec::new();
+
+    for value in values {
+        let keep = match &args[0].kind {
+            TokenKind::None => interpreter.truthy(&value)?,
+            _ => {
+                let result = interpreter.call(args[0].clone(), vec![value.clone()])?;
+                interpreter.truthy(&result)?
+            }
+        };
+
+        if keep {
+            kept.push(value);
+        }
+    }
+
+    Ok(list(kept))
+}
+
+/// `reduce(func, values, initial)`, combining the values from left to right with a function of
+/// two arguments, starting from the initial value if given
+fn reduce(interpreter: &mut Interpreter, args: Vec<Token>) -> Result<Token, RuntimeError> {
+    let mut values = collect(interpreter, &args[1])?.into_iter();
+
+    let mut result = match args.get(2).cloned().or_else(|| values.next()) {
+        Some(value) => value,
+        None => {
+            return Err(RuntimeError::new(String::from(
+                "Value error: reduce of an empty sequence with no initial value.",
+            )));
+        }
+    };
+
+    for value in values {
+        result = interpreter.call(args[0].clone(), vec![result, value])?;
+    }
+
+    Ok(result)
+}
+
+/// `sorted(values, key, reverse)`, a new list of the values in ascending order, or descending if
+/// `reverse` is truthy. Values are compared by the result of `key` on them if it's given. Equal
+/// values keep their order.
+fn sorted(interpreter: &mut Interpreter, args: Vec<Token>) -> Result<Token, RuntimeError> {
+    let values = collect(interpreter, &args[0])?;
+
+    let reverse = match optional(&args, 2) {
+        Some(reverse) => interpreter.truthy(reverse)?,
+        None => false,
+    };
+
+    let keys = match optional(&args, 1) {
+        Some(key) => values
+            .iter()
+            .map(|value| interpreter.call(key.clone(), vec![value.clone()]))
+            .collect::<Result<Vec<Token>, RuntimeError>>()?,
+        None => values.clone(),
+    };
+
+    let mut order: Vec<usize> = (0..values.len()).collect();
+
+    merge_sort(&mut order, &mut |lhs, rhs| {
+        let ordering = compare(interpreter, "sorted", &keys[lhs], &keys[rhs])?;
+        Ok(if reverse {
+            ordering.reverse()
+        } else {
+            ordering
+        })
+    })?;
+
+    Ok(list(
+        order
+            .into_iter()
+            .map(|index| values[index].clone())
+            .collect(),
+    ))
+}
+
+/// `zip(a, b, ...)`, a list of lists holding the first value of each argument, then the second,
+/// and so on until the shortest runs out
+fn zip(interpreter: &mut Interpreter, args: Vec<Token>) -> Result<Token, RuntimeError> {
+    let mut iterators = args
+        .into_iter()
+        .map(|arg| interpreter.iterate(arg))
+        .collect::<Result<Vec<_>, RuntimeError>>()?;
+
+    let mut rows = Vec::new();
+
+    if iterators.is_empty() {
+        return Ok(list(rows));
+    }
+
+    'rows: loop {
+        let mut row = Vec::new();
+
+        for iterator in iterators.iter_mut() {
+            match iterator.next(interpreter)? {
+                Some(value) => row.push(value),
+                None => break 'rows,
+            }
+        }
+
+        rows.push(list(row));
+    }
+
+    Ok(list(rows))
+}
+
+/// `enumerate(values, start)`, a list of `[index, value]` pairs, counting from start (0 if not
+/// given)
+fn enumerate(interpreter: &mut Interpreter, args: Vec<Token>) -> Result<Token, RuntimeError> {
+    let values = collect(interpreter, &args[0])?;
+
+    let start = match optional(&args, 1) {
+        Some(start) => expect_number("enumerate", start)?,
+        None => 0.0,
+    };
+
+    let pairs = values
+        .into_iter()
+        .enumerate()
+        .map(|(index, value)| {
+            let index = Token::from(TokenKind::Number(start + index as f64));
+            list(vec![index, value])
+        })
+        .collect();
+
+    Ok(list(pairs))
+}
+
+/// `any(values)`, whether any value is truthy, stopping at the first that is
+fn any(interpreter: &mut Interpreter, args: Vec<Token>) -> Result<Token, RuntimeError> {
+    let mut iterator = interpreter.iterate(args[0].clone())?;
+
+    while let Some(value) = iterator.next(interpreter)? {
+        if interpreter.truthy(&value)? {
+            return Ok(Token::from(TokenKind::Bool(true)));
+        }
+    }
+
+    Ok(Token::from(TokenKind::Bool(false)))
+}
+
+/// `all(values)`, whether every value is truthy, stopping at the first that isn't
+fn all(interpreter: &mut Interpreter, args: Vec<Token>) -> Result<Token, RuntimeError> {
+    let mut iterator = interpreter.iterate(args[0].clone())?;
+
+    while let Some(value) = iterator.next(interpreter)? {
+        if !interpreter.truthy(&value)? {
+            return Ok(Token::from(TokenKind::Bool(false)));
+        }
+    }
+
+    Ok(Token::from(TokenKind::Bool(true)))
+}
+
+/// `sum(values, start)`, the total of some numbers added to start (0 if not given)
+fn sum(interpreter: &mut Interpreter, args: Vec<Token>) -> Result<Token, RuntimeError> {
+    let values = collect(interpreter, &args[0])?;
+
+    let mut total = match optional(&args, 1) {
+        Some(start) => expect_number("sum", start)?,
+        None => 0.0,
+    };
+
+    for value in values {
+        total += expect_number("sum", &value)?;
+    }
+
+    Ok(Token::from(TokenKind::Number(total)))
+}
+
+/// `min(values, key)` or `min(a, b, ...)`, the smallest value, the first if there are several
+fn min(interpreter: &mut Interpreter, args: Vec<Token>) -> Result<Token, RuntimeError> {
+    extreme(interpreter, "min", Ordering::Less, args)
+}
+
+/// `max(values, key)` or `max(a, b, ...)`, the largest value, the first if there are several
+fn max(interpreter: &mut Interpreter, args: Vec<Token>) -> Result<Token, RuntimeError> {
+    extreme(interpreter, "max", Ordering::Greater, args)
+}
+
+/// Finds the value furthest in the direction of `wanted`, compared by the result of a key function
+/// if one is given
+fn extreme(
+    interpreter: &mut Interpreter,
+    func: &str,
+    wanted: Ordering,
+    args: Vec<Token>,
+) -> Result<Token, RuntimeError> {
+    // A collection to look through is given on its own or followed by a key function (or none),
+    // and otherwise the arguments are the values themselves
+    let key_given = args.len() == 2
+        && matches!(
+            args[1].kind,
+            TokenKind::None
+                | TokenKind::Function(..)
+                | TokenKind::NativeFunction(..)
+                | TokenKind::Type(..)
+                | TokenKind::Constructor(..)
+        );
+
+    let (values, key) = if args.len() == 1 || key_given {
+        (collect(interpreter, &args[0])?, optional(&args, 1).cloned())
+    } else {
+        (args, None)
+    };
+
+    let mut best: Option<(Token, Token)> = None;
+
+    for value in values {
+        let compared = match &key {
+            Some(key) => interpreter.call(key.clone(), vec![value.clone()])?,
+            None => value.clone(),
+        };
+
+        let better = match &best {
+            Some((_, best)) => compare(interpreter, func, &compared, best)? == wanted,
+            None => true,
+        };
+
+        if better {
+            best = Some((value, compared));
+        }
+    }
+
+    match best {
+        Some((value, _)) => Ok(value),
+        None => Err(RuntimeError::new(format!(
+            "Value error: {} of an empty sequence.",
+            func
+        ))),
+    }
+}
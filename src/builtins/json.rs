@@ -0,0 +1,566 @@
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use crate::builtins::{define_native, expect_number, expect_string};
+use crate::environment::Environment;
+use crate::function::NativeFunction;
+use crate::interpreter::{Interpreter, RuntimeError};
+use crate::map::Map;
+use crate::token::{Token, TokenKind};
+
+// How deeply arrays and objects may nest in parsed text, as each level takes space on the stack
+const MAX_DEPTH: usize = 512;
+
+/// Fills in the globals of the `json` module
+pub fn define_module(globals: &mut Environment) {
+    define_native(globals, NativeFunction::new("parse", 1..=1, parse));
+    define_native(globals, NativeFunction::new("stringify", 1..=2, stringify));
+}
+
+/// `parse(text)`, converting JSON text into lists, maps, numbers, strings, bools and none
+fn parse(_: &mut Interpreter, args: Vec<Token>) -> Result<Token, RuntimeError> {
+    let text = expect_string("parse", &args[0])?;
+    let mut parser = JsonParser::new(&text);
+
+    parser.skip_whitespace();
+    let value = parser.parse_value()?;
+    parser.skip_whitespace();
+
+    if let Some(c) = parser.peek() {
+        return Err(parser.error(format!("unexpected '{}' after the end of the value", c)));
+    }
+
+    Ok(value)
+}
+
+/// `stringify(value, indent)`, converting a value into JSON text. Without an indent the text is
+/// written on a single line.
+fn stringify(_: &mut Interpreter, args: Vec<Token>) -> Result<Token, RuntimeError> {
+    let indent = match args.get(1) {
+        None
+        | Some(Token {
+            kind: TokenKind::None,
+            ..
+        }) => None,
+
+        Some(indent) => {
+            let indent = expect_number("stringify", indent)?;
+
+            if indent.fract() != 0.0 || indent < 0.0 {
+                return Err(RuntimeError::new(format!(
+                    "Value error: stringify indent must be a whole number of spaces, found {}.",
+                    indent
+                )));
+            }
+
+            Some(indent as usize)
+        }
+    };
+
+    let mut writer = JsonWriter {
+        output: String::new(),
+        indent,
+        containers: Vec::new(),
+    };
+
+    writer.write_value(&args[0], 0)?;
+    Ok(Token::from(TokenKind::String(writer.output)))
+}
+
+struct JsonParser {
+    chars: Vec<char>,
+    cursor: usize,
+
+    // Position of the next character, counting from 1
+    line: usize,
+    column: usize,
+
+    // Number of arrays and objects the next character is inside of
+    depth: usize,
+}
+
+impl JsonParser {
+    fn new(text: &str) -> JsonParser {
+        JsonParser {
+            chars: text.chars().collect(),
+            cursor: 0,
+
+            line: 1,
+            column: 1,
+
+            depth: 0,
+        }
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.cursor).copied()
+    }
+
+    fn advance(&mut self) -> Option<char> {
+        let c = self.peek()?;
+        self.cursor += 1;
+
+        if c == '\n' {
+            self.line += 1;
+            self.column = 1;
+        } else {
+            self.column += 1;
+        }
+
+        Some(c)
+    }
+
+    fn error(&self, message: String) -> RuntimeError {
+        RuntimeError::new(format!(
+            "Value error: invalid JSON at line {}, column {}: {}.",
+            self.line, self.column, message
+        ))
+    }
+
+    fn unexpected(&self) -> RuntimeError {
+        match self.peek() {
+            Some(c) => self.error(format!("unexpected '{}'", c)),
+            None => self.error(String::from("unexpected end of input")),
+        }
+    }
+
+    fn expect(&mut self, expected: char) -> Result<(), RuntimeError> {
+        if self.peek() == Some(expected) {
+            self.advance();
+            Ok(())
+        } else {
+            Err(self.unexpected())
+        }
+    }
+
+    fn skip_whitespace(&mut self) {
+        while let Some(' ' | '\t' | '\n' | '\r') = self.peek() {
+            self.advance();
+        }
+    }
+
+    fn parse_value(&mut self) -> Result<Token, RuntimeError> {
+        match self.peek() {
+            Some('{') => self.parse_nested(JsonParser::parse_object),
+            Some('[') => self.parse_nested(JsonParser::parse_array),
+            Some('"') => Ok(Token::from(TokenKind::String(self.parse_string()?))),
+            Some('-' | '0'..='9') => self.parse_number(),
+
+            Some('t') => self.parse_keyword("true", TokenKind::Bool(true)),
+            Some('f') => self.parse_keyword("false", TokenKind::Bool(false)),
+            Some('n') => self.parse_keyword("null", TokenKind::None),
+
+            _ => Err(self.unexpected()),
+        }
+    }
+
+    fn parse_nested(
+        &mut self,
+        parse: fn(&mut JsonParser) -> Result<Token, RuntimeError>,
+    ) -> Result<Token, RuntimeError> {
+        if self.depth == MAX_DEPTH {
+            return Err(self.error(format!(
+                "arrays and objects nested more than {} deep",
+                MAX_DEPTH
+            )));
+        }
+
+        self.depth += 1;
+        let value = parse(self);
+        self.depth -= 1;
+
+        value
+    }
+
+    fn parse_keyword(&mut self, keyword: &str, kind: TokenKind) -> Result<Token, RuntimeError> {
+        for expected in keyword.chars() {
+            self.expect(expected)?;
+        }
+
+        Ok(Token::from(kind))
+    }
+
+    fn parse_object(&mut self) -> Result<Token, RuntimeError> {
+        self.advance();
+        self.skip_whitespace();
+
+        let mut map = Map::new();
+
+        if self.peek() == Some('}') {
+            self.advance();
+            return Ok(Token::from(TokenKind::Map(Rc::new(RefCell::new(map)))));
+        }
+
+        loop {
+            self.skip_whitespace();
+
+            if self.peek() != Some('"') {
+                return Err(match self.peek() {
+                    Some(..) => self.error(String::from("expected a string as object key")),
+                    None => self.unexpected(),
+                });
+            }
+
+            let key = self.parse_string()?;
+
+            self.skip_whitespace();
+            self.expect(':')?;
+            self.skip_whitespace();
+
+            let value = self.parse_value()?;
+            map.insert(Token::from(TokenKind::String(key)), value)?;
+
+            self.skip_whitespace();
+
+            match self.peek() {
+                Some(',') => {
+                    self.advance();
+                }
+
+                Some('}') => {
+                    self.advance();
+                    break;
+                }
+
+                _ => return Err(self.unexpected()),
+            }
+        }
+
+        Ok(Token::from(TokenKind::Map(Rc::new(RefCell::new(map)))))
+    }
+
+    fn parse_array(&mut self) -> Result<Token, RuntimeError> {
+        self.advance();
+        self.skip_whitespace();
+
+        let mut elements = Vec::new();
+
+        if self.peek() == Some(']') {
+            self.advance();
+            return Ok(Token::from(TokenKind::List(Rc::new(RefCell::new(
+                elements,
+            )))));
+        }
+
+        loop {
+            self.skip_whitespace();
+            elements.push(self.parse_value()?);
+            self.skip_whitespace();
+
+            match self.peek() {
+                Some(',') => {
+                    self.advance();
+                }
+
+                Some(']') => {
+                    self.advance();
+                    break;
+                }
+
+                _ => return Err(self.unexpected()),
+            }
+        }
+
+        Ok(Token::from(TokenKind::List(Rc::new(RefCell::new(
+            elements,
+        )))))
+    }
+
+    fn parse_string(&mut self) -> Result<String, RuntimeError> {
+        self.advance();
+
+        let mut string = String::new();
+
+        loop {
+            match self.peek() {
+                Some('"') => {
+                    self.advance();
+                    return Ok(string);
+                }
+
+                Some('\\') => {
+                    self.advance();
+                    string.push(self.parse_escape()?);
+                }
+
+                Some(c) if (c as u32) < 0x20 => {
+                    return Err(self.error(String::from("control character in string")));
+                }
+
+                Some(c) => {
+                    self.advance();
+                    string.push(c);
+                }
+
+                None => return Err(self.unexpected()),
+            }
+        }
+    }
+
+    fn parse_escape(&mut self) -> Result<char, RuntimeError> {
+        let c = match self.peek() {
+            Some('"') => '"',
+            Some('\\') => '\\',
+            Some('/') => '/',
+            Some('b') => '\u{8}',
+            Some('f') => '\u{c}',
+            Some('n') => '\n',
+            Some('r') => '\r',
+            Some('t') => '\t',
+
+            Some('u') => {
+                self.advance();
+                let unit = self.parse_hex()?;
+
+                // Characters outside the basic plane are written as a pair of surrogates
+                if (0xd800..0xdc00).contains(&unit) {
+                    self.expect('\\')?;
+                    self.expect('u')?;
+
+                    let low = self.parse_hex()?;
+
+                    if !(0xdc00..0xe000).contains(&low) {
+                        return Err(self.error(String::from("invalid surrogate pair")));
+                    }
+
+                    let code = 0x10000 + ((unit - 0xd800) << 10) + (low - 0xdc00);
+                    return Ok(char::from_u32(code).unwrap());
+                }
+
+                return match char::from_u32(unit) {
+                    Some(c) => Ok(c),
+                    None => Err(self.error(String::from("invalid surrogate pair"))),
+                };
+            }
+
+            _ => return Err(self.error(String::from("invalid escape sequence"))),
+        };
+
+        self.advance();
+        Ok(c)
+    }
+
+    fn parse_hex(&mut self) -> Result<u32, RuntimeError> {
+        let mut value = 0;
+
+        for _ in 0..4 {
+            let digit = match self.peek().and_then(|c| c.to_digit(16)) {
+                Some(digit) => digit,
+                None => return Err(self.error(String::from("invalid unicode escape"))),
+            };
+
+            self.advance();
+            value = value * 16 + digit;
+        }
+
+        Ok(value)
+    }
+
+    fn parse_number(&mut self) -> Result<Token, RuntimeError> {
+        let start = self.cursor;
+
+        if self.peek() == Some('-') {
+            self.advance();
+        }
+
+        // Leading zeros aren't allowed, so a zero must be the whole integer part
+        match self.peek() {
+            Some('0') => {
+                self.advance();
+            }
+
+            Some('1'..='9') => self.skip_digits(),
+            _ => return Err(self.unexpected()),
+        }
+
+        if self.peek() == Some('.') {
+            self.advance();
+            self.expect_digits()?;
+        }
+
+        if let Some('e' | 'E') = self.peek() {
+            self.advance();
+
+            if let Some('+' | '-') = self.peek() {
+                self.advance();
+            }
+
+            self.expect_digits()?;
+        }
+
+        let text: String = self.chars[start..self.cursor].iter().collect();
+        Ok(Token::from(TokenKind::Number(text.parse().unwrap())))
+    }
+
+    fn skip_digits(&mut self) {
+        while let Some('0'..='9') = self.peek() {
+            self.advance();
+        }
+    }
+
+    fn expect_digits(&mut self) -> Result<(), RuntimeError> {
+        match self.peek() {
+            Some('0'..='9') => {
+                self.skip_digits();
+                Ok(())
+            }
+
+            _ => Err(self.unexpected()),
+        }
+    }
+}
+
+struct JsonWriter {
+    output: String,
+    indent: Option<usize>,
+
+    // The lists and maps currently being written, to catch values which contain themselves
+    containers: Vec<*const ()>,
+}
+
+impl JsonWriter {
+    fn write_value(&mut self, value: &Token, depth: usize) -> Result<(), RuntimeError> {
+        match &value.kind {
+            TokenKind::None => self.output.push_str("null"),
+            TokenKind::Bool(val) => self.output.push_str(&val.to_string()),
+
+            TokenKind::Number(val) if val.is_finite() => self.output.push_str(&val.to_string()),
+            TokenKind::Number(val) => {
+                return Err(RuntimeError::new(format!(
+                    "Value error: could not convert {} to JSON.",
+                    val
+                )));
+            }
+
+            TokenKind::String(val) => self.write_string(val),
+
+            // Dates have no JSON type of their own, so are written as ISO-8601 strings
+            TokenKind::Date(date) => self.write_string(&date.to_string()),
+
+            TokenKind::List(list) => {
+                self.enter(Rc::as_ptr(list) as *const ())?;
+
+                let elements = list.borrow().clone();
+                self.write_container(('[', ']'), elements.len(), depth, |writer, index| {
+                    writer.write_value(&elements[index], depth + 1)
+                })?;
+
+                self.containers.pop();
+            }
+
+            // Sets have no JSON type of their own, so are written as arrays
+            TokenKind::Set(set) => {
+                let elements = set.borrow().values().to_vec();
+                self.write_container(('[', ']'), elements.len(), depth, |writer, index| {
+                    writer.write_value(&elements[index], depth + 1)
+                })?;
+            }
+
+            TokenKind::Map(map) => {
+                self.enter(Rc::as_ptr(map) as *const ())?;
+
+                let entries = map.borrow().entries().to_vec();
+                self.write_container(('{', '}'), entries.len(), depth, |writer, index| {
+                    let (key, value) = &entries[index];
+
+                    match &key.kind {
+                        TokenKind::String(key) => writer.write_string(key),
+                        kind => {
+                            return Err(RuntimeError::new(format!(
+                                "Type error: JSON object keys must be Strings, found {:?}.",
+                                kind
+                            )));
+                        }
+                    }
+
+                    writer.output.push(':');
+
+                    if writer.indent.is_some() {
+                        writer.output.push(' ');
+                    }
+
+                    writer.write_value(value, depth + 1)
+                })?;
+
+                self.containers.pop();
+            }
+
+            kind => {
+                return Err(RuntimeError::new(format!(
+                    "Type error: could not convert value of type {:?} to JSON.",
+                    kind
+                )));
+            }
+        }
+
+        Ok(())
+    }
+
+    fn enter(&mut self, container: *const ()) -> Result<(), RuntimeError> {
+        if self.containers.contains(&container) {
+            return Err(RuntimeError::new(String::from(
+                "Value error: could not convert a value that contains itself to JSON.",
+            )));
+        }
+
+        self.containers.push(container);
+        Ok(())
+    }
+
+    /// Writes a list or object with `len` items, putting each on its own line when indenting
+    fn write_container<F>(
+        &mut self,
+        (open, close): (char, char),
+        len: usize,
+        depth: usize,
+        mut write_item: F,
+    ) -> Result<(), RuntimeError>
+    where
+        F: FnMut(&mut JsonWriter, usize) -> Result<(), RuntimeError>,
+    {
+        self.output.push(open);
+
+        for index in 0..len {
+            if index > 0 {
+                self.output.push(',');
+            }
+
+            self.write_line_break(depth + 1);
+            write_item(self, index)?;
+        }
+
+        if len > 0 {
+            self.write_line_break(depth);
+        }
+
+        self.output.push(close);
+        Ok(())
+    }
+
+    fn write_line_break(&mut self, depth: usize) {
+        if let Some(indent) = self.indent {
+            self.output.push('\n');
+            self.output.push_str(&" ".repeat(indent * depth));
+        }
+    }
+
+    fn write_string(&mut self, string: &str) {
+        self.output.push('"');
+
+        for c in string.chars() {
+            match c {
+                '"' => self.output.push_str("\\\""),
+                '\\' => self.output.push_str("\\\\"),
+                '\n' => self.output.push_str("\\n"),
+                '\r' => self.output.push_str("\\r"),
+                '\t' => self.output.push_str("\\t"),
+                '\u{8}' => self.output.push_str("\\b"),
+                '\u{c}' => self.output.push_str("\\f"),
+                c if (c as u32) < 0x20 => self.output.push_str(&format!("\\u{:04x}", c as u32)),
+                c => self.output.push(c),
+            }
+        }
+
+        self.output.push('"');
+    }
+}
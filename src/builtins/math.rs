@@ -0,0 +1,136 @@
+use crate::builtins::{define_native, expect_number};
+use crate::environment::Environment;
+use crate::function::NativeFunction;
+use crate::interpreter::{Interpreter, RuntimeError};
+use crate::token::{Token, TokenKind};
+
+type UnaryFn = fn(f64) -> f64;
+
+/// Fills in the globals of the `math` module
+pub fn define_module(globals: &mut Environment) {
+    let constants = [
+        ("pi", std::f64::consts::PI),
+        ("e", std::f64::consts::E),
+        ("inf", f64::INFINITY),
+        ("nan", f64::NAN),
+    ];
+
+    for (name, value) in constants {
+        globals.define(String::from(name), number(value));
+    }
+
+    let unary: [(&'static str, UnaryFn); 14] = [
+        ("sqrt", f64::sqrt),
+        ("floor", f64::floor),
+        ("ceil", f64::ceil),
+        ("abs", f64::abs),
+        ("sin", f64::sin),
+        ("cos", f64::cos),
+        ("tan", f64::tan),
+        ("asin", f64::asin),
+        ("acos", f64::acos),
+        ("atan", f64::atan),
+        ("exp", f64::exp),
+        ("log2", f64::log2),
+        ("log10", f64::log10),
+        ("trunc", f64::trunc),
+    ];
+
+    for (name, op) in unary {
+        let func = NativeFunction::new(name, 1..=1, move |_, args| {
+            Ok(number(op(expect_number(name, &args[0])?)))
+        });
+
+        define_native(globals, func);
+    }
+
+    define_native(globals, NativeFunction::new("atan2", 2..=2, atan2));
+    define_native(globals, NativeFunction::new("log", 1..=2, log));
+    define_native(globals, NativeFunction::new("round", 1..=2, round));
+    define_native(globals, NativeFunction::new("isnan", 1..=1, isnan));
+    define_native(globals, NativeFunction::new("isinf", 1..=1, isinf));
+    define_native(globals, NativeFunction::new("min", 1..=usize::MAX, min));
+    define_native(globals, NativeFunction::new("max", 1..=usize::MAX, max));
+}
+
+fn number(value: f64) -> Token {
+    Token::from(TokenKind::Number(value))
+}
+
+/// `atan2(y, x)`, the angle from the x axis to the point (x, y)
+fn atan2(_: &mut Interpreter, args: Vec<Token>) -> Result<Token, RuntimeError> {
+    let y = expect_number("atan2", &args[0])?;
+    let x = expect_number("atan2", &args[1])?;
+
+    Ok(number(y.atan2(x)))
+}
+
+/// `log(x)` or `log(x, base)`, the natural logarithm of x unless another base is given
+fn log(_: &mut Interpreter, args: Vec<Token>) -> Result<Token, RuntimeError> {
+    let value = expect_number("log", &args[0])?;
+
+    match args.get(1) {
+        Some(base) => Ok(number(value.log(expect_number("log", base)?))),
+        None => Ok(number(value.ln())),
+    }
+}
+
+/// `round(x)` or `round(x, digits)`, rounding halfway cases away from zero
+fn round(_: &mut Interpreter, args: Vec<Token>) -> Result<Token, RuntimeError> {
+    let value = expect_number("round", &args[0])?;
+
+    match args.get(1) {
+        Some(digits) => {
+            let scale = 10f64.powf(expect_number("round", digits)?.trunc());
+            Ok(number((value * scale).round() / scale))
+        }
+
+        None => Ok(number(value.round())),
+    }
+}
+
+fn isnan(_: &mut Interpreter, args: Vec<Token>) -> Result<Token, RuntimeError> {
+    let value = expect_number("isnan", &args[0])?;
+    Ok(Token::from(TokenKind::Bool(value.is_nan())))
+}
+
+fn isinf(_: &mut Interpreter, args: Vec<Token>) -> Result<Token, RuntimeError> {
+    let value = expect_number("isinf", &args[0])?;
+    Ok(Token::from(TokenKind::Bool(value.is_infinite())))
+}
+
+/// `min(a, b, ...)` or `min(list)`
+fn min(_: &mut Interpreter, args: Vec<Token>) -> Result<Token, RuntimeError> {
+    let values = numbers("min", args)?;
+    Ok(number(values.into_iter().fold(f64::INFINITY, f64::min)))
+}
+
+/// `max(a, b, ...)` or `max(list)`
+fn max(_: &mut Interpreter, args: Vec<Token>) -> Result<Token, RuntimeError> {
+    let values = numbers("max", args)?;
+    Ok(number(values.into_iter().fold(f64::NEG_INFINITY, f64::max)))
+}
+
+/// Collects the numbers passed to a function either as separate arguments or as a single list
+fn numbers(func: &str, args: Vec<Token>) -> Result<Vec<f64>, RuntimeError> {
+    let values = match args.as_slice() {
+        [Token {
+            kind: TokenKind::List(list),
+            ..
+        }] => list.borrow().clone(),
+
+        _ => args,
+    };
+
+    if values.is_empty() {
+        return Err(RuntimeError::new(format!(
+            "Value error: {} expected at least one value.",
+            func
+        )));
+    }
+
+    values
+        .iter()
+        .map(|value| expect_number(func, value))
+        .collect()
+}
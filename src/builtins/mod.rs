@@ -0,0 +1,477 @@
+use std::cell::RefCell;
+use std::io::{self, BufRead, Read, Write};
+use std::rc::Rc;
+
+use crate::date::Date;
+use crate::environment::Environment;
+use crate::function::NativeFunction;
+use crate::generator::Generator;
+use crate::interpreter::{Interpreter, RuntimeError};
+use crate::map::Map;
+use crate::module::Module;
+use crate::set::Set;
+use crate::token::{Token, TokenKind};
+
+mod fs;
+mod functional;
+mod json;
+mod math;
+mod os;
+mod random;
+mod re;
+mod sys;
+mod time;
+
+/// Adds the functions available to every script to the global environment
+pub fn define_globals(globals: &mut Environment) {
+    define_native(globals, NativeFunction::new("range", 1..=3, range));
+    define_native(globals, NativeFunction::new("next", 1..=2, next));
+    define_native(globals, NativeFunction::new("len", 1..=1, len));
+    define_native(globals, NativeFunction::new("input", 0..=1, input));
+    define_native(
+        globals,
+        NativeFunction::new("read_all_stdin", 0..=0, read_all_stdin),
+    );
+    define_native(globals, NativeFunction::new("exit", 0..=1, exit));
+    define_native(globals, NativeFunction::new("set", 0..=1, set));
+    define_native(globals, NativeFunction::new("bytes", 0..=1, bytes));
+    define_native(globals, NativeFunction::new("from_hex", 1..=1, from_hex));
+
+    functional::define_globals(globals);
+}
+
+/// Creates the module implemented in Rust that `import name` refers to, if there is one
+pub fn native_module(interpreter: &Interpreter, name: &str) -> Option<Module> {
+    let mut globals = Environment::new();
+
+    match name {
+        "fs" => fs::define_module(&mut globals),
+        "json" => json::define_module(&mut globals),
+        "math" => math::define_module(&mut globals),
+        "os" => os::define_module(&mut globals),
+        "random" => random::define_module(&mut globals),
+        "re" => re::define_module(&mut globals),
+        "sys" => sys::define_module(&mut globals, interpreter.script_args()),
+        "time" => time::define_module(&mut globals),
+        _ => return None,
+    }
+
+    Some(Module::new(
+        String::from(name),
+        Rc::new(RefCell::new(globals)),
+    ))
+}
+
+/// Looks up a method of a map, bound to that map
+pub fn map_method(map: &Rc<RefCell<Map>>, name: &str) -> Option<NativeFunction> {
+    let map = Rc::clone(map);
+
+    let method = match name {
+        // `map.get(key, default)`, the value for a key or the default (none if not given)
+        "get" => NativeFunction::new("get", 1..=2, move |_, args| {
+            let value = map.borrow().get(&args[0])?;
+            let default = args.get(1).cloned();
+
+            Ok(value.or(default).unwrap_or(Token::from(TokenKind::None)))
+        }),
+
+        "contains" => NativeFunction::new("contains", 1..=1, move |_, args| {
+            let found = map.borrow().contains(&args[0])?;
+            Ok(Token::from(TokenKind::Bool(found)))
+        }),
+
+        // `map.remove(key)`, removing an entry and returning its value, or none if it was missing
+        "remove" => NativeFunction::new("remove", 1..=1, move |_, args| {
+            let value = map.borrow_mut().remove(&args[0])?;
+            Ok(value.unwrap_or(Token::from(TokenKind::None)))
+        }),
+
+        "keys" => NativeFunction::new("keys", 0..=0, move |_, _| {
+            let keys = map.borrow().keys();
+            Ok(Token::from(TokenKind::List(Rc::new(RefCell::new(keys)))))
+        }),
+
+        "values" => NativeFunction::new("values", 0..=0, move |_, _| {
+            let values = map.borrow().values();
+            Ok(Token::from(TokenKind::List(Rc::new(RefCell::new(values)))))
+        }),
+
+        _ => return None,
+    };
+
+    Some(method)
+}
+
+/// Looks up a method of some bytes, bound to them
+pub fn bytes_method(bytes: &Rc<Vec<u8>>, name: &str) -> Option<NativeFunction> {
+    let bytes = Rc::clone(bytes);
+
+    let method = match name {
+        // `bytes.hex()`, the bytes written as pairs of lowercase hex digits
+        "hex" => NativeFunction::new("hex", 0..=0, move |_, _| {
+            let hex: String = bytes.iter().map(|byte| format!("{:02x}", byte)).collect();
+            Ok(Token::from(TokenKind::String(hex)))
+        }),
+
+        // `bytes.decode()`, the string the bytes hold in UTF-8
+        "decode" => NativeFunction::new("decode", 0..=0, move |_, _| {
+            match String::from_utf8(bytes.to_vec()) {
+                Ok(text) => Ok(Token::from(TokenKind::String(text))),
+                Err(error) => Err(RuntimeError::new(format!(
+                    "Value error: bytes are not valid UTF-8, starting at byte {}.",
+                    error.utf8_error().valid_up_to()
+                ))),
+            }
+        }),
+
+        // `bytes.slice(start, end)`, the bytes from start up to but excluding end (the end if not
+        // given). Negative positions count back from the end.
+        "slice" => NativeFunction::new("slice", 1..=2, move |_, args| {
+            let len = bytes.len() as f64;
+
+            let mut bounds = [0.0, len];
+
+            for (bound, arg) in bounds.iter_mut().zip(args.iter()) {
+                let position = expect_number("slice", arg)?;
+
+                if position.fract() != 0.0 {
+                    return Err(RuntimeError::new(format!(
+                        "Type error: slice expected a whole Number but got {}.",
+                        position
+                    )));
+                }
+
+                let position = if position < 0.0 {
+                    position + len
+                } else {
+                    position
+                };
+                *bound = position.max(0.0).min(len);
+            }
+
+            let [start, end] = bounds;
+            let slice = bytes[start as usize..end.max(start) as usize].to_vec();
+
+            Ok(Token::from(TokenKind::Bytes(Rc::new(slice))))
+        }),
+
+        _ => return None,
+    };
+
+    Some(method)
+}
+
+/// Looks up a method of a set, bound to that set. The operations combining sets take anything
+/// that can be iterated over, and give back a new set.
+pub fn set_method(set: &Rc<RefCell<Set>>, name: &str) -> Option<NativeFunction> {
+    let set = Rc::clone(set);
+
+    let operation: fn(&Set, &Set) -> Result<Set, RuntimeError> = match name {
+        // `set.add(value)`, adding a value and returning whether it was new
+        "add" => {
+            return Some(NativeFunction::new("add", 1..=1, move |_, args| {
+                let added = set.borrow_mut().insert(args[0].clone())?;
+                Ok(Token::from(TokenKind::Bool(added)))
+            }));
+        }
+
+        // `set.remove(value)`, removing a value and returning whether it was there
+        "remove" => {
+            return Some(NativeFunction::new("remove", 1..=1, move |_, args| {
+                let removed = set.borrow_mut().remove(&args[0])?;
+                Ok(Token::from(TokenKind::Bool(removed)))
+            }));
+        }
+
+        "contains" => {
+            return Some(NativeFunction::new("contains", 1..=1, move |_, args| {
+                let found = set.borrow().contains(&args[0])?;
+                Ok(Token::from(TokenKind::Bool(found)))
+            }));
+        }
+
+        "union" => Set::union,
+        "intersection" => Set::intersection,
+        "difference" => Set::difference,
+        "symmetric_difference" => Set::symmetric_difference,
+
+        _ => return None,
+    };
+
+    Some(NativeFunction::new(
+        name,
+        1..=1,
+        move |interpreter, args| {
+            let other = to_set(interpreter, &args[0])?;
+            let result = operation(&set.borrow(), &other)?;
+
+            Ok(Token::from(TokenKind::Set(Rc::new(RefCell::new(result)))))
+        },
+    ))
+}
+
+/// Collects the values of anything that can be iterated over into a set
+fn to_set(interpreter: &mut Interpreter, value: &Token) -> Result<Set, RuntimeError> {
+    let mut iterator = interpreter.iterate(value.clone())?;
+    let mut set = Set::new();
+
+    while let Some(value) = iterator.next(interpreter)? {
+        set.insert(value)?;
+    }
+
+    Ok(set)
+}
+
+/// Looks up a field of a date. Offsets are in seconds, and weekdays count from 1 for Monday.
+pub fn date_field(date: &Date, name: &str) -> Option<f64> {
+    let value = match name {
+        "year" => date.year as f64,
+        "month" => date.month as f64,
+        "day" => date.day as f64,
+        "hour" => date.hour as f64,
+        "minute" => date.minute as f64,
+        "second" => date.second as f64,
+        "nanosecond" => date.nanosecond as f64,
+        "offset" => date.offset as f64,
+        "weekday" => date.weekday() as f64,
+        "timestamp" => date.timestamp(),
+        _ => return None,
+    };
+
+    Some(value)
+}
+
+fn define_native(environment: &mut Environment, func: NativeFunction) {
+    environment.define(
+        func.name.clone(),
+        Token::from(TokenKind::NativeFunction(Rc::new(func))),
+    );
+}
+
+pub fn expect_number(func: &str, value: &Token) -> Result<f64, RuntimeError> {
+    match value.to_number() {
+        Some(val) => Ok(val),
+        None => Err(RuntimeError::new(format!(
+            "Type error: {} expected a Number but got {:?}.",
+            func, value.kind
+        ))),
+    }
+}
+
+pub fn expect_string(func: &str, value: &Token) -> Result<String, RuntimeError> {
+    match &value.kind {
+        TokenKind::String(val) => Ok(val.clone()),
+        kind => Err(RuntimeError::new(format!(
+            "Type error: {} expected a String but got {:?}.",
+            func, kind
+        ))),
+    }
+}
+
+/// `range(stop)` or `range(start, stop, step)`, lazily counting from start up to but excluding stop
+fn range(_: &mut Interpreter, args: Vec<Token>) -> Result<Token, RuntimeError> {
+    let numbers = args
+        .iter()
+        .map(|arg| expect_number("range", arg))
+        .collect::<Result<Vec<f64>, RuntimeError>>()?;
+
+    let (start, stop, step) = match numbers.as_slice() {
+        [stop] => (0.0, *stop, 1.0),
+        [start, stop] => (*start, *stop, 1.0),
+        [start, stop, step] => (*start, *stop, *step),
+        _ => unreachable!(),
+    };
+
+    if step == 0.0 {
+        return Err(RuntimeError::new(String::from(
+            "Value error: range step must not be zero.",
+        )));
+    }
+
+    let mut current = start;
+
+    let generator = Generator::from_native("range", move |_| {
+        if (step > 0.0 && current >= stop) || (step < 0.0 && current <= stop) {
+            return Ok(None);
+        }
+
+        let value = current;
+        current += step;
+
+        Ok(Some(Token::from(TokenKind::Number(value))))
+    });
+
+    Ok(Token::from(TokenKind::Generator(Rc::new(RefCell::new(
+        generator,
+    )))))
+}
+
+/// `next(generator, default)`, resuming a generator and returning the value it produces
+fn next(interpreter: &mut Interpreter, args: Vec<Token>) -> Result<Token, RuntimeError> {
+    let generator = match &args[0].kind {
+        TokenKind::Generator(generator) => Rc::clone(generator),
+        kind => {
+            return Err(RuntimeError::new(format!(
+                "Type error: next expected a Generator but got {:?}.",
+                kind
+            )));
+        }
+    };
+
+    match Generator::resume(&generator, interpreter)? {
+        Some(value) => Ok(value),
+        None => match args.get(1) {
+            Some(default) => Ok(default.clone()),
+            None => Err(RuntimeError::new(format!(
+                "Iteration error: generator {} is exhausted.",
+                generator.borrow().name
+            ))),
+        },
+    }
+}
+
+/// `len(value)`, the number of characters in a string or elements in a list
+fn len(_: &mut Interpreter, args: Vec<Token>) -> Result<Token, RuntimeError> {
+    let len = match &args[0].kind {
+        TokenKind::String(val) => val.chars().count(),
+        TokenKind::List(list) => list.borrow().len(),
+        TokenKind::Map(map) => map.borrow().len(),
+        TokenKind::Set(set) => set.borrow().len(),
+        TokenKind::Bytes(bytes) => bytes.len(),
+
+        kind => {
+            return Err(RuntimeError::new(format!(
+                "Type error: len could not get the length of value of type {:?}.",
+                kind
+            )));
+        }
+    };
+
+    Ok(Token::from(TokenKind::Number(len as f64)))
+}
+
+/// `set(values)`, a set of the values of anything that can be iterated over, or an empty set
+fn set(interpreter: &mut Interpreter, args: Vec<Token>) -> Result<Token, RuntimeError> {
+    let set = match args.first() {
+        Some(values) => to_set(interpreter, values)?,
+        None => Set::new(),
+    };
+
+    Ok(Token::from(TokenKind::Set(Rc::new(RefCell::new(set)))))
+}
+
+/// `bytes(value)`, the UTF-8 encoding of a string, or bytes holding a list of numbers from 0 to 255
+fn bytes(interpreter: &mut Interpreter, args: Vec<Token>) -> Result<Token, RuntimeError> {
+    let bytes = match args.first().map(|arg| &arg.kind) {
+        None => Vec::new(),
+        Some(TokenKind::String(text)) => text.as_bytes().to_vec(),
+        Some(TokenKind::Bytes(bytes)) => {
+            return Ok(Token::from(TokenKind::Bytes(Rc::clone(bytes))))
+        }
+
+        Some(..) => {
+            let mut iterator = interpreter.iterate(args[0].clone())?;
+            let mut bytes = Vec::new();
+
+            while let Some(value) = iterator.next(interpreter)? {
+                match value.kind {
+                    TokenKind::Number(val)
+                        if val.fract() == 0.0 && (0.0..=255.0).contains(&val) =>
+                    {
+                        bytes.push(val as u8)
+                    }
+
+                    kind => {
+                        return Err(RuntimeError::new(format!(
+                            "Value error: bytes expected whole Numbers from 0 to 255 but got {:?}.",
+                            kind
+                        )));
+                    }
+                }
+            }
+
+            bytes
+        }
+    };
+
+    Ok(Token::from(TokenKind::Bytes(Rc::new(bytes))))
+}
+
+/// `from_hex(text)`, the bytes written as pairs of hex digits, ignoring whitespace between them
+fn from_hex(_: &mut Interpreter, args: Vec<Token>) -> Result<Token, RuntimeError> {
+    let text = expect_string("from_hex", &args[0])?;
+    let digits: Vec<char> = text.chars().filter(|c| !c.is_whitespace()).collect();
+
+    let bytes = digits
+        .chunks(2)
+        .map(|pair| {
+            let pair: String = pair.iter().collect();
+
+            match u8::from_str_radix(&pair, 16) {
+                Ok(byte) if pair.len() == 2 => Ok(byte),
+                _ => Err(RuntimeError::new(format!(
+                    "Value error: invalid hex string '{}'.",
+                    text
+                ))),
+            }
+        })
+        .collect::<Result<Vec<u8>, RuntimeError>>()?;
+
+    Ok(Token::from(TokenKind::Bytes(Rc::new(bytes))))
+}
+
+/// `input(prompt)`, reading a line from stdin without its line ending, or none at the end of input
+fn input(_: &mut Interpreter, args: Vec<Token>) -> Result<Token, RuntimeError> {
+    if let Some(prompt) = args.first() {
+        print!("{}", expect_string("input", prompt)?);
+        let _ = io::stdout().flush();
+    }
+
+    let mut line = String::new();
+
+    match io::stdin().lock().read_line(&mut line) {
+        Ok(0) => Ok(Token::from(TokenKind::None)),
+
+        Ok(..) => {
+            let len = line.trim_end_matches(['\n', '\r']).len();
+            line.truncate(len);
+
+            Ok(Token::from(TokenKind::String(line)))
+        }
+
+        Err(..) => Err(RuntimeError::new(String::from(
+            "IO error: failed to read from stdin.",
+        ))),
+    }
+}
+
+/// `read_all_stdin()`, reading everything left in stdin
+fn read_all_stdin(_: &mut Interpreter, _: Vec<Token>) -> Result<Token, RuntimeError> {
+    let mut contents = String::new();
+
+    match io::stdin().read_to_string(&mut contents) {
+        Ok(..) => Ok(Token::from(TokenKind::String(contents))),
+        Err(..) => Err(RuntimeError::new(String::from(
+            "IO error: failed to read from stdin.",
+        ))),
+    }
+}
+
+/// `exit(code)`, stopping the script with the given exit status from 0 to 255, or 0 if none is
+/// given
+fn exit(_: &mut Interpreter, args: Vec<Token>) -> Result<Token, RuntimeError> {
+    let code = match args.first() {
+        Some(code) => expect_number("exit", code)?,
+        None => 0.0,
+    };
+
+    // Processes can only report a status from 0 to 255, and larger codes would wrap around
+    if code.fract() != 0.0 || !(0.0..=255.0).contains(&code) {
+        return Err(RuntimeError::new(format!(
+            "Value error: exit code must be a whole number from 0 to 255, found {}.",
+            code
+        )));
+    }
+
+    Err(RuntimeError::exit(code as i32))
+}
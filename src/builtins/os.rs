@@ -0,0 +1,199 @@
+use std::cell::RefCell;
+use std::env;
+use std::io::{self, Read, Write};
+use std::process::{Command, Stdio};
+use std::rc::Rc;
+use std::thread;
+
+use crate::builtins::{define_native, expect_string};
+use crate::environment::Environment;
+use crate::function::NativeFunction;
+use crate::interpreter::{Interpreter, RuntimeError};
+use crate::map::Map;
+use crate::token::{Token, TokenKind};
+
+/// Fills in the globals of the `os` module
+pub fn define_module(globals: &mut Environment) {
+    define_native(globals, NativeFunction::new("getenv", 1..=2, getenv));
+    define_native(globals, NativeFunction::new("setenv", 2..=2, setenv));
+    define_native(globals, NativeFunction::new("cwd", 0..=0, cwd));
+    define_native(globals, NativeFunction::new("chdir", 1..=1, chdir));
+    define_native(globals, NativeFunction::new("run", 1..=3, run));
+}
+
+fn string(value: String) -> Token {
+    Token::from(TokenKind::String(value))
+}
+
+/// `getenv(name, default)`, the value of an environment variable, or the default (none if not
+/// given) when it isn't set
+fn getenv(interpreter: &mut Interpreter, args: Vec<Token>) -> Result<Token, RuntimeError> {
+    let name = expect_string("getenv", &args[0])?;
+
+    match interpreter.env_var(&name) {
+        Some(value) => Ok(string(value)),
+        None => Ok(args.get(1).cloned().unwrap_or(Token::from(TokenKind::None))),
+    }
+}
+
+/// `setenv(name, value)`, setting an environment variable for the script and the programs it
+/// runs. The interpreter keeps it to itself, so the host process's environment is left alone.
+fn setenv(interpreter: &mut Interpreter, args: Vec<Token>) -> Result<Token, RuntimeError> {
+    let name = expect_string("setenv", &args[0])?;
+    let value = expect_string("setenv", &args[1])?;
+
+    if name.is_empty() || name.contains(['=', '\0']) || value.contains('\0') {
+        return Err(RuntimeError::new(format!(
+            "Value error: invalid environment variable '{}'.",
+            name
+        )));
+    }
+
+    interpreter.set_env_var(name, value);
+    Ok(Token::from(TokenKind::None))
+}
+
+/// `cwd()`, the current working directory
+fn cwd(_: &mut Interpreter, _: Vec<Token>) -> Result<Token, RuntimeError> {
+    match env::current_dir() {
+        Ok(path) => Ok(string(path.display().to_string())),
+        Err(..) => Err(RuntimeError::new(String::from(
+            "IO error: could not get the current directory.",
+        ))),
+    }
+}
+
+/// `chdir(path)`, changing the current working directory
+fn chdir(_: &mut Interpreter, args: Vec<Token>) -> Result<Token, RuntimeError> {
+    let path = expect_string("chdir", &args[0])?;
+
+    match env::set_current_dir(&path) {
+        Ok(()) => Ok(Token::from(TokenKind::None)),
+        Err(..) => Err(RuntimeError::new(format!(
+            "IO error: could not change directory to '{}'.",
+            path
+        ))),
+    }
+}
+
+/// `run(cmd, args, stream)`, running a program and waiting for it to finish. Returns a map of its
+/// `stdout`, `stderr` and exit `status`, along with `ok` for whether it succeeded and `error`
+/// describing why it couldn't be started, if it couldn't. When `stream` is true the program's
+/// output is also passed through as it is written.
+fn run(interpreter: &mut Interpreter, args: Vec<Token>) -> Result<Token, RuntimeError> {
+    let program = expect_string("run", &args[0])?;
+
+    let program_args = match args.get(1).map(|arg| &arg.kind) {
+        None | Some(TokenKind::None) => Vec::new(),
+
+        Some(TokenKind::List(list)) => list
+            .borrow()
+            .iter()
+            .map(|arg| expect_string("run", arg))
+            .collect::<Result<Vec<String>, RuntimeError>>()?,
+
+        Some(kind) => {
+            return Err(RuntimeError::new(format!(
+                "Type error: run expected a List of arguments but got {:?}.",
+                kind
+            )));
+        }
+    };
+
+    let stream = match args.get(2) {
+        Some(stream) => stream.to_bool().unwrap_or(false),
+        None => false,
+    };
+
+    let child = Command::new(&program)
+        .args(&program_args)
+        .envs(interpreter.env_vars())
+        .stdin(Stdio::inherit())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn();
+
+    let mut child = match child {
+        Ok(child) => child,
+        Err(error) => {
+            return result(
+                String::new(),
+                String::new(),
+                None,
+                Some(format!("could not run command '{}': {}", program, error)),
+            );
+        }
+    };
+
+    // Both pipes are read at once, so that a program filling one can't block waiting on the other
+    let stderr = child.stderr.take().unwrap();
+    let stderr = thread::spawn(move || capture(stderr, stream.then(io::stderr)));
+    let stdout = capture(child.stdout.take().unwrap(), stream.then(io::stdout));
+    let stderr = stderr.join().unwrap_or_default();
+
+    match child.wait() {
+        Ok(status) => result(stdout, stderr, status.code(), None),
+        Err(error) => result(
+            stdout,
+            stderr,
+            None,
+            Some(format!(
+                "could not wait for command '{}': {}",
+                program, error
+            )),
+        ),
+    }
+}
+
+/// Reads everything from a pipe, copying it to `echo` as it arrives if given
+fn capture<R: Read, W: Write>(mut pipe: R, mut echo: Option<W>) -> String {
+    let mut output = Vec::new();
+    let mut buffer = [0; 4096];
+
+    while let Ok(len) = pipe.read(&mut buffer) {
+        if len == 0 {
+            break;
+        }
+
+        if let Some(echo) = &mut echo {
+            let _ = echo.write_all(&buffer[..len]);
+            let _ = echo.flush();
+        }
+
+        output.extend_from_slice(&buffer[..len]);
+    }
+
+    String::from_utf8_lossy(&output).into_owned()
+}
+
+fn result(
+    stdout: String,
+    stderr: String,
+    status: Option<i32>,
+    error: Option<String>,
+) -> Result<Token, RuntimeError> {
+    let none = || Token::from(TokenKind::None);
+
+    let status = match status {
+        Some(code) => Token::from(TokenKind::Number(code as f64)),
+        None => none(),
+    };
+
+    let ok = status.kind == TokenKind::Number(0.0);
+
+    let fields = [
+        ("stdout", string(stdout)),
+        ("stderr", string(stderr)),
+        ("status", status),
+        ("ok", Token::from(TokenKind::Bool(ok))),
+        ("error", error.map(string).unwrap_or_else(none)),
+    ];
+
+    let mut map = Map::new();
+
+    for (name, value) in fields {
+        map.insert(string(String::from(name)), value)?;
+    }
+
+    Ok(Token::from(TokenKind::Map(Rc::new(RefCell::new(map)))))
+}
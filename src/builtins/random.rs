@@ -0,0 +1,176 @@
+use std::cell::RefCell;
+use std::rc::Rc;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::builtins::{define_native, expect_number};
+use crate::environment::Environment;
+use crate::function::NativeFunction;
+use crate::interpreter::RuntimeError;
+use crate::token::{Token, TokenKind};
+
+/// A xoshiro256** generator. It's written out here rather than taken from the platform so that a
+/// seed gives the same numbers everywhere.
+struct Rng {
+    state: [u64; 4],
+}
+
+impl Rng {
+    fn new(seed: u64) -> Rng {
+        // The state is filled from the seed with splitmix64, which never leaves it all zeros
+        let mut seed = seed;
+        let mut state = [0; 4];
+
+        for word in state.iter_mut() {
+            seed = seed.wrapping_add(0x9e3779b97f4a7c15);
+
+            let mut z = seed;
+            z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+            z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+            *word = z ^ (z >> 31);
+        }
+
+        Rng { state }
+    }
+
+    fn next(&mut self) -> u64 {
+        let s = &mut self.state;
+        let result = s[1].wrapping_mul(5).rotate_left(7).wrapping_mul(9);
+        let t = s[1] << 17;
+
+        s[2] ^= s[0];
+        s[3] ^= s[1];
+        s[1] ^= s[2];
+        s[0] ^= s[3];
+        s[2] ^= t;
+        s[3] = s[3].rotate_left(45);
+
+        result
+    }
+
+    /// A number in [0, 1), using the top 53 bits so every value is equally likely
+    fn next_float(&mut self) -> f64 {
+        (self.next() >> 11) as f64 / (1u64 << 53) as f64
+    }
+
+    /// A number in [0, bound), without favouring any by rejecting the uneven top of the range
+    fn below(&mut self, bound: u64) -> u64 {
+        let limit = u64::MAX - u64::MAX % bound;
+
+        loop {
+            let value = self.next();
+
+            if value < limit {
+                return value % bound;
+            }
+        }
+    }
+}
+
+/// Fills in the globals of the `random` module. Its functions share one generator, seeded from
+/// the clock until `seed` is called.
+pub fn define_module(globals: &mut Environment) {
+    let clock = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_nanos() as u64)
+        .unwrap_or_default();
+
+    let rng = Rc::new(RefCell::new(Rng::new(clock)));
+
+    // `seed(n)`, restarting the sequence of numbers so that the same seed always gives the same one
+    let generator = Rc::clone(&rng);
+    let seed = NativeFunction::new("seed", 1..=1, move |_, args| {
+        let seed = expect_number("seed", &args[0])?;
+
+        // Whole seeds are used as integers, so that 2 and -2 aren't near-identical bit patterns
+        let seed = if seed.fract() == 0.0 && seed.abs() < 9.2e18 {
+            seed as i64 as u64
+        } else {
+            seed.to_bits()
+        };
+
+        *generator.borrow_mut() = Rng::new(seed);
+        Ok(Token::from(TokenKind::None))
+    });
+
+    // `random()`, a number from 0 up to but excluding 1
+    let generator = Rc::clone(&rng);
+    let random = NativeFunction::new("random", 0..=0, move |_, _| {
+        let value = generator.borrow_mut().next_float();
+        Ok(Token::from(TokenKind::Number(value)))
+    });
+
+    // `randint(a, b)`, a whole number from a to b, including both
+    let generator = Rc::clone(&rng);
+    let randint = NativeFunction::new("randint", 2..=2, move |_, args| {
+        let low = expect_integer("randint", &args[0])?;
+        let high = expect_integer("randint", &args[1])?;
+
+        if low > high {
+            return Err(RuntimeError::new(format!(
+                "Value error: randint expected a range with {} no greater than {}.",
+                low, high
+            )));
+        }
+
+        let offset = generator.borrow_mut().below((high - low) as u64 + 1);
+        Ok(Token::from(TokenKind::Number((low + offset as i64) as f64)))
+    });
+
+    // `choice(list)`, a randomly picked element of a list
+    let generator = Rc::clone(&rng);
+    let choice = NativeFunction::new("choice", 1..=1, move |_, args| {
+        let list = expect_list("choice", &args[0])?;
+        let list = list.borrow();
+
+        if list.is_empty() {
+            return Err(RuntimeError::new(String::from(
+                "Index error: choice can't pick from an empty list.",
+            )));
+        }
+
+        let index = generator.borrow_mut().below(list.len() as u64) as usize;
+        Ok(list[index].clone())
+    });
+
+    // `shuffle(list)`, putting the elements of a list in a random order in place
+    let generator = Rc::clone(&rng);
+    let shuffle = NativeFunction::new("shuffle", 1..=1, move |_, args| {
+        let list = expect_list("shuffle", &args[0])?;
+        let mut list = list.borrow_mut();
+        let mut generator = generator.borrow_mut();
+
+        for index in (1..list.len()).rev() {
+            let other = generator.below(index as u64 + 1) as usize;
+            list.swap(index, other);
+        }
+
+        Ok(Token::from(TokenKind::None))
+    });
+
+    for func in [seed, random, randint, choice, shuffle] {
+        define_native(globals, func);
+    }
+}
+
+fn expect_integer(func: &str, value: &Token) -> Result<i64, RuntimeError> {
+    let number = expect_number(func, value)?;
+
+    if number.fract() != 0.0 || number.abs() > 9.0e15 {
+        return Err(RuntimeError::new(format!(
+            "Type error: {} expected a whole number but got {}.",
+            func, number
+        )));
+    }
+
+    Ok(number as i64)
+}
+
+fn expect_list(func: &str, value: &Token) -> Result<Rc<RefCell<Vec<Token>>>, RuntimeError> {
+    match &value.kind {
+        TokenKind::List(list) => Ok(Rc::clone(list)),
+        kind => Err(RuntimeError::new(format!(
+            "Type error: {} expected a List but got {:?}.",
+            func, kind
+        ))),
+    }
+}
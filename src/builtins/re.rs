@@ -0,0 +1,293 @@
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use crate::builtins::{define_native, expect_number, expect_string};
+use crate::environment::Environment;
+use crate::function::NativeFunction;
+use crate::interpreter::{Interpreter, RuntimeError};
+use crate::map::Map;
+use crate::regex::{Captures, Regex};
+use crate::token::{Token, TokenKind};
+
+/// Fills in the globals of the `re` module. Matches are maps holding the matched text as `match`,
+/// its character positions as `start` and `end`, a list of the groups as `groups`, and a map of
+/// the named groups as `named`. Groups that didn't take part in the match are none.
+pub fn define_module(globals: &mut Environment) {
+    define_native(globals, NativeFunction::new("match", 2..=2, match_start));
+    define_native(globals, NativeFunction::new("search", 2..=2, search));
+    define_native(globals, NativeFunction::new("find_all", 2..=2, find_all));
+    define_native(globals, NativeFunction::new("replace", 3..=4, replace));
+    define_native(globals, NativeFunction::new("split", 2..=3, split));
+}
+
+fn compile(func: &str, pattern: &Token) -> Result<Regex, RuntimeError> {
+    let pattern = expect_string(func, pattern)?;
+
+    Regex::new(&pattern).map_err(|message| {
+        RuntimeError::new(format!(
+            "Value error: invalid regular expression '{}': {}.",
+            pattern, message
+        ))
+    })
+}
+
+fn string(text: &[char]) -> Token {
+    Token::from(TokenKind::String(text.iter().collect()))
+}
+
+fn group(text: &[char], captures: &Captures, index: usize) -> Token {
+    match captures.get(index) {
+        Some((start, end)) => string(&text[start..end]),
+        None => Token::from(TokenKind::None),
+    }
+}
+
+fn list(elements: Vec<Token>) -> Token {
+    Token::from(TokenKind::List(Rc::new(RefCell::new(elements))))
+}
+
+fn match_value(regex: &Regex, text: &[char], captures: &Captures) -> Result<Token, RuntimeError> {
+    let (start, end) = captures.get(0).unwrap();
+
+    let groups = (1..regex.groups())
+        .map(|index| group(text, captures, index))
+        .collect();
+
+    let mut names: Vec<(&String, &usize)> = regex.names().iter().collect();
+    names.sort_by_key(|(_, index)| **index);
+
+    let mut named = Map::new();
+
+    for (name, index) in names {
+        named.insert(
+            Token::from(TokenKind::String(name.clone())),
+            group(text, captures, *index),
+        )?;
+    }
+
+    let fields = [
+        ("match", string(&text[start..end])),
+        ("start", Token::from(TokenKind::Number(start as f64))),
+        ("end", Token::from(TokenKind::Number(end as f64))),
+        ("groups", list(groups)),
+        (
+            "named",
+            Token::from(TokenKind::Map(Rc::new(RefCell::new(named)))),
+        ),
+    ];
+
+    let mut map = Map::new();
+
+    for (name, value) in fields {
+        map.insert(Token::from(TokenKind::String(String::from(name))), value)?;
+    }
+
+    Ok(Token::from(TokenKind::Map(Rc::new(RefCell::new(map)))))
+}
+
+/// `match(pattern, text)`, a match at the very start of the text, or none
+fn match_start(_: &mut Interpreter, args: Vec<Token>) -> Result<Token, RuntimeError> {
+    let regex = compile("match", &args[0])?;
+    let text: Vec<char> = expect_string("match", &args[1])?.chars().collect();
+
+    match regex.match_at(&text, 0) {
+        Some(captures) => match_value(&regex, &text, &captures),
+        None => Ok(Token::from(TokenKind::None)),
+    }
+}
+
+/// `search(pattern, text)`, the first match anywhere in the text, or none
+fn search(_: &mut Interpreter, args: Vec<Token>) -> Result<Token, RuntimeError> {
+    let regex = compile("search", &args[0])?;
+    let text: Vec<char> = expect_string("search", &args[1])?.chars().collect();
+
+    match regex.search(&text, 0) {
+        Some(captures) => match_value(&regex, &text, &captures),
+        None => Ok(Token::from(TokenKind::None)),
+    }
+}
+
+/// `find_all(pattern, text)`, every match in the text. Each is the matched text if the pattern
+/// has no groups, the group's text if it has one, and a list of the groups if it has more.
+fn find_all(_: &mut Interpreter, args: Vec<Token>) -> Result<Token, RuntimeError> {
+    let regex = compile("find_all", &args[0])?;
+    let text: Vec<char> = expect_string("find_all", &args[1])?.chars().collect();
+
+    let matches = regex
+        .find_all(&text)
+        .iter()
+        .map(|captures| match regex.groups() {
+            1 => group(&text, captures, 0),
+            2 => group(&text, captures, 1),
+            groups => list(
+                (1..groups)
+                    .map(|index| group(&text, captures, index))
+                    .collect(),
+            ),
+        })
+        .collect();
+
+    Ok(list(matches))
+}
+
+/// `replace(pattern, text, replacement, count)`, the text with matches replaced, all of them or
+/// only the first `count`. The replacement is either a function taking the match, or a string
+/// where `$1` or `${1}` stands for a group, `${name}` for a named group, and `$$` for a `$`.
+fn replace(interpreter: &mut Interpreter, args: Vec<Token>) -> Result<Token, RuntimeError> {
+    let regex = compile("replace", &args[0])?;
+    let text: Vec<char> = expect_string("replace", &args[1])?.chars().collect();
+
+    let count = match args.get(3) {
+        Some(count) => expect_number("replace", count)?.max(0.0) as usize,
+        None => 0,
+    };
+
+    let mut matches = regex.find_all(&text);
+
+    if count > 0 {
+        matches.truncate(count);
+    }
+
+    let mut output = String::new();
+    let mut last = 0;
+
+    for captures in matches {
+        let (start, end) = captures.get(0).unwrap();
+        output.extend(&text[last..start]);
+
+        match &args[2].kind {
+            TokenKind::String(template) => {
+                expand(&regex, &text, &captures, template, &mut output)?;
+            }
+
+            _ => {
+                let found = match_value(&regex, &text, &captures)?;
+
+                match interpreter.call(args[2].clone(), vec![found])?.kind {
+                    TokenKind::String(replacement) => output.push_str(&replacement),
+                    kind => {
+                        return Err(RuntimeError::new(format!(
+                            "Type error: replace expected the replacement function to return a String but got {:?}.",
+                            kind
+                        )));
+                    }
+                }
+            }
+        }
+
+        last = end;
+    }
+
+    output.extend(&text[last..]);
+    Ok(Token::from(TokenKind::String(output)))
+}
+
+/// Writes a replacement string, filling in the groups it refers to
+fn expand(
+    regex: &Regex,
+    text: &[char],
+    captures: &Captures,
+    template: &str,
+    output: &mut String,
+) -> Result<(), RuntimeError> {
+    let template: Vec<char> = template.chars().collect();
+    let mut position = 0;
+
+    while position < template.len() {
+        let c = template[position];
+        position += 1;
+
+        if c != '$' {
+            output.push(c);
+            continue;
+        }
+
+        let reference: String = match template.get(position) {
+            Some('$') => {
+                output.push('$');
+                position += 1;
+                continue;
+            }
+
+            Some('{') => {
+                let end = template[position..].iter().position(|c| *c == '}');
+
+                match end {
+                    Some(end) => {
+                        let name = template[position + 1..position + end].iter().collect();
+                        position += end + 1;
+                        name
+                    }
+                    None => {
+                        return Err(RuntimeError::new(String::from(
+                            "Value error: replacement has a '${' without a closing '}'.",
+                        )));
+                    }
+                }
+            }
+
+            _ => {
+                let digits = template[position..]
+                    .iter()
+                    .take_while(|c| c.is_ascii_digit())
+                    .count();
+
+                position += digits;
+                template[position - digits..position].iter().collect()
+            }
+        };
+
+        let index = match reference.parse::<usize>() {
+            Ok(index) if index < regex.groups() => Some(index),
+            Ok(..) => None,
+            Err(..) => regex.names().get(&reference).copied(),
+        };
+
+        match index {
+            Some(index) => {
+                if let Some((start, end)) = captures.get(index) {
+                    output.extend(&text[start..end]);
+                }
+            }
+
+            None => {
+                return Err(RuntimeError::new(format!(
+                    "Value error: replacement refers to unknown group '{}'.",
+                    reference
+                )));
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// `split(pattern, text, max)`, the pieces of the text between matches, splitting at all of them
+/// or only the first `max`
+fn split(_: &mut Interpreter, args: Vec<Token>) -> Result<Token, RuntimeError> {
+    let regex = compile("split", &args[0])?;
+    let text: Vec<char> = expect_string("split", &args[1])?.chars().collect();
+
+    let max = match args.get(2) {
+        Some(max) => expect_number("split", max)?.max(0.0) as usize,
+        None => 0,
+    };
+
+    let mut matches = regex.find_all(&text);
+
+    if max > 0 {
+        matches.truncate(max);
+    }
+
+    let mut pieces = Vec::new();
+    let mut last = 0;
+
+    for captures in matches {
+        let (start, end) = captures.get(0).unwrap();
+        pieces.push(string(&text[last..start]));
+        last = end;
+    }
+
+    pieces.push(string(&text[last..]));
+    Ok(list(pieces))
+}
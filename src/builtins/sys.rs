@@ -0,0 +1,19 @@
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use crate::environment::Environment;
+use crate::token::{Token, TokenKind};
+
+/// Fills in the globals of the `sys` module, with `args` holding the command line arguments given
+/// after the script's path
+pub fn define_module(globals: &mut Environment, args: &[String]) {
+    let args = args
+        .iter()
+        .map(|arg| Token::from(TokenKind::String(arg.clone())))
+        .collect();
+
+    globals.define(
+        String::from("args"),
+        Token::from(TokenKind::List(Rc::new(RefCell::new(args)))),
+    );
+}
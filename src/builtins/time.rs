@@ -0,0 +1,126 @@
+use std::thread;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use crate::builtins::{define_native, expect_number, expect_string};
+use crate::date::Date;
+use crate::environment::Environment;
+use crate::function::NativeFunction;
+use crate::interpreter::{Interpreter, RuntimeError};
+use crate::token::{Token, TokenKind};
+
+thread_local! {
+    // The point `monotonic` counts from, fixed the first time it's needed
+    static START: Instant = Instant::now();
+}
+
+/// Fills in the globals of the `time` module
+pub fn define_module(globals: &mut Environment) {
+    define_native(globals, NativeFunction::new("now", 0..=0, now));
+    define_native(globals, NativeFunction::new("monotonic", 0..=0, monotonic));
+    define_native(globals, NativeFunction::new("sleep", 1..=1, sleep));
+    define_native(globals, NativeFunction::new("date", 0..=2, date));
+    define_native(globals, NativeFunction::new("parse", 1..=1, parse));
+    define_native(globals, NativeFunction::new("format", 0..=1, format));
+}
+
+fn number(value: f64) -> Token {
+    Token::from(TokenKind::Number(value))
+}
+
+fn current_time() -> f64 {
+    match SystemTime::now().duration_since(UNIX_EPOCH) {
+        Ok(duration) => duration.as_secs_f64(),
+        Err(error) => -error.duration().as_secs_f64(),
+    }
+}
+
+/// `now()`, the wall clock time in seconds since the Unix epoch
+fn now(_: &mut Interpreter, _: Vec<Token>) -> Result<Token, RuntimeError> {
+    Ok(number(current_time()))
+}
+
+/// `monotonic()`, seconds from a fixed point which never goes backwards, for timing parts of a
+/// script
+fn monotonic(_: &mut Interpreter, _: Vec<Token>) -> Result<Token, RuntimeError> {
+    Ok(number(START.with(|start| start.elapsed().as_secs_f64())))
+}
+
+/// `sleep(seconds)`, pausing the script
+fn sleep(_: &mut Interpreter, args: Vec<Token>) -> Result<Token, RuntimeError> {
+    let seconds = expect_number("sleep", &args[0])?;
+
+    if !(seconds >= 0.0 && seconds.is_finite()) {
+        return Err(RuntimeError::new(format!(
+            "Value error: sleep expected a non-negative number of seconds but got {}.",
+            seconds
+        )));
+    }
+
+    thread::sleep(Duration::from_secs_f64(seconds));
+    Ok(Token::from(TokenKind::None))
+}
+
+/// `date(timestamp, offset)`, the date at a timestamp (now if not given) as seen `offset` seconds
+/// ahead of UTC (0 if not given)
+fn date(_: &mut Interpreter, args: Vec<Token>) -> Result<Token, RuntimeError> {
+    let timestamp = match args.first() {
+        Some(timestamp) => expect_number("date", timestamp)?,
+        None => current_time(),
+    };
+
+    let offset = match args.get(1) {
+        Some(offset) => expect_number("date", offset)?,
+        None => 0.0,
+    };
+
+    if !timestamp.is_finite() {
+        return Err(RuntimeError::new(format!(
+            "Value error: date expected a finite timestamp but got {}.",
+            timestamp
+        )));
+    }
+
+    if offset % 60.0 != 0.0 || offset.abs() >= 86400.0 {
+        return Err(RuntimeError::new(format!(
+            "Value error: date offset must be a whole number of minutes less than a day, but got {}.",
+            offset
+        )));
+    }
+
+    let date = Date::from_timestamp(timestamp, offset as i32);
+    Ok(Token::from(TokenKind::Date(date)))
+}
+
+/// `parse(text)`, reading an ISO-8601 timestamp into a date
+fn parse(_: &mut Interpreter, args: Vec<Token>) -> Result<Token, RuntimeError> {
+    let text = expect_string("parse", &args[0])?;
+
+    match Date::parse(&text) {
+        Ok(date) => Ok(Token::from(TokenKind::Date(date))),
+        Err(message) => Err(RuntimeError::new(format!(
+            "Value error: invalid ISO-8601 timestamp '{}': {}.",
+            text, message
+        ))),
+    }
+}
+
+/// `format(value)`, writing a date or a timestamp (now if not given) in ISO-8601 form.
+/// Timestamps are written in UTC.
+fn format(_: &mut Interpreter, args: Vec<Token>) -> Result<Token, RuntimeError> {
+    let date = match args.first().map(|arg| &arg.kind) {
+        None => Date::from_timestamp(current_time(), 0),
+        Some(TokenKind::Date(date)) => *date,
+        Some(TokenKind::Number(timestamp)) if timestamp.is_finite() => {
+            Date::from_timestamp(*timestamp, 0)
+        }
+
+        Some(kind) => {
+            return Err(RuntimeError::new(format!(
+                "Type error: format expected a Date or a timestamp but got {:?}.",
+                kind
+            )));
+        }
+    };
+
+    Ok(Token::from(TokenKind::String(date.to_string())))
+}
@@ -0,0 +1,99 @@
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::fmt;
+use std::rc::Rc;
+
+use crate::function::Function;
+use crate::token::{self, Token};
+
+pub struct Class {
+    pub name: String,
+    pub methods: HashMap<String, Rc<Function>>,
+}
+
+impl Class {
+    pub fn new(name: String, methods: HashMap<String, Rc<Function>>) -> Class {
+        Class { name, methods }
+    }
+
+    pub fn find_method(&self, name: &str) -> Option<Rc<Function>> {
+        self.methods.get(name).cloned()
+    }
+}
+
+impl fmt::Debug for Class {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "<class {}>", self.name)
+    }
+}
+
+impl PartialEq for Class {
+    fn eq(&self, other: &Class) -> bool {
+        std::ptr::eq(self, other)
+    }
+}
+
+pub struct Instance {
+    pub class: Rc<Class>,
+
+    // Fields are kept in insertion order so that instances print predictably
+    fields: RefCell<Vec<(String, Token)>>,
+}
+
+impl Instance {
+    pub fn new(class: Rc<Class>) -> Instance {
+        Instance {
+            class,
+            fields: RefCell::new(Vec::new()),
+        }
+    }
+
+    pub fn get_field(&self, name: &str) -> Option<Token> {
+        self.fields
+            .borrow()
+            .iter()
+            .find(|(field, _)| field == name)
+            .map(|(_, value)| value.clone())
+    }
+
+    pub fn set_field(&self, name: &str, value: Token) {
+        let mut fields = self.fields.borrow_mut();
+
+        match fields.iter_mut().find(|(field, _)| field == name) {
+            Some((_, slot)) => *slot = value,
+            None => fields.push((String::from(name), value)),
+        }
+    }
+
+    pub fn fields(&self) -> Vec<(String, Token)> {
+        self.fields.borrow().clone()
+    }
+}
+
+impl fmt::Display for Instance {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let written = token::write_once(self, || {
+            let fields: Vec<String> = self
+                .fields()
+                .iter()
+                .map(|(name, value)| format!("{}={}", name, value.to_string().unwrap_or_default()))
+                .collect();
+
+            format!("{}({})", self.class.name, fields.join(", "))
+        });
+
+        write!(f, "{}", written)
+    }
+}
+
+impl fmt::Debug for Instance {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "<{} instance>", self.class.name)
+    }
+}
+
+impl PartialEq for Instance {
+    fn eq(&self, other: &Instance) -> bool {
+        std::ptr::eq(self, other)
+    }
+}
@@ -0,0 +1,296 @@
+use std::fmt;
+
+/// A calendar date and time of day, at a fixed offset from UTC
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Date {
+    pub year: i64,
+    pub month: u32,
+    pub day: u32,
+    pub hour: u32,
+    pub minute: u32,
+    pub second: u32,
+    pub nanosecond: u32,
+
+    // Seconds ahead of UTC, always a whole number of minutes
+    pub offset: i32,
+}
+
+impl Date {
+    /// The date at `timestamp` seconds since the Unix epoch, as seen `offset` seconds ahead of UTC
+    pub fn from_timestamp(timestamp: f64, offset: i32) -> Date {
+        let local = timestamp + offset as f64;
+        let mut seconds = local.floor() as i64;
+        let mut nanosecond = ((local - local.floor()) * 1e9).round() as u32;
+
+        if nanosecond >= 1_000_000_000 {
+            seconds += 1;
+            nanosecond -= 1_000_000_000;
+        }
+
+        let (year, month, day) = civil_from_days(seconds.div_euclid(86400));
+        let time = seconds.rem_euclid(86400) as u32;
+
+        Date {
+            year,
+            month,
+            day,
+            hour: time / 3600,
+            minute: time / 60 % 60,
+            second: time % 60,
+            nanosecond,
+            offset,
+        }
+    }
+
+    /// Seconds since the Unix epoch
+    pub fn timestamp(&self) -> f64 {
+        let days = days_from_civil(self.year, self.month, self.day);
+        let seconds = days * 86400 + (self.hour * 3600 + self.minute * 60 + self.second) as i64
+            - self.offset as i64;
+
+        seconds as f64 + self.nanosecond as f64 / 1e9
+    }
+
+    /// The day of the week, from 1 for Monday to 7 for Sunday
+    pub fn weekday(&self) -> u32 {
+        // The epoch was a Thursday
+        (days_from_civil(self.year, self.month, self.day) + 3).rem_euclid(7) as u32 + 1
+    }
+
+    /// Reads an ISO-8601 timestamp such as `2024-01-31`, `2024-01-31T09:30:00Z` or
+    /// `2024-01-31 09:30:00.25+01:00`. Timestamps without an offset are taken to be in UTC.
+    pub fn parse(text: &str) -> Result<Date, String> {
+        let mut reader = Reader { text, position: 0 };
+
+        let year = reader.digits(4, "year")? as i64;
+        reader.expect('-')?;
+        let month = reader.digits(2, "month")?;
+        reader.expect('-')?;
+        let day = reader.digits(2, "day")?;
+
+        if !(1..=12).contains(&month) {
+            return Err(String::from("month out of range"));
+        }
+
+        if day < 1 || day > days_in_month(year, month) {
+            return Err(String::from("day out of range"));
+        }
+
+        let mut date = Date {
+            year,
+            month,
+            day,
+            hour: 0,
+            minute: 0,
+            second: 0,
+            nanosecond: 0,
+            offset: 0,
+        };
+
+        if reader.at_end() {
+            return Ok(date);
+        }
+
+        if !reader.accept('T') && !reader.accept(' ') {
+            return Err(reader.unexpected());
+        }
+
+        date.hour = reader.digits(2, "hour")?;
+        reader.expect(':')?;
+        date.minute = reader.digits(2, "minute")?;
+
+        if reader.accept(':') {
+            date.second = reader.digits(2, "second")?;
+
+            if reader.accept('.') {
+                date.nanosecond = reader.fraction()?;
+            }
+        }
+
+        if date.hour > 23 {
+            return Err(String::from("hour out of range"));
+        }
+
+        if date.minute > 59 {
+            return Err(String::from("minute out of range"));
+        }
+
+        if date.second > 59 {
+            return Err(String::from("second out of range"));
+        }
+
+        if let Some(sign) = reader.sign() {
+            let hours = reader.digits(2, "offset")?;
+            reader.accept(':');
+            let minutes = reader.digits(2, "offset")?;
+
+            if hours > 23 || minutes > 59 {
+                return Err(String::from("offset out of range"));
+            }
+
+            date.offset = sign * (hours * 3600 + minutes * 60) as i32;
+        } else {
+            reader.accept('Z');
+        }
+
+        if !reader.at_end() {
+            return Err(reader.unexpected());
+        }
+
+        Ok(date)
+    }
+}
+
+// Dates are written in ISO-8601 form, with as many fractional digits as needed
+impl fmt::Display for Date {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}",
+            self.year, self.month, self.day, self.hour, self.minute, self.second
+        )?;
+
+        if self.nanosecond != 0 {
+            let fraction = format!("{:09}", self.nanosecond);
+            write!(f, ".{}", fraction.trim_end_matches('0'))?;
+        }
+
+        if self.offset == 0 {
+            write!(f, "Z")
+        } else {
+            let sign = if self.offset < 0 { '-' } else { '+' };
+            let offset = self.offset.abs();
+            write!(f, "{}{:02}:{:02}", sign, offset / 3600, offset / 60 % 60)
+        }
+    }
+}
+
+struct Reader<'a> {
+    text: &'a str,
+    position: usize,
+}
+
+impl Reader<'_> {
+    fn peek(&self) -> Option<char> {
+        self.text[self.position..].chars().next()
+    }
+
+    fn at_end(&self) -> bool {
+        self.position == self.text.len()
+    }
+
+    fn accept(&mut self, expected: char) -> bool {
+        if self.peek() == Some(expected) {
+            self.position += expected.len_utf8();
+            true
+        } else {
+            false
+        }
+    }
+
+    fn expect(&mut self, expected: char) -> Result<(), String> {
+        if self.accept(expected) {
+            Ok(())
+        } else {
+            Err(self.unexpected())
+        }
+    }
+
+    fn sign(&mut self) -> Option<i32> {
+        if self.accept('+') {
+            Some(1)
+        } else if self.accept('-') {
+            Some(-1)
+        } else {
+            None
+        }
+    }
+
+    fn digits(&mut self, count: usize, field: &str) -> Result<u32, String> {
+        let mut value = 0;
+
+        for _ in 0..count {
+            match self.peek().and_then(|c| c.to_digit(10)) {
+                Some(digit) => {
+                    value = value * 10 + digit;
+                    self.position += 1;
+                }
+                None => return Err(format!("expected {} digits for the {}", count, field)),
+            }
+        }
+
+        Ok(value)
+    }
+
+    /// Reads the digits after a decimal point as nanoseconds, ignoring any past the ninth
+    fn fraction(&mut self) -> Result<u32, String> {
+        let mut value = 0;
+        let mut count = 0;
+
+        while let Some(digit) = self.peek().and_then(|c| c.to_digit(10)) {
+            if count < 9 {
+                value = value * 10 + digit;
+            }
+
+            count += 1;
+            self.position += 1;
+        }
+
+        if count == 0 {
+            return Err(String::from("expected digits after '.'"));
+        }
+
+        Ok(value * 10u32.pow(9 - count.min(9)))
+    }
+
+    fn unexpected(&self) -> String {
+        match self.peek() {
+            Some(c) => format!("unexpected '{}'", c),
+            None => String::from("unexpected end of text"),
+        }
+    }
+}
+
+fn is_leap_year(year: i64) -> bool {
+    year % 4 == 0 && (year % 100 != 0 || year % 400 == 0)
+}
+
+fn days_in_month(year: i64, month: u32) -> u32 {
+    match month {
+        2 if is_leap_year(year) => 29,
+        2 => 28,
+        4 | 6 | 9 | 11 => 30,
+        _ => 31,
+    }
+}
+
+// Days between the epoch and a date, counting in 400 year eras of the proleptic Gregorian
+// calendar, with years starting in March so that leap days fall at their end
+fn days_from_civil(year: i64, month: u32, day: u32) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year.rem_euclid(400);
+    let month = month as i64;
+
+    let day_of_year =
+        (153 * (if month > 2 { month - 3 } else { month + 9 }) + 2) / 5 + day as i64 - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+
+    era * 146097 + day_of_era - 719468
+}
+
+fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let days = days + 719468;
+    let era = days.div_euclid(146097);
+    let day_of_era = days.rem_euclid(146097);
+
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month = (5 * day_of_year + 2) / 153;
+    let day = (day_of_year - (153 * month + 2) / 5 + 1) as u32;
+    let month = if month < 10 { month + 3 } else { month - 9 } as u32;
+    let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+
+    (year, month, day)
+}
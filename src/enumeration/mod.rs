@@ -0,0 +1,112 @@
+use std::fmt;
+use std::rc::Rc;
+
+use crate::token::Token;
+
+pub struct Enumeration {
+    pub name: String,
+    pub variants: Vec<(String, Vec<String>)>,
+}
+
+impl Enumeration {
+    pub fn new(name: String, variants: Vec<(String, Vec<String>)>) -> Enumeration {
+        Enumeration { name, variants }
+    }
+
+    pub fn find_variant(&self, name: &str) -> Option<usize> {
+        self.variants
+            .iter()
+            .position(|(variant, _)| variant == name)
+    }
+
+    pub fn variant_name(&self, index: usize) -> &str {
+        &self.variants[index].0
+    }
+
+    pub fn variant_fields(&self, index: usize) -> &[String] {
+        &self.variants[index].1
+    }
+}
+
+impl fmt::Debug for Enumeration {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "<enum {}>", self.name)
+    }
+}
+
+impl PartialEq for Enumeration {
+    fn eq(&self, other: &Enumeration) -> bool {
+        std::ptr::eq(self, other)
+    }
+}
+
+/// A value of an enumeration, tagged with the index of the variant it was constructed as
+pub struct Variant {
+    pub enumeration: Rc<Enumeration>,
+    pub index: usize,
+    pub values: Vec<Token>,
+}
+
+impl Variant {
+    pub fn new(enumeration: Rc<Enumeration>, index: usize, values: Vec<Token>) -> Variant {
+        Variant {
+            enumeration,
+            index,
+            values,
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        self.enumeration.variant_name(self.index)
+    }
+
+    pub fn get_field(&self, name: &str) -> Option<Token> {
+        self.enumeration
+            .variant_fields(self.index)
+            .iter()
+            .position(|field| field == name)
+            .map(|index| self.values[index].clone())
+    }
+
+    pub fn is_variant_of(&self, enumeration: &Rc<Enumeration>, index: usize) -> bool {
+        Rc::ptr_eq(&self.enumeration, enumeration) && self.index == index
+    }
+}
+
+impl fmt::Display for Variant {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}.{}", self.enumeration.name, self.name())?;
+
+        let fields = self.enumeration.variant_fields(self.index);
+
+        if !fields.is_empty() {
+            let fields: Vec<String> = fields
+                .iter()
+                .zip(&self.values)
+                .map(|(name, value)| format!("{}={}", name, value.to_string().unwrap_or_default()))
+                .collect();
+
+            write!(f, "({})", fields.join(", "))?;
+        }
+
+        Ok(())
+    }
+}
+
+impl fmt::Debug for Variant {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "<{}.{}>", self.enumeration.name, self.name())
+    }
+}
+
+impl PartialEq for Variant {
+    fn eq(&self, other: &Variant) -> bool {
+        Rc::ptr_eq(&self.enumeration, &other.enumeration)
+            && self.index == other.index
+            && self
+                .values
+                .iter()
+                .zip(&other.values)
+                .all(|(lhs, rhs)| lhs.is_equal(rhs.clone()))
+    }
+}
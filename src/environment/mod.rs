@@ -0,0 +1,219 @@
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::rc::Rc;
+
+use crate::function::Function;
+use crate::token::{Token, TokenKind};
+
+enum Binding {
+    Value(Token),
+    Constant(Token),
+
+    // Declared with `global` or `nonlocal`, referring to the binding in another environment
+    Forward(Rc<RefCell<Environment>>),
+}
+
+pub enum AssignError {
+    Undefined,
+    Constant,
+}
+
+/// A single scope of variables. Every block gets its own environment, enclosed by the one it
+/// appears in, and function calls get one enclosed by the environment the function was defined in.
+#[derive(Default)]
+pub struct Environment {
+    values: HashMap<String, Binding>,
+    enclosing: Option<Rc<RefCell<Environment>>>,
+
+    // Whether this is the outermost scope of a function call
+    is_function: bool,
+}
+
+impl Environment {
+    pub fn new() -> Environment {
+        Environment::default()
+    }
+
+    pub fn from(enclosing: Rc<RefCell<Environment>>) -> Environment {
+        Environment {
+            values: HashMap::new(),
+            enclosing: Some(enclosing),
+            is_function: false,
+        }
+    }
+
+    pub fn for_function(closure: Rc<RefCell<Environment>>) -> Environment {
+        Environment {
+            is_function: true,
+            ..Environment::from(closure)
+        }
+    }
+
+    /// Removes every variable, breaking the reference cycles between an environment and the
+    /// functions defined in it. The values are dropped after the environment is released, as
+    /// dropping them can run code that needs it.
+    pub fn clear(environment: &Rc<RefCell<Environment>>) {
+        let values = std::mem::take(&mut environment.borrow_mut().values);
+        drop(values);
+    }
+
+    /// Lets go of an environment whose scope has ended. When nothing refers to it any more but
+    /// the functions and classes defined in it, which nothing else refers to either, the cycle
+    /// between them is broken so that they can be freed.
+    pub fn release(environment: Rc<RefCell<Environment>>) {
+        let owned: usize = environment
+            .borrow()
+            .values
+            .values()
+            .map(|binding| match binding {
+                Binding::Value(value) | Binding::Constant(value) => {
+                    owned_references(value, &environment)
+                }
+                Binding::Forward(..) => 0,
+            })
+            .sum();
+
+        if owned > 0 && Rc::strong_count(&environment) == owned + 1 {
+            Environment::clear(&environment);
+        }
+    }
+
+    pub fn define(&mut self, name: String, value: Token) {
+        self.values.insert(name, Binding::Value(value));
+    }
+
+    pub fn define_constant(&mut self, name: String, value: Token) {
+        self.values.insert(name, Binding::Constant(value));
+    }
+
+    /// Whether `name` is bound to a constant in this scope, ignoring enclosing ones
+    pub fn is_constant(&self, name: &str) -> bool {
+        matches!(self.values.get(name), Some(Binding::Constant(..)))
+    }
+
+    /// Makes `name` refer to its binding in `target` for the rest of this scope
+    pub fn forward(&mut self, name: String, target: Rc<RefCell<Environment>>) {
+        self.values.insert(name, Binding::Forward(target));
+    }
+
+    /// The values of the variables declared in this environment itself
+    pub fn values(&self) -> Vec<Token> {
+        self.values
+            .values()
+            .filter_map(|binding| match binding {
+                Binding::Value(value) | Binding::Constant(value) => Some(value.clone()),
+                Binding::Forward(..) => None,
+            })
+            .collect()
+    }
+
+    pub fn get(&self, name: &str) -> Option<Token> {
+        match self.values.get(name) {
+            Some(Binding::Value(value)) | Some(Binding::Constant(value)) => Some(value.clone()),
+            Some(Binding::Forward(target)) => target.borrow().get(name),
+
+            None => match &self.enclosing {
+                Some(enclosing) => enclosing.borrow().get(name),
+                None => None,
+            },
+        }
+    }
+
+    /// Updates the nearest binding of `name`
+    pub fn assign(&mut self, name: &str, value: Token) -> Result<(), AssignError> {
+        match self.values.get_mut(name) {
+            Some(Binding::Value(slot)) => {
+                *slot = value;
+                Ok(())
+            }
+
+            Some(Binding::Constant(..)) => Err(AssignError::Constant),
+            Some(Binding::Forward(target)) => target.borrow_mut().assign(name, value),
+
+            None => match &self.enclosing {
+                Some(enclosing) => enclosing.borrow_mut().assign(name, value),
+                None => Err(AssignError::Undefined),
+            },
+        }
+    }
+
+    pub fn contains(&self, name: &str) -> bool {
+        self.values.contains_key(name)
+    }
+
+    /// Finds the environment enclosing the function call that `environment` belongs to
+    pub fn closure_of(environment: &Rc<RefCell<Environment>>) -> Option<Rc<RefCell<Environment>>> {
+        let mut current = Rc::clone(environment);
+
+        loop {
+            let next = {
+                let env = current.borrow();
+
+                if env.is_function {
+                    return env.enclosing.clone();
+                }
+
+                env.enclosing.clone()?
+            };
+
+            current = next;
+        }
+    }
+
+    /// Finds the global environment of the script that `environment` belongs to
+    pub fn global_of(environment: &Rc<RefCell<Environment>>) -> Rc<RefCell<Environment>> {
+        let mut current = Rc::clone(environment);
+
+        loop {
+            let next = match &current.borrow().enclosing {
+                Some(enclosing) => Rc::clone(enclosing),
+                None => break,
+            };
+
+            current = next;
+        }
+
+        current
+    }
+
+    /// Finds the innermost environment, starting from `environment`, which binds `name`
+    pub fn resolve(
+        environment: &Rc<RefCell<Environment>>,
+        name: &str,
+    ) -> Option<Rc<RefCell<Environment>>> {
+        let mut current = Rc::clone(environment);
+
+        loop {
+            let next = {
+                let env = current.borrow();
+
+                if env.contains(name) {
+                    break;
+                }
+
+                env.enclosing.clone()?
+            };
+
+            current = next;
+        }
+
+        Some(current)
+    }
+}
+
+/// Counts the references to `environment` held by a value, as long as the value itself is only
+/// held by the variable it is in
+fn owned_references(value: &Token, environment: &Rc<RefCell<Environment>>) -> usize {
+    let closes_over =
+        |func: &Rc<Function>| Rc::strong_count(func) == 1 && Rc::ptr_eq(&func.closure, environment);
+
+    match &value.kind {
+        TokenKind::Function(func) if closes_over(func) => 1,
+        TokenKind::Type(class) if Rc::strong_count(class) == 1 => class
+            .methods
+            .values()
+            .filter(|method| closes_over(method))
+            .count(),
+        _ => 0,
+    }
+}
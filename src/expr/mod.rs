@@ -1,278 +1,440 @@
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use crate::interpreter::{Interpreter, RuntimeError};
+use crate::map::Map;
+use crate::set::Set;
 use crate::token::{Token, TokenKind};
 
 #[derive(Debug, PartialEq, Clone)]
 pub enum Expr {
     Unary(Token, Box<Expr>),
     Binary(Box<Expr>, Token, Box<Expr>),
+    Logical(Box<Expr>, Token, Box<Expr>),
     Literal(Token),
     Grouping(Token, Box<Expr>, Token),
+
+    List(Token, Vec<Expr>),
+    Map(Token, Vec<(Expr, Expr)>),
+    Set(Token, Vec<Expr>),
+
+    Call(Box<Expr>, Token, Vec<(Option<Token>, Expr)>),
+    Get(Box<Expr>, Token),
+    Index(Box<Expr>, Token, Box<Expr>),
 }
 
 impl Expr {
-    pub fn evaluate(&mut self) -> Token {
+    pub fn evaluate(&self, interpreter: &mut Interpreter) -> Result<Token, RuntimeError> {
         match self {
-            Expr::Unary(..) => self.evaluate_unary(),
-            Expr::Binary(..) => self.evaluate_binary(),
-            Expr::Literal(..) => self.evaluate_literal(),
-            Expr::Grouping(..) => self.evaluate_grouping(),
+            Expr::Unary(..) => self.evaluate_unary(interpreter),
+            Expr::Binary(..) => self.evaluate_binary(interpreter),
+            Expr::Logical(..) => self.evaluate_logical(interpreter),
+            Expr::Literal(..) => self.evaluate_literal(interpreter),
+            Expr::Grouping(..) => self.evaluate_grouping(interpreter),
+            Expr::List(..) => self.evaluate_list(interpreter),
+            Expr::Map(..) => self.evaluate_map(interpreter),
+            Expr::Set(..) => self.evaluate_set(interpreter),
+            Expr::Call(..) => self.evaluate_call(interpreter),
+            Expr::Get(..) => self.evaluate_get(interpreter),
+            Expr::Index(..) => self.evaluate_index(interpreter),
         }
     }
 
-    fn evaluate_unary(&mut self) -> Token {
+    fn evaluate_unary(&self, interpreter: &mut Interpreter) -> Result<Token, RuntimeError> {
         match self {
             Expr::Unary(op, expr) => {
-                let rhs = expr.evaluate();
+                let rhs = expr.evaluate(interpreter)?;
+
+                if let Some(result) = interpreter.call_unary_operator(&op.kind, &rhs)? {
+                    return Ok(result);
+                }
 
                 let kind = match op.kind {
-                    TokenKind::Minus => {
-                        let val = rhs.to_number();
+                    TokenKind::Minus => match rhs.to_number() {
+                        Some(val) => TokenKind::Number(-val),
 
-                        if let None = val {
-                            eprintln!(
-                                "Conversion error: Could not convert type {:?} to Number.\n",
+                        None => {
+                            return Err(RuntimeError::new(format!(
+                                "Conversion error: Could not convert type {:?} to Number.",
                                 rhs.kind,
-                            );
-
-                            TokenKind::None
-                        } else {
-                            TokenKind::Number(-val.unwrap())
+                            )));
                         }
-                    }
-
-                    TokenKind::Bang | TokenKind::Not => {
-                        let val = rhs.to_bool();
-
-                        if let None = val {
-                            eprintln!(
-                                "Conversion error: Could not convert type {:?} to Bool.\n",
-                                rhs.kind,
-                            );
+                    },
 
-                            TokenKind::None
-                        } else {
-                            TokenKind::Bool(!val.unwrap())
-                        }
-                    }
+                    TokenKind::Bang | TokenKind::Not => TokenKind::Bool(!interpreter.truthy(&rhs)?),
 
                     _ => {
-                        eprintln!(
-                            "Expression error: Could not apply operation {:?} to expression {:?}.\n",
+                        return Err(RuntimeError::new(format!(
+                            "Expression error: Could not apply operation {:?} to expression {:?}.",
                             op.kind, expr
-                        );
-
-                        TokenKind::None
+                        )));
                     }
                 };
 
-                Token::from(kind)
+                Ok(Token::from(kind))
             }
 
-            _ => Token::from(TokenKind::None),
+            _ => Ok(Token::from(TokenKind::None)),
         }
     }
 
-    fn evaluate_binary(&mut self) -> Token {
+    fn evaluate_binary(&self, interpreter: &mut Interpreter) -> Result<Token, RuntimeError> {
         match self {
             Expr::Binary(lhs, op, rhs) => {
-                let lhs = lhs.evaluate();
-                let rhs = rhs.evaluate();
-
-                let kind = match op.kind {
-                    TokenKind::Plus => match (lhs.clone().kind, rhs.clone().kind) {
-                        (TokenKind::Number(..), TokenKind::Number(..)) => {
-                            TokenKind::Number(lhs.to_number().unwrap() + rhs.to_number().unwrap())
-                        }
+                let lhs = lhs.evaluate(interpreter)?;
+                let rhs = rhs.evaluate(interpreter)?;
 
-                        (TokenKind::String(..), TokenKind::String(..)) => TokenKind::String(
-                            lhs.to_string().unwrap() + rhs.to_string().unwrap().as_str(),
-                        ),
+                Expr::binary_operation(interpreter, op, lhs, rhs)
+            }
 
-                        _ => {
-                            eprintln!(
-                                    "Expression error: Could not apply operation {:?} to expressions {:?} and {:?}.\n",
-                                    op.kind, lhs.kind, rhs.kind 
-                                );
+            _ => Ok(Token::from(TokenKind::None)),
+        }
+    }
 
-                            TokenKind::None
-                        }
-                    },
-                    TokenKind::Minus => {
-                        let lval = lhs.to_number();
-                        let rval = rhs.to_number();
+    /// Applies a binary operator to two already evaluated operands
+    pub fn binary_operation(
+        interpreter: &mut Interpreter,
+        op: &Token,
+        lhs: Token,
+        rhs: Token,
+    ) -> Result<Token, RuntimeError> {
+        // Equality is dispatched through `values_equal` so that it can fall back to identity
+        if op.kind != TokenKind::EqualEqual {
+            if let Some(result) = interpreter.call_operator(&op.kind, &lhs, &rhs)? {
+                return Ok(result);
+            }
+        }
 
-                        match (lval, rval) {
-                            (Some(lval), Some(rval)) => TokenKind::Number(lval - rval),
+        if let (TokenKind::Set(lval), TokenKind::Set(rval)) = (&lhs.kind, &rhs.kind) {
+            if let Some(set) = lval.borrow().operation(&op.kind, &rval.borrow())? {
+                return Ok(Token::from(TokenKind::Set(Rc::new(RefCell::new(set)))));
+            }
+        }
 
-                            _ => {
-                                eprintln!(
-                                    "Expression error: Could not apply operation {:?} to expressions {:?} and {:?}.\n",
-                                    op.kind, lhs.kind, rhs.kind 
-                                );
+        let kind = match op.kind {
+            TokenKind::Plus => match (lhs.clone().kind, rhs.clone().kind) {
+                (TokenKind::Number(..), TokenKind::Number(..)) => {
+                    TokenKind::Number(lhs.to_number().unwrap() + rhs.to_number().unwrap())
+                }
+
+                (TokenKind::String(..), TokenKind::String(..)) => {
+                    TokenKind::String(lhs.to_string().unwrap() + rhs.to_string().unwrap().as_str())
+                }
+
+                (TokenKind::Bytes(lval), TokenKind::Bytes(rval)) => {
+                    TokenKind::Bytes(Rc::new([lval.as_slice(), rval.as_slice()].concat()))
+                }
+
+                _ => {
+                    return Err(RuntimeError::new(format!(
+                                "Expression error: Could not apply operation {:?} to expressions {:?} and {:?}.",
+                                op.kind, lhs.kind, rhs.kind
+                            )));
+                }
+            },
+            TokenKind::Minus => {
+                let lval = lhs.to_number();
+                let rval = rhs.to_number();
+
+                match (lval, rval) {
+                    (Some(lval), Some(rval)) => TokenKind::Number(lval - rval),
 
-                                TokenKind::None
-                            }
-                        }
+                    _ => {
+                        return Err(RuntimeError::new(format!(
+                                    "Expression error: Could not apply operation {:?} to expressions {:?} and {:?}.",
+                                    op.kind, lhs.kind, rhs.kind
+                                )));
                     }
-                    TokenKind::Star => {
-                        let lval = lhs.to_number();
-                        let rval = rhs.to_number();
+                }
+            }
+            TokenKind::Star => {
+                let lval = lhs.to_number();
+                let rval = rhs.to_number();
 
-                        match (lval, rval) {
-                            (Some(lval), Some(rval)) => TokenKind::Number(lval * rval),
+                match (lval, rval) {
+                    (Some(lval), Some(rval)) => TokenKind::Number(lval * rval),
 
-                            _ => {
-                                eprintln!(
-                                    "Expression error: Could not apply operation {:?} to expressions {:?} and {:?}.\n",
+                    _ => {
+                        return Err(RuntimeError::new(format!(
+                                    "Expression error: Could not apply operation {:?} to expressions {:?} and {:?}.",
                                     op.kind, lhs.kind, rhs.kind
-                                );
+                                )));
+                    }
+                }
+            }
+            TokenKind::Slash => {
+                let lval = lhs.to_number();
+                let rval = rhs.to_number();
 
-                                TokenKind::None
-                            }
-                        }
+                match (lval, rval) {
+                    (Some(lval), Some(rval)) => TokenKind::Number(lval / rval),
+
+                    _ => {
+                        return Err(RuntimeError::new(format!(
+                                    "Expression error: Could not apply operation {:?} to expressions {:?} and {:?}.",
+                                    op.kind, lhs.kind, rhs.kind
+                                )));
                     }
-                    TokenKind::Slash => {
-                        let lval = lhs.to_number();
-                        let rval = rhs.to_number();
+                }
+            }
+            TokenKind::Percent => {
+                let lval = lhs.to_number();
+                let rval = rhs.to_number();
 
-                        match (lval, rval) {
-                            (Some(lval), Some(rval)) => TokenKind::Number(lval / rval),
+                match (lval, rval) {
+                    (Some(lval), Some(rval)) => TokenKind::Number(lval % rval),
 
-                            _ => {
-                                eprintln!(
-                                    "Expression error: Could not apply operation {:?} to expressions {:?} and {:?}.\n",
-                                    op.kind, lhs.kind, rhs.kind 
-                                );
+                    _ => {
+                        return Err(RuntimeError::new(format!(
+                                    "Expression error: Could not apply operation {:?} to expressions {:?} and {:?}.",
+                                    op.kind, lhs.kind, rhs.kind
+                                )));
+                    }
+                }
+            }
+            TokenKind::Caret => {
+                let lval = lhs.to_number();
+                let rval = rhs.to_number();
 
-                                TokenKind::None
-                            }
-                        }
+                match (lval, rval) {
+                    (Some(lval), Some(rval)) => TokenKind::Number(lval.powf(rval)),
+
+                    _ => {
+                        return Err(RuntimeError::new(format!(
+                                    "Expression error: Could not apply operation {:?} to expressions {:?} and {:?}.",
+                                    op.kind, lhs.kind, rhs.kind
+                                )));
                     }
-                    TokenKind::Percent => {
-                        let lval = lhs.to_number();
-                        let rval = rhs.to_number();
+                }
+            }
 
-                        match (lval, rval) {
-                            (Some(lval), Some(rval)) => TokenKind::Number(lval % rval),
+            TokenKind::Greater => {
+                let lval = lhs.to_number();
+                let rval = rhs.to_number();
 
-                            _ => {
-                                eprintln!(
-                                    "Expression error: Could not apply operation {:?} to expressions {:?} and {:?}.\n",
-                                    op.kind, lhs.kind, rhs.kind 
-                                );
+                match (lval, rval) {
+                    (Some(lval), Some(rval)) => TokenKind::Bool(lval > rval),
 
-                                TokenKind::None
-                            }
-                        }
+                    _ => {
+                        return Err(RuntimeError::new(format!(
+                                    "Expression error: Could not apply operation {:?} to expressions {:?} and {:?}.",
+                                    op.kind, lhs.kind, rhs.kind
+                                )));
                     }
-                    TokenKind::Caret => {
-                        let lval = lhs.to_number();
-                        let rval = rhs.to_number();
-
-                        match (lval, rval) {
-                            (Some(lval), Some(rval)) => TokenKind::Number(lval.powf(rval)),
+                }
+            }
+            TokenKind::GreaterEqual => {
+                let lval = lhs.to_number();
+                let rval = rhs.to_number();
 
-                            _ => {
-                                eprintln!(
-                                    "Expression error: Could not apply operation {:?} to expressions {:?} and {:?}.\n",
-                                    op.kind, lhs.kind, rhs.kind 
-                                );
+                match (lval, rval) {
+                    (Some(lval), Some(rval)) => TokenKind::Bool(lval >= rval),
 
-                                TokenKind::None
-                            }
-                        }
+                    _ => {
+                        return Err(RuntimeError::new(format!(
+                                    "Expression error: Could not apply operation {:?} to expressions {:?} and {:?}.",
+                                    op.kind, lhs.kind, rhs.kind
+                                )));
                     }
+                }
+            }
+            TokenKind::Less => {
+                let lval = lhs.to_number();
+                let rval = rhs.to_number();
 
-                    TokenKind::Greater => {
-                        let lval = lhs.to_number();
-                        let rval = rhs.to_number();
+                match (lval, rval) {
+                    (Some(lval), Some(rval)) => TokenKind::Bool(lval < rval),
 
-                        match (lval, rval) {
-                            (Some(lval), Some(rval)) => TokenKind::Bool(lval > rval),
+                    _ => {
+                        return Err(RuntimeError::new(format!(
+                                    "Expression error: Could not apply operation {:?} to expressions {:?} and {:?}.",
+                                    op.kind, lhs.kind, rhs.kind
+                                )));
+                    }
+                }
+            }
+            TokenKind::LessEqual => {
+                let lval = lhs.to_number();
+                let rval = rhs.to_number();
 
-                            _ => {
-                                eprintln!(
-                                    "Expression error: Could not apply operation {:?} to expressions {:?} and {:?}.\n",
-                                    op.kind, lhs.kind, rhs.kind 
-                                );
+                match (lval, rval) {
+                    (Some(lval), Some(rval)) => TokenKind::Bool(lval <= rval),
 
-                                TokenKind::None
-                            }
-                        }
+                    _ => {
+                        return Err(RuntimeError::new(format!(
+                                    "Expression error: Could not apply operation {:?} to expressions {:?} and {:?}.",
+                                    op.kind, lhs.kind, rhs.kind
+                                )));
                     }
-                    TokenKind::GreaterEqual => {
-                        let lval = lhs.to_number();
-                        let rval = rhs.to_number();
+                }
+            }
 
-                        match (lval, rval) {
-                            (Some(lval), Some(rval)) => TokenKind::Bool(lval >= rval),
+            TokenKind::EqualEqual => TokenKind::Bool(interpreter.values_equal(&lhs, &rhs)?),
+            TokenKind::BangEqual => TokenKind::Bool(!interpreter.values_equal(&lhs, &rhs)?),
+            TokenKind::Is => TokenKind::Bool(Expr::is_variant(&lhs, &rhs)?),
 
-                            _ => {
-                                eprintln!(
-                                    "Expression error: Could not apply operation {:?} to expressions {:?} and {:?}.\n",
-                                    op.kind, lhs.kind, rhs.kind 
-                                );
+            _ => TokenKind::None,
+        };
 
-                                TokenKind::None
-                            }
-                        }
-                    }
-                    TokenKind::Less => {
-                        let lval = lhs.to_number();
-                        let rval = rhs.to_number();
+        Ok(Token::from(kind))
+    }
 
-                        match (lval, rval) {
-                            (Some(lval), Some(rval)) => TokenKind::Bool(lval < rval),
+    /// `value is Enum.Variant`, whether a value is a particular variant of an enum, whatever data
+    /// it holds
+    fn is_variant(value: &Token, variant: &Token) -> Result<bool, RuntimeError> {
+        let (enumeration, index) = match &variant.kind {
+            TokenKind::Constructor(enumeration, index) => (enumeration, *index),
+            TokenKind::Variant(variant) => (&variant.enumeration, variant.index),
+
+            kind => {
+                return Err(RuntimeError::new(format!(
+                    "Type error: the right side of 'is' must be an enum variant, found {:?}.",
+                    kind
+                )));
+            }
+        };
 
-                            _ => {
-                                eprintln!(
-                                    "Expression error: Could not apply operation {:?} to expressions {:?} and {:?}.\n",
-                                    op.kind, lhs.kind, rhs.kind 
-                                );
+        Ok(match &value.kind {
+            TokenKind::Variant(value) => value.is_variant_of(enumeration, index),
+            _ => false,
+        })
+    }
 
-                                TokenKind::None
-                            }
-                        }
-                    }
-                    TokenKind::LessEqual => {
-                        let lval = lhs.to_number();
-                        let rval = rhs.to_number();
+    fn evaluate_logical(&self, interpreter: &mut Interpreter) -> Result<Token, RuntimeError> {
+        match self {
+            Expr::Logical(lhs, op, rhs) => {
+                let lhs = lhs.evaluate(interpreter)?;
+                let lval = interpreter.truthy(&lhs)?;
 
-                        match (lval, rval) {
-                            (Some(lval), Some(rval)) => TokenKind::Bool(lval <= rval),
+                // Short-circuit, yielding whichever operand decided the result
+                match op.kind {
+                    TokenKind::Or if lval => Ok(lhs),
+                    TokenKind::And if !lval => Ok(lhs),
 
-                            _ => {
-                                eprintln!(
-                                    "Expression error: Could not apply operation {:?} to expressions {:?} and {:?}.\n",
-                                    op.kind, lhs.kind, rhs.kind 
-                                );
+                    _ => rhs.evaluate(interpreter),
+                }
+            }
 
-                                TokenKind::None
-                            }
-                        }
+            _ => Ok(Token::from(TokenKind::None)),
+        }
+    }
+
+    fn evaluate_literal(&self, interpreter: &mut Interpreter) -> Result<Token, RuntimeError> {
+        match self {
+            Expr::Literal(tok) => match tok.kind {
+                TokenKind::Identifier(..) => interpreter.lookup(tok),
+
+                _ => Ok(tok.clone()),
+            },
+
+            _ => Ok(Token::from(TokenKind::None)),
+        }
+    }
+
+    fn evaluate_grouping(&self, interpreter: &mut Interpreter) -> Result<Token, RuntimeError> {
+        match self {
+            Expr::Grouping(_, expr, _) => expr.evaluate(interpreter),
+
+            _ => Ok(Token::from(TokenKind::None)),
+        }
+    }
+
+    fn evaluate_call(&self, interpreter: &mut Interpreter) -> Result<Token, RuntimeError> {
+        match self {
+            Expr::Call(callee, _, args) => {
+                let callee = callee.evaluate(interpreter)?;
+
+                let mut values = Vec::new();
+                let mut keywords = Vec::new();
+
+                for (name, arg) in args {
+                    let value = arg.evaluate(interpreter)?;
+
+                    match name {
+                        Some(name) => keywords.push((name.clone(), value)),
+                        None => values.push(value),
                     }
+                }
 
-                    TokenKind::EqualEqual => TokenKind::Bool(lhs.is_equal(rhs)),
-                    TokenKind::BangEqual => TokenKind::Bool(!lhs.is_equal(rhs)),
+                interpreter.call_with_keywords(callee, values, keywords)
+            }
 
-                    _ => TokenKind::None,
-                };
+            _ => Ok(Token::from(TokenKind::None)),
+        }
+    }
+
+    fn evaluate_list(&self, interpreter: &mut Interpreter) -> Result<Token, RuntimeError> {
+        match self {
+            Expr::List(_, elements) => {
+                let mut values = Vec::new();
+
+                for element in elements {
+                    values.push(element.evaluate(interpreter)?);
+                }
+
+                Ok(Token::from(TokenKind::List(Rc::new(RefCell::new(values)))))
+            }
+
+            _ => Ok(Token::from(TokenKind::None)),
+        }
+    }
+
+    fn evaluate_map(&self, interpreter: &mut Interpreter) -> Result<Token, RuntimeError> {
+        match self {
+            Expr::Map(_, entries) => {
+                let mut map = Map::new();
+
+                for (key, value) in entries {
+                    let key = key.evaluate(interpreter)?;
+                    let value = value.evaluate(interpreter)?;
 
-                Token::from(kind)
+                    map.insert(key, value)?;
+                }
+
+                Ok(Token::from(TokenKind::Map(Rc::new(RefCell::new(map)))))
             }
 
-            _ => Token::from(TokenKind::None),
+            _ => Ok(Token::from(TokenKind::None)),
         }
     }
 
-    fn evaluate_literal(&mut self) -> Token {
+    fn evaluate_set(&self, interpreter: &mut Interpreter) -> Result<Token, RuntimeError> {
         match self {
-            Expr::Literal(tok) => tok.clone(),
+            Expr::Set(_, elements) => {
+                let mut set = Set::new();
+
+                for element in elements {
+                    set.insert(element.evaluate(interpreter)?)?;
+                }
+
+                Ok(Token::from(TokenKind::Set(Rc::new(RefCell::new(set)))))
+            }
 
-            _ => Token::from(TokenKind::None),
+            _ => Ok(Token::from(TokenKind::None)),
         }
     }
 
-    fn evaluate_grouping(&mut self) -> Token {
-        Token::from(TokenKind::None)
+    fn evaluate_index(&self, interpreter: &mut Interpreter) -> Result<Token, RuntimeError> {
+        match self {
+            Expr::Index(object, _, index) => {
+                let object = object.evaluate(interpreter)?;
+                let index = index.evaluate(interpreter)?;
+
+                interpreter.get_index(object, index)
+            }
+
+            _ => Ok(Token::from(TokenKind::None)),
+        }
+    }
+
+    fn evaluate_get(&self, interpreter: &mut Interpreter) -> Result<Token, RuntimeError> {
+        match self {
+            Expr::Get(object, name) => {
+                let object = object.evaluate(interpreter)?;
+                interpreter.get_property(object, name)
+            }
+
+            _ => Ok(Token::from(TokenKind::None)),
+        }
     }
 }
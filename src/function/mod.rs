@@ -0,0 +1,191 @@
+use std::cell::RefCell;
+use std::fmt;
+use std::ops::RangeInclusive;
+use std::rc::Rc;
+
+use crate::environment::Environment;
+use crate::expr::Expr;
+use crate::interpreter::{Interpreter, RuntimeError};
+use crate::statement::Statement;
+use crate::token::{Token, TokenKind};
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct Parameter {
+    pub name: Token,
+
+    // Evaluated on every call that doesn't pass a value for the parameter
+    pub default: Option<Expr>,
+
+    // Collects any remaining positional arguments into a list
+    pub variadic: bool,
+}
+
+pub struct Function {
+    pub name: Token,
+    pub params: Vec<Parameter>,
+    pub body: Rc<Vec<Statement>>,
+    pub closure: Rc<RefCell<Environment>>,
+
+    // Functions containing `yield` return a generator instead of running their body when called
+    pub is_generator: bool,
+
+    // Bound methods receive the instance they were accessed through as their first argument
+    pub receiver: Option<Token>,
+}
+
+impl Function {
+    pub fn new(
+        name: Token,
+        params: Vec<Parameter>,
+        body: Rc<Vec<Statement>>,
+        closure: Rc<RefCell<Environment>>,
+        is_generator: bool,
+    ) -> Function {
+        Function {
+            name,
+            params,
+            body,
+            closure,
+            is_generator,
+            receiver: None,
+        }
+    }
+
+    pub fn bind(&self, receiver: Token) -> Function {
+        Function {
+            name: self.name.clone(),
+            params: self.params.clone(),
+            body: Rc::clone(&self.body),
+            closure: Rc::clone(&self.closure),
+            is_generator: self.is_generator,
+            receiver: Some(receiver),
+        }
+    }
+}
+
+impl fmt::Debug for Function {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "<func {}>", self.name.lexeme)
+    }
+}
+
+impl PartialEq for Function {
+    fn eq(&self, other: &Function) -> bool {
+        std::ptr::eq(self, other)
+    }
+}
+
+pub type NativeFn = dyn Fn(&mut Interpreter, Vec<Token>) -> Result<Token, RuntimeError>;
+
+/// A function implemented in Rust, such as the builtins
+pub struct NativeFunction {
+    pub name: String,
+    pub arity: RangeInclusive<usize>,
+
+    // Names of the parameters that can be given as keyword arguments, in order
+    pub params: Vec<String>,
+    pub func: Box<NativeFn>,
+}
+
+impl NativeFunction {
+    pub fn new<F>(name: &str, arity: RangeInclusive<usize>, func: F) -> NativeFunction
+    where
+        F: Fn(&mut Interpreter, Vec<Token>) -> Result<Token, RuntimeError> + 'static,
+    {
+        NativeFunction {
+            name: String::from(name),
+            arity,
+            params: Vec::new(),
+            func: Box::new(func),
+        }
+    }
+
+    /// Names the parameters, so that they can also be given as keyword arguments
+    pub fn with_params(mut self, params: &[&str]) -> NativeFunction {
+        self.params = params.iter().map(|param| String::from(*param)).collect();
+        self
+    }
+
+    /// Calls the function with keyword arguments moved into the positions of their parameters.
+    /// Parameters skipped over are passed as none.
+    pub fn call_with_keywords(
+        &self,
+        interpreter: &mut Interpreter,
+        mut args: Vec<Token>,
+        keywords: Vec<(Token, Token)>,
+    ) -> Result<Token, RuntimeError> {
+        let mut given = vec![true; args.len()];
+
+        for (name, value) in keywords {
+            let index = match self.params.iter().position(|param| *param == name.lexeme) {
+                Some(index) => index,
+                None if self.params.is_empty() => {
+                    return Err(RuntimeError::new(format!(
+                        "Call error: unexpected keyword argument '{}', <native func {}> only accepts positional arguments.",
+                        name.lexeme, self.name
+                    )));
+                }
+                None => {
+                    return Err(RuntimeError::new(format!(
+                        "Call error: function {} got an unexpected keyword argument '{}'.",
+                        self.name, name.lexeme
+                    )));
+                }
+            };
+
+            if given.get(index) == Some(&true) {
+                return Err(RuntimeError::new(format!(
+                    "Call error: function {} got multiple values for argument '{}'.",
+                    self.name, name.lexeme
+                )));
+            }
+
+            if index >= args.len() {
+                args.resize(index + 1, Token::from(TokenKind::None));
+                given.resize(index + 1, false);
+            }
+
+            args[index] = value;
+            given[index] = true;
+        }
+
+        self.call(interpreter, args)
+    }
+
+    pub fn call(
+        &self,
+        interpreter: &mut Interpreter,
+        args: Vec<Token>,
+    ) -> Result<Token, RuntimeError> {
+        if !self.arity.contains(&args.len()) {
+            let expected = if self.arity.start() == self.arity.end() {
+                self.arity.start().to_string()
+            } else if *self.arity.end() == usize::MAX {
+                format!("at least {}", self.arity.start())
+            } else {
+                format!("{} to {}", self.arity.start(), self.arity.end())
+            };
+
+            return Err(RuntimeError::new(format!(
+                "Call error: function {} expected {} arguments but got {}.",
+                self.name,
+                expected,
+                args.len()
+            )));
+        }
+
+        (self.func)(interpreter, args)
+    }
+}
+
+impl fmt::Debug for NativeFunction {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "<native func {}>", self.name)
+    }
+}
+
+impl PartialEq for NativeFunction {
+    fn eq(&self, other: &NativeFunction) -> bool {
+        std::ptr::eq(self, other)
+    }
+}
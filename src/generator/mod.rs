@@ -0,0 +1,465 @@
+use std::cell::RefCell;
+use std::fmt;
+use std::rc::Rc;
+
+use crate::environment::Environment;
+use crate::interpreter::{Interpreter, Interrupt, RuntimeError};
+use crate::iterator::ValueIterator;
+use crate::statement::{self, Statement};
+use crate::token::{Token, TokenKind};
+
+pub type NativeGeneratorFn = dyn FnMut(&mut Interpreter) -> Result<Option<Token>, RuntimeError>;
+
+/// A suspended computation producing a sequence of values on demand
+pub struct Generator {
+    pub name: String,
+    state: State,
+}
+
+enum State {
+    Suspended(Box<Frames>),
+    Native(Box<NativeGeneratorFn>),
+    Running,
+    Finished,
+}
+
+/// The execution state of a suspended function body. Rather than recursing through the statement
+/// tree like `Statement::evaluate`, generators keep an explicit stack of the blocks they are in so
+/// that they can stop at a `yield` and pick up from the same place later.
+struct Frames {
+    body: Rc<Vec<Statement>>,
+    stack: Vec<Frame>,
+
+    // Where the managers of unfinished `with` blocks go if the generator is dropped, to be exited
+    // by the interpreter
+    abandoned: Rc<RefCell<Vec<Token>>>,
+}
+
+struct Frame {
+    block: Block,
+    environment: Rc<RefCell<Environment>>,
+
+    // Index of the next statement to run within the block
+    index: usize,
+}
+
+enum Block {
+    Body,
+    Then,
+    Else,
+    Loop(Option<ValueIterator>, Option<Token>),
+
+    // The body of a `with` block, holding the manager to exit when leaving it
+    With(Token),
+
+    Try,
+    Catch,
+}
+
+impl Generator {
+    pub fn new(
+        name: String,
+        body: Rc<Vec<Statement>>,
+        environment: Rc<RefCell<Environment>>,
+        abandoned: Rc<RefCell<Vec<Token>>>,
+    ) -> Generator {
+        let frames = Frames {
+            body,
+            abandoned,
+            stack: vec![Frame {
+                block: Block::Body,
+                environment,
+                index: 0,
+            }],
+        };
+
+        Generator {
+            name,
+            state: State::Suspended(Box::new(frames)),
+        }
+    }
+
+    pub fn from_native<F>(name: &str, func: F) -> Generator
+    where
+        F: FnMut(&mut Interpreter) -> Result<Option<Token>, RuntimeError> + 'static,
+    {
+        Generator {
+            name: String::from(name),
+            state: State::Native(Box::new(func)),
+        }
+    }
+
+    /// Runs the generator until it produces its next value, returning none once it has finished
+    pub fn resume(
+        generator: &Rc<RefCell<Generator>>,
+        interpreter: &mut Interpreter,
+    ) -> Result<Option<Token>, RuntimeError> {
+        // The state is taken out for the duration of the call, so that the generator can't be
+        // resumed again from within its own body
+        let state = std::mem::replace(&mut generator.borrow_mut().state, State::Running);
+
+        let (result, state) = match state {
+            State::Suspended(mut frames) => {
+                let result = match interpreter.enter_call() {
+                    Ok(()) => {
+                        let previous = interpreter.current_environment();
+                        let result = frames.run(interpreter);
+                        interpreter.replace_environment(previous);
+                        interpreter.leave_call();
+
+                        result
+                    }
+                    Err(error) => Err(error),
+                };
+
+                (result, State::Suspended(frames))
+            }
+
+            State::Native(mut func) => {
+                let result = func(interpreter);
+                (result, State::Native(func))
+            }
+
+            State::Running => {
+                return Err(RuntimeError::new(format!(
+                    "Iteration error: generator {} is already running.",
+                    generator.borrow().name
+                )));
+            }
+
+            State::Finished => return Ok(None),
+        };
+
+        generator.borrow_mut().state = match result {
+            Ok(Some(..)) => state,
+            _ => State::Finished,
+        };
+
+        result
+    }
+
+    /// Stops a suspended generator, exiting the `with` blocks it is in. Errors from the exits are
+    /// ignored, as nothing is consuming the generator any more.
+    pub fn close(generator: &Rc<RefCell<Generator>>, interpreter: &mut Interpreter) {
+        let state = std::mem::replace(&mut generator.borrow_mut().state, State::Finished);
+
+        match state {
+            State::Suspended(mut frames) => {
+                while !frames.stack.is_empty() {
+                    let _ = frames.pop(interpreter);
+                }
+            }
+
+            state => generator.borrow_mut().state = state,
+        }
+    }
+}
+
+impl Frames {
+    fn run(&mut self, interpreter: &mut Interpreter) -> Result<Option<Token>, RuntimeError> {
+        loop {
+            let mut error = match self.run_until_yield(interpreter) {
+                Err(error) => error,
+                result => return result,
+            };
+
+            // Leave every block inside the innermost `try` block, or finish the generator if the
+            // error isn't caught
+            loop {
+                match self.stack.last() {
+                    Some(Frame {
+                        block: Block::Try, ..
+                    }) if error.is_catchable() => break,
+
+                    None => return Err(error),
+                    _ => {}
+                }
+
+                if let Err(exit_error) = self.pop(interpreter) {
+                    error = exit_error;
+                }
+            }
+
+            self.stack.pop();
+
+            let body = Rc::clone(&self.body);
+            let parent = Frames::resolve(&body, &self.stack);
+            let statement = &parent[self.stack.last().unwrap().index - 1];
+
+            if let Statement::Try(_, name, _) = statement {
+                let message = Token::from(TokenKind::String(error.message));
+                self.push(Block::Catch, name.as_ref().map(|name| (name, message)));
+            }
+        }
+    }
+
+    fn run_until_yield(
+        &mut self,
+        interpreter: &mut Interpreter,
+    ) -> Result<Option<Token>, RuntimeError> {
+        let body = Rc::clone(&self.body);
+
+        loop {
+            let frame = match self.stack.last_mut() {
+                Some(frame) => frame,
+                None => return Ok(None),
+            };
+
+            // Statements run in the scope of the innermost block
+            interpreter.replace_environment(Rc::clone(&frame.environment));
+
+            let block = Frames::resolve(&body, &self.stack);
+            let frame = self.stack.last_mut().unwrap();
+
+            if frame.index >= block.len() {
+                self.finish_block(&body, interpreter)?;
+                continue;
+            }
+
+            let statement = &block[frame.index];
+            frame.index += 1;
+
+            // Statements that can't suspend are run as usual
+            if !statement.contains_yield() {
+                match statement.evaluate(interpreter) {
+                    Ok(()) => {}
+                    Err(Interrupt::Break(target)) => self.unwind(true, &target, interpreter)?,
+                    Err(Interrupt::Continue(target)) => self.unwind(false, &target, interpreter)?,
+
+                    Err(Interrupt::Return(..)) => {
+                        while !self.stack.is_empty() {
+                            self.pop(interpreter)?;
+                        }
+                    }
+
+                    Err(Interrupt::Error(error)) => return Err(error),
+                }
+
+                continue;
+            }
+
+            match statement {
+                Statement::Yield(expr) => return Ok(Some(expr.evaluate(interpreter)?)),
+
+                Statement::If(condition, ..) => {
+                    let condition = condition.evaluate(interpreter)?;
+
+                    let block = if interpreter.truthy(&condition)? {
+                        Block::Then
+                    } else {
+                        Block::Else
+                    };
+
+                    self.push(block, None);
+                }
+
+                Statement::While(condition, _, label) => {
+                    let condition = condition.evaluate(interpreter)?;
+
+                    if interpreter.truthy(&condition)? {
+                        self.push(Block::Loop(None, label.clone()), None);
+                    }
+                }
+
+                Statement::Loop(_, label) => self.push(Block::Loop(None, label.clone()), None),
+
+                Statement::For(name, iterable, _, label) => {
+                    let iterable = iterable.evaluate(interpreter)?;
+                    let mut iterator = interpreter.iterate(iterable)?;
+
+                    if let Some(value) = iterator.next(interpreter)? {
+                        let block = Block::Loop(Some(iterator), label.clone());
+                        self.push(block, Some((name, value)));
+                    }
+                }
+
+                Statement::Try(..) => self.push(Block::Try, None),
+
+                Statement::With(expr, name, _) => {
+                    let manager = expr.evaluate(interpreter)?;
+                    let value = interpreter.enter_context(&manager)?;
+
+                    let binding = name.as_ref().map(|name| (name, value));
+                    self.push(Block::With(manager), binding);
+                }
+
+                _ => {}
+            }
+        }
+    }
+
+    /// Finds the statements of the innermost block on the stack. Each frame's block belongs to the
+    /// statement its parent frame most recently started.
+    fn resolve<'a>(body: &'a [Statement], stack: &[Frame]) -> &'a [Statement] {
+        let mut block = body;
+
+        for pair in stack.windows(2) {
+            let statement = &block[pair[0].index - 1];
+
+            block = match (&pair[1].block, statement) {
+                (Block::Then, Statement::If(_, then_branch, _)) => then_branch,
+                (Block::Else, Statement::If(_, _, else_branch)) => else_branch,
+                (Block::Loop(..), Statement::While(_, body, _))
+                | (Block::Loop(..), Statement::Loop(body, _))
+                | (Block::Loop(..), Statement::For(_, _, body, _))
+                | (Block::With(..), Statement::With(_, _, body))
+                | (Block::Try, Statement::Try(body, ..)) => body,
+                (Block::Catch, Statement::Try(_, _, handler)) => handler,
+
+                _ => &[],
+            };
+        }
+
+        block
+    }
+
+    /// Enters a block in a new scope, binding the loop variable of a `for` loop or the value of a
+    /// `with` block in it
+    fn push(&mut self, block: Block, binding: Option<(&Token, Token)>) {
+        let environment = self.scope(self.stack.len(), binding);
+
+        self.stack.push(Frame {
+            block,
+            environment,
+            index: 0,
+        });
+    }
+
+    /// Creates the scope for a block nested `depth` frames deep
+    fn scope(&self, depth: usize, binding: Option<(&Token, Token)>) -> Rc<RefCell<Environment>> {
+        let mut environment = Environment::from(Rc::clone(&self.stack[depth - 1].environment));
+
+        if let Some((name, value)) = binding {
+            environment.define(name.lexeme.clone(), value);
+        }
+
+        Rc::new(RefCell::new(environment))
+    }
+
+    /// Leaves the block at the top of the stack, going round again if it is the body of a loop
+    fn finish_block(
+        &mut self,
+        body: &[Statement],
+        interpreter: &mut Interpreter,
+    ) -> Result<(), RuntimeError> {
+        let depth = self.stack.len();
+
+        if let Block::Loop(..) = self.stack[depth - 1].block {
+            let parent = Frames::resolve(body, &self.stack[..depth - 1]);
+            let statement = &parent[self.stack[depth - 2].index - 1];
+
+            let repeat = match statement {
+                Statement::While(condition, ..) => {
+                    interpreter.replace_environment(Rc::clone(&self.stack[depth - 2].environment));
+
+                    let condition = condition.evaluate(interpreter)?;
+                    interpreter.truthy(&condition)?
+                }
+
+                Statement::For(name, ..) => {
+                    let next = match &mut self.stack[depth - 1].block {
+                        Block::Loop(Some(iterator), _) => iterator.next(interpreter)?,
+                        _ => None,
+                    };
+
+                    match next {
+                        Some(value) => {
+                            self.stack[depth - 1].environment =
+                                self.scope(depth - 1, Some((name, value)));
+
+                            self.stack[depth - 1].index = 0;
+                            return Ok(());
+                        }
+
+                        None => false,
+                    }
+                }
+
+                _ => true,
+            };
+
+            // Each iteration of a loop gets a fresh scope
+            if repeat {
+                self.stack[depth - 1].environment = self.scope(depth - 1, None);
+                self.stack[depth - 1].index = 0;
+                return Ok(());
+            }
+        }
+
+        self.pop(interpreter)
+    }
+
+    /// Leaves the block at the top of the stack, running the exit action of a `with` block
+    fn pop(&mut self, interpreter: &mut Interpreter) -> Result<(), RuntimeError> {
+        match self.stack.pop() {
+            Some(Frame {
+                block: Block::With(manager),
+                ..
+            }) => interpreter.exit_context(&manager),
+
+            _ => Ok(()),
+        }
+    }
+
+    /// Pops blocks up to the loop targeted by a `break` or `continue`, either leaving it or
+    /// skipping to its next iteration
+    fn unwind(
+        &mut self,
+        exit_loop: bool,
+        target: &Option<String>,
+        interpreter: &mut Interpreter,
+    ) -> Result<(), RuntimeError> {
+        while let Some(frame) = self.stack.last_mut() {
+            if let Block::Loop(_, label) = &frame.block {
+                if !statement::targets_loop(label, target) {
+                    self.pop(interpreter)?;
+                    continue;
+                }
+
+                if exit_loop {
+                    self.pop(interpreter)?;
+                } else {
+                    frame.index = usize::MAX;
+                }
+
+                return Ok(());
+            }
+
+            self.pop(interpreter)?;
+        }
+
+        Ok(())
+    }
+}
+
+// A generator dropped while suspended never reaches the end of its `with` blocks, so their
+// managers are handed over to be exited
+impl Drop for Frames {
+    fn drop(&mut self) {
+        // Frames can hold other suspended generators, which hand over their own managers as they
+        // are dropped here, so the queue is only borrowed once they are gone
+        let managers: Vec<Token> = self
+            .stack
+            .drain(..)
+            .rev()
+            .filter_map(|frame| match frame.block {
+                Block::With(manager) => Some(manager),
+                _ => None,
+            })
+            .collect();
+
+        self.abandoned.borrow_mut().extend(managers);
+    }
+}
+
+impl fmt::Debug for Generator {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "<generator {}>", self.name)
+    }
+}
+
+impl PartialEq for Generator {
+    fn eq(&self, other: &Generator) -> bool {
+        std::ptr::eq(self, other)
+    }
+}
@@ -0,0 +1,557 @@
+use std::any::Any;
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::fmt;
+use std::rc::Rc;
+
+use crate::environment::Environment;
+use crate::function::NativeFunction;
+use crate::interpreter::{Interpreter, RuntimeError};
+use crate::map::Map;
+use crate::module::Module;
+use crate::token::{Token, TokenKind};
+
+/// Rust types that can be read out of a script's values
+pub trait FromValue: Sized {
+    /// The kind of value expected, as named in error messages
+    fn expected() -> String;
+
+    fn from_value(value: &Token) -> Option<Self>;
+}
+
+/// Rust types that can be handed to scripts as values
+pub trait IntoValue {
+    fn into_value(self) -> Token;
+}
+
+/// Rust values that can be passed as the arguments of a call into a script, such as tuples of
+/// values
+pub trait IntoArgs {
+    fn into_args(self) -> Vec<Token>;
+}
+
+/// A function, class or other callable value of a script, kept by the host to call later. It
+/// refers to the value itself, so it keeps working after the variable it came from changes.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Callback {
+    value: Token,
+}
+
+impl Callback {
+    /// Calls the value, converting the result to `R`
+    pub fn call<A, R>(&self, interpreter: &mut Interpreter, args: A) -> Result<R, RuntimeError>
+    where
+        A: IntoArgs,
+        R: FromValue,
+    {
+        let result = interpreter.call(self.value.clone(), args.into_args())?;
+
+        R::from_value(&result).ok_or_else(|| {
+            RuntimeError::new(format!(
+                "Type error: {} returned {:?} but the host expected {}.",
+                self.value.to_string().unwrap_or_default(),
+                result.kind,
+                R::expected()
+            ))
+        })
+    }
+}
+
+/// Gives access to the concrete type behind a host object. It is implemented for every type, so
+/// host objects don't need to implement it themselves.
+pub trait AsAny {
+    fn as_any(&self) -> &dyn Any;
+    fn into_any(self: Rc<Self>) -> Rc<dyn Any>;
+}
+
+impl<T: Any> AsAny for T {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn into_any(self: Rc<Self>) -> Rc<dyn Any> {
+        self
+    }
+}
+
+/// A Rust value handed to scripts as an opaque object, whose properties and methods are provided
+/// by the host. State that methods change needs interior mutability.
+///
+/// Objects are shared by reference counting, which can't free cycles. The interpreter breaks the
+/// cycle between a function and the local scope it was defined in once nothing else uses them,
+/// and the rest of the cycles between functions and the scopes they close over when it is
+/// dropped. Objects held in variables are released by then at the latest, but not those inside
+/// lists, maps or instances that a script has made refer to themselves.
+pub trait HostObject: AsAny + 'static {
+    /// The name of the object's type, as shown to scripts
+    fn type_name(&self) -> &str;
+
+    /// The value of a property, or `None` if there is no such property
+    fn get_property(&self, _name: &str) -> Option<Token> {
+        None
+    }
+
+    fn set_property(&self, name: &str, _value: Token) -> Result<(), RuntimeError> {
+        Err(RuntimeError::new(format!(
+            "Attribute error: could not set attribute '{}' on {} object.",
+            name,
+            self.type_name()
+        )))
+    }
+
+    /// The names of the methods scripts can call
+    fn methods(&self) -> &[&str] {
+        &[]
+    }
+
+    /// Calls one of the methods named by `methods`
+    fn call_method(&self, name: &str, _args: &Args) -> Result<Token, RuntimeError> {
+        Err(RuntimeError::new(format!(
+            "Attribute error: {} object has no method '{}'.",
+            self.type_name(),
+            name
+        )))
+    }
+
+    /// Whether the object equals another host object, by default only when they are the same one
+    fn equals(&self, other: &dyn HostObject) -> bool {
+        std::ptr::eq(
+            self.as_any() as *const dyn Any as *const u8,
+            other.as_any() as *const dyn Any as *const u8,
+        )
+    }
+
+    /// The object's printed form
+    fn display(&self) -> String {
+        format!("<{} object>", self.type_name())
+    }
+}
+
+impl PartialEq for dyn HostObject {
+    fn eq(&self, other: &dyn HostObject) -> bool {
+        self.equals(other)
+    }
+}
+
+impl fmt::Debug for dyn HostObject {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.display())
+    }
+}
+
+/// Wraps a host object as a value scripts can use
+pub fn object<T: HostObject>(value: T) -> Token {
+    Token::from(TokenKind::Object(Rc::new(value)))
+}
+
+/// Looks up a property of a host object, or one of its methods bound to it
+pub fn property(object: &Rc<dyn HostObject>, name: &str) -> Result<Token, RuntimeError> {
+    if let Some(value) = object.get_property(name) {
+        return Ok(value);
+    }
+
+    if !object.methods().contains(&name) {
+        return Err(RuntimeError::new(format!(
+            "Attribute error: {} object has no attribute '{}'.",
+            object.type_name(),
+            name
+        )));
+    }
+
+    let object = Rc::clone(object);
+    let method = String::from(name);
+
+    let func = native(name, move |args| object.call_method(&method, args));
+    Ok(Token::from(TokenKind::NativeFunction(Rc::new(func))))
+}
+
+/// The arguments a script passed to a function registered by the host
+pub struct Args {
+    name: String,
+    values: Vec<Token>,
+}
+
+impl Args {
+    pub fn len(&self) -> usize {
+        self.values.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.values.is_empty()
+    }
+
+    pub fn values(&self) -> &[Token] {
+        &self.values
+    }
+
+    /// The argument at `index`, converted to `T`
+    pub fn get<T: FromValue>(&self, index: usize) -> Result<T, RuntimeError> {
+        match self.values.get(index) {
+            Some(value) => self.convert(index, value),
+            None => Err(RuntimeError::new(format!(
+                "Call error: function {} expected at least {} arguments but got {}.",
+                self.name,
+                index + 1,
+                self.values.len()
+            ))),
+        }
+    }
+
+    /// The argument at `index` converted to `T`, or `None` if it wasn't given or is none
+    pub fn optional<T: FromValue>(&self, index: usize) -> Result<Option<T>, RuntimeError> {
+        match self.values.get(index) {
+            None
+            | Some(Token {
+                kind: TokenKind::None,
+                ..
+            }) => Ok(None),
+            Some(value) => self.convert(index, value).map(Some),
+        }
+    }
+
+    /// The arguments from `start` onwards, each converted to `T`
+    pub fn rest<T: FromValue>(&self, start: usize) -> Result<Vec<T>, RuntimeError> {
+        (start..self.values.len())
+            .map(|index| self.convert(index, &self.values[index]))
+            .collect()
+    }
+
+    fn convert<T: FromValue>(&self, index: usize, value: &Token) -> Result<T, RuntimeError> {
+        T::from_value(value).ok_or_else(|| {
+            RuntimeError::new(format!(
+                "Type error: {} expected {} for argument {} but got {:?}.",
+                self.name,
+                T::expected(),
+                index + 1,
+                value.kind
+            ))
+        })
+    }
+}
+
+/// Wraps a host function so scripts can call it with any number of arguments
+pub fn native<F, R>(name: &str, func: F) -> NativeFunction
+where
+    F: Fn(&Args) -> Result<R, RuntimeError> + 'static,
+    R: IntoValue,
+{
+    let owned = String::from(name);
+
+    NativeFunction::new(name, 0..=usize::MAX, move |_, values| {
+        let args = Args {
+            name: owned.clone(),
+            values,
+        };
+
+        func(&args).map(IntoValue::into_value)
+    })
+}
+
+/// A module of host functions and constants, which scripts can import by name once registered
+pub struct NativeModule {
+    name: String,
+    globals: Environment,
+}
+
+impl NativeModule {
+    pub fn new(name: &str) -> NativeModule {
+        NativeModule {
+            name: String::from(name),
+            globals: Environment::new(),
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn function<F, R>(mut self, name: &str, func: F) -> NativeModule
+    where
+        F: Fn(&Args) -> Result<R, RuntimeError> + 'static,
+        R: IntoValue,
+    {
+        let func = native(name, func);
+
+        self.globals.define(
+            String::from(name),
+            Token::from(TokenKind::NativeFunction(Rc::new(func))),
+        );
+
+        self
+    }
+
+    pub fn constant<V: IntoValue>(mut self, name: &str, value: V) -> NativeModule {
+        self.globals
+            .define_constant(String::from(name), value.into_value());
+
+        self
+    }
+
+    pub fn into_module(self) -> Module {
+        Module::new(self.name, Rc::new(RefCell::new(self.globals)))
+    }
+}
+
+impl FromValue for Token {
+    fn expected() -> String {
+        String::from("a value")
+    }
+
+    fn from_value(value: &Token) -> Option<Token> {
+        Some(value.clone())
+    }
+}
+
+impl FromValue for f64 {
+    fn expected() -> String {
+        String::from("a Number")
+    }
+
+    fn from_value(value: &Token) -> Option<f64> {
+        value.to_number()
+    }
+}
+
+impl FromValue for i64 {
+    fn expected() -> String {
+        String::from("a whole Number")
+    }
+
+    fn from_value(value: &Token) -> Option<i64> {
+        match value.to_number() {
+            Some(number) if number.fract() == 0.0 && number.abs() < 2f64.powi(63) => {
+                Some(number as i64)
+            }
+            _ => None,
+        }
+    }
+}
+
+impl FromValue for String {
+    fn expected() -> String {
+        String::from("a String")
+    }
+
+    fn from_value(value: &Token) -> Option<String> {
+        match &value.kind {
+            TokenKind::String(text) => Some(text.clone()),
+            _ => None,
+        }
+    }
+}
+
+impl FromValue for bool {
+    fn expected() -> String {
+        String::from("a Bool")
+    }
+
+    fn from_value(value: &Token) -> Option<bool> {
+        match value.kind {
+            TokenKind::Bool(flag) => Some(flag),
+            _ => None,
+        }
+    }
+}
+
+impl IntoValue for Token {
+    fn into_value(self) -> Token {
+        self
+    }
+}
+
+impl IntoValue for TokenKind {
+    fn into_value(self) -> Token {
+        Token::from(self)
+    }
+}
+
+impl IntoValue for () {
+    fn into_value(self) -> Token {
+        Token::from(TokenKind::None)
+    }
+}
+
+impl IntoValue for f64 {
+    fn into_value(self) -> Token {
+        Token::from(TokenKind::Number(self))
+    }
+}
+
+impl IntoValue for i64 {
+    fn into_value(self) -> Token {
+        Token::from(TokenKind::Number(self as f64))
+    }
+}
+
+impl IntoValue for String {
+    fn into_value(self) -> Token {
+        Token::from(TokenKind::String(self))
+    }
+}
+
+impl IntoValue for &str {
+    fn into_value(self) -> Token {
+        Token::from(TokenKind::String(String::from(self)))
+    }
+}
+
+impl IntoValue for bool {
+    fn into_value(self) -> Token {
+        Token::from(TokenKind::Bool(self))
+    }
+}
+
+impl FromValue for Callback {
+    fn expected() -> String {
+        String::from("a function")
+    }
+
+    fn from_value(value: &Token) -> Option<Callback> {
+        match value.kind {
+            TokenKind::Function(..)
+            | TokenKind::NativeFunction(..)
+            | TokenKind::Type(..)
+            | TokenKind::Constructor(..) => Some(Callback {
+                value: value.clone(),
+            }),
+            _ => None,
+        }
+    }
+}
+
+impl<T: FromValue> FromValue for Option<T> {
+    fn expected() -> String {
+        format!("{} or none", T::expected())
+    }
+
+    fn from_value(value: &Token) -> Option<Option<T>> {
+        match value.kind {
+            TokenKind::None => Some(None),
+            _ => T::from_value(value).map(Some),
+        }
+    }
+}
+
+impl<T: FromValue> FromValue for Vec<T> {
+    fn expected() -> String {
+        format!("a List of {}", without_article(T::expected()))
+    }
+
+    fn from_value(value: &Token) -> Option<Vec<T>> {
+        match &value.kind {
+            TokenKind::List(list) => list.borrow().iter().map(T::from_value).collect(),
+            _ => None,
+        }
+    }
+}
+
+impl<T: FromValue> FromValue for HashMap<String, T> {
+    fn expected() -> String {
+        format!("a Map of String to {}", without_article(T::expected()))
+    }
+
+    fn from_value(value: &Token) -> Option<HashMap<String, T>> {
+        match &value.kind {
+            TokenKind::Map(map) => map
+                .borrow()
+                .entries()
+                .iter()
+                .map(|(key, value)| Some((String::from_value(key)?, T::from_value(value)?)))
+                .collect(),
+            _ => None,
+        }
+    }
+}
+
+/// Turns "a Number" into "Number", for naming the elements of a collection
+fn without_article(expected: String) -> String {
+    match expected.strip_prefix("a ") {
+        Some(rest) => String::from(rest),
+        None => expected,
+    }
+}
+
+impl IntoValue for Callback {
+    fn into_value(self) -> Token {
+        self.value
+    }
+}
+
+impl<T: IntoValue> IntoValue for Option<T> {
+    fn into_value(self) -> Token {
+        match self {
+            Some(value) => value.into_value(),
+            None => Token::from(TokenKind::None),
+        }
+    }
+}
+
+impl<T: IntoValue> IntoValue for Vec<T> {
+    fn into_value(self) -> Token {
+        let values = self.into_iter().map(IntoValue::into_value).collect();
+        Token::from(TokenKind::List(Rc::new(RefCell::new(values))))
+    }
+}
+
+// Entries are sorted by key, as a `HashMap` has no order of its own
+impl<T: IntoValue> IntoValue for HashMap<String, T> {
+    fn into_value(self) -> Token {
+        let mut entries: Vec<(String, T)> = self.into_iter().collect();
+        entries.sort_by(|a, b| a.0.cmp(&b.0));
+
+        let mut map = Map::new();
+
+        for (key, value) in entries {
+            map.insert(key.into_value(), value.into_value())
+                .expect("strings can always be map keys");
+        }
+
+        Token::from(TokenKind::Map(Rc::new(RefCell::new(map))))
+    }
+}
+
+impl IntoArgs for Vec<Token> {
+    fn into_args(self) -> Vec<Token> {
+        self
+    }
+}
+
+macro_rules! tuple_args {
+    ($($name:ident),*) => {
+        impl<$($name: IntoValue),*> IntoArgs for ($($name,)*) {
+            #[allow(non_snake_case)]
+            fn into_args(self) -> Vec<Token> {
+                let ($($name,)*) = self;
+                vec![$($name.into_value()),*]
+            }
+        }
+    };
+}
+
+tuple_args!();
+tuple_args!(A);
+tuple_args!(A, B);
+tuple_args!(A, B, C);
+tuple_args!(A, B, C, D);
+tuple_args!(A, B, C, D, E);
+tuple_args!(A, B, C, D, E, F);
+
+impl<T: HostObject> FromValue for Rc<T> {
+    fn expected() -> String {
+        let name = std::any::type_name::<T>();
+        format!("a {} object", name.rsplit("::").next().unwrap_or(name))
+    }
+
+    fn from_value(value: &Token) -> Option<Rc<T>> {
+        match &value.kind {
+            TokenKind::Object(object) => Rc::clone(object).into_any().downcast().ok(),
+            _ => None,
+        }
+    }
+}
+
+impl<T: HostObject> IntoValue for Rc<T> {
+    fn into_value(self) -> Token {
+        Token::from(TokenKind::Object(self))
+    }
+}
@@ -0,0 +1,91 @@
+use std::fmt;
+
+use crate::token::Token;
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct RuntimeError {
+    pub message: String,
+
+    // Set when the error is really a request from the script to stop with this exit status
+    pub exit_code: Option<i32>,
+}
+
+impl RuntimeError {
+    pub fn new(message: String) -> RuntimeError {
+        RuntimeError {
+            message,
+            exit_code: None,
+        }
+    }
+
+    /// Stops the program, unwinding through any `with` blocks on the way out
+    pub fn exit(code: i32) -> RuntimeError {
+        RuntimeError {
+            message: format!("Exit: script exited with status {}.", code),
+            exit_code: Some(code),
+        }
+    }
+
+    /// Whether a `try` block may handle this error
+    pub fn is_catchable(&self) -> bool {
+        self.exit_code.is_none()
+    }
+}
+
+impl fmt::Display for RuntimeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.message)
+    }
+}
+
+/// Reasons for a statement to stop executing before reaching the end of its block
+#[derive(Debug, PartialEq, Clone)]
+pub enum Interrupt {
+    Return(Token),
+    // Loop control statements carry the label of the loop they target, if they name one
+    Break(Option<String>),
+    Continue(Option<String>),
+
+    Error(RuntimeError),
+}
+
+impl From<RuntimeError> for Interrupt {
+    fn from(error: RuntimeError) -> Interrupt {
+        Interrupt::Error(error)
+    }
+}
+
+/// Errors from running source code through an embedded interpreter
+#[derive(Debug, PartialEq, Clone)]
+pub enum Error {
+    // The messages for each lexing or parsing error found, before anything was run
+    Syntax(Vec<String>),
+    Runtime(RuntimeError),
+}
+
+impl Error {
+    /// The exit status the script asked to stop with, if that's why it stopped
+    pub fn exit_code(&self) -> Option<i32> {
+        match self {
+            Error::Runtime(error) => error.exit_code,
+            Error::Syntax(..) => None,
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::Syntax(errors) => write!(f, "{}", errors.join("\n")),
+            Error::Runtime(error) => write!(f, "{}", error),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl From<RuntimeError> for Error {
+    fn from(error: RuntimeError) -> Error {
+        Error::Runtime(error)
+    }
+}
@@ -1,7 +1,1157 @@
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::env;
+use std::path::{Path, PathBuf};
+use std::rc::{Rc, Weak};
+
+use crate::builtins;
+use crate::class::Instance;
+use crate::enumeration::Variant;
+use crate::environment::{AssignError, Environment};
+use crate::function::Function;
+use crate::generator::Generator;
+use crate::host::{self, Args, Callback, FromValue, IntoValue, NativeModule};
+use crate::interpreter::{Error, Interrupt, RuntimeError};
+use crate::iterator::ValueIterator;
+use crate::module::{self, Module};
+use crate::parser;
 use crate::statement::Statement;
+use crate::token::{Token, TokenKind};
+
+/// The default limit on nested function calls.
+///
+/// Every call takes space on the stack of the thread running the interpreter, and how much
+/// depends on the build and on how deeply blocks nest inside the function. Reaching this limit
+/// takes up to 4 MiB of stack in release builds, and up to 24 MiB in debug builds, where plain
+/// recursive functions need around 8 MiB. Threads started with `std::thread::spawn` only have
+/// 2 MiB, so hosts should either give the interpreter's thread a bigger stack or lower the limit
+/// with [`Interpreter::set_max_call_depth`].
+pub const MAX_CALL_DEPTH: usize = 200;
+
+pub struct Interpreter {
+    pub globals: Rc<RefCell<Environment>>,
+    environment: Rc<RefCell<Environment>>,
+
+    // Modules that have been run, by the canonical path of their file
+    modules: HashMap<PathBuf, Rc<Module>>,
+    native_modules: HashMap<String, Rc<Module>>,
+
+    // Canonical and displayed paths of the scripts currently being run, each importing the next
+    loading: Vec<(PathBuf, String)>,
+
+    // Directories searched for modules after the one containing the importing script
+    search_path: Vec<PathBuf>,
+
+    // Command line arguments given after the script's path
+    script_args: Vec<String>,
+
+    // Environment variables set by the script, which are kept here rather than changing the
+    // process's own environment, as that isn't safe while other threads may be reading it
+    env_vars: HashMap<String, String>,
+
+    // Number of function calls currently running, and how many may be
+    call_depth: usize,
+    max_call_depth: usize,
+
+    // Managers of `with` blocks that dropped generators were suspended in, still to be exited
+    abandoned: Rc<RefCell<Vec<Token>>>,
+
+    // Local environments that functions have been defined in, which can outlive their scope in
+    // a cycle with those functions
+    closures: Vec<Weak<RefCell<Environment>>>,
+}
+
+impl Interpreter {
+    pub fn new() -> Interpreter {
+        let globals = Rc::new(RefCell::new(Environment::new()));
+        builtins::define_globals(&mut globals.borrow_mut());
+
+        Interpreter {
+            globals: Rc::clone(&globals),
+            environment: globals,
+
+            modules: HashMap::new(),
+            native_modules: HashMap::new(),
+            loading: Vec::new(),
+            search_path: Vec::new(),
+
+            script_args: Vec::new(),
+            env_vars: HashMap::new(),
+
+            call_depth: 0,
+            max_call_depth: MAX_CALL_DEPTH,
 
-pub fn interpret_program(statements: Vec<Statement>) {
-    for mut statement in statements {
-        statement.evaluate();
+            abandoned: Rc::new(RefCell::new(Vec::new())),
+            closures: Vec::new(),
+        }
     }
+
+    /// Sets the file of the script run in the global environment, which its imports are resolved
+    /// relative to
+    pub fn set_script_path(&mut self, path: &Path) {
+        self.globals.borrow_mut().define(
+            String::from("__file__"),
+            Token::from(TokenKind::String(path.display().to_string())),
+        );
+
+        if let Ok(canonical) = path.canonicalize() {
+            self.loading = vec![(canonical, path.display().to_string())];
+        }
+    }
+
+    pub fn set_script_args(&mut self, args: Vec<String>) {
+        self.script_args = args;
+    }
+
+    /// Sets how deeply function calls may nest before raising an error. Each call takes space on
+    /// the stack of the thread running the interpreter, so this should suit its size, as
+    /// described for [`MAX_CALL_DEPTH`].
+    pub fn set_max_call_depth(&mut self, depth: usize) {
+        self.max_call_depth = depth;
+    }
+
+    pub fn script_args(&self) -> &[String] {
+        &self.script_args
+    }
+
+    /// Sets an environment variable for the script and the programs it runs, without changing
+    /// the environment of the host process
+    pub fn set_env_var(&mut self, name: String, value: String) {
+        self.env_vars.insert(name, value);
+    }
+
+    /// The value of an environment variable as the script sees it, preferring any it has set
+    pub fn env_var(&self, name: &str) -> Option<String> {
+        match self.env_vars.get(name) {
+            Some(value) => Some(value.clone()),
+            None => env::var(name).ok(),
+        }
+    }
+
+    /// The environment variables set by the script, to pass on to the programs it runs
+    pub fn env_vars(&self) -> &HashMap<String, String> {
+        &self.env_vars
+    }
+
+    pub fn add_search_path(&mut self, directory: PathBuf) {
+        self.search_path.push(directory);
+    }
+
+    pub fn interpret(&mut self, statements: &[Statement]) -> Result<(), RuntimeError> {
+        for statement in statements {
+            match statement.evaluate(self) {
+                Ok(()) => {}
+                Err(Interrupt::Error(error)) => return Err(error),
+
+                // The parser rejects these outside of functions and loops
+                Err(..) => {}
+            }
+        }
+
+        Ok(())
+    }
+
+    pub fn execute_block(
+        &mut self,
+        statements: &[Statement],
+        environment: Rc<RefCell<Environment>>,
+    ) -> Result<(), Interrupt> {
+        let previous = std::mem::replace(&mut self.environment, environment);
+
+        let mut result = Ok(());
+
+        for statement in statements {
+            result = statement.evaluate(self);
+
+            if result.is_err() {
+                break;
+            }
+        }
+
+        let environment = std::mem::replace(&mut self.environment, previous);
+        Environment::release(environment);
+
+        result
+    }
+
+    /// Runs a block in a new scope nested within the current one
+    pub fn execute_scoped(&mut self, statements: &[Statement]) -> Result<(), Interrupt> {
+        let environment = Environment::from(self.current_environment());
+        self.execute_block(statements, Rc::new(RefCell::new(environment)))
+    }
+
+    /// Runs source code in the global environment, giving the value of its last statement if that
+    /// is an expression, or `None` otherwise
+    pub fn eval(&mut self, source: &str) -> Result<Token, Error> {
+        let mut statements = parser::parse(String::from(source)).map_err(Error::Syntax)?;
+
+        let last = match statements.last() {
+            Some(Statement::Expr(..)) => statements.pop(),
+            _ => None,
+        };
+
+        self.interpret(&statements)?;
+
+        match last {
+            Some(Statement::Expr(expr)) => Ok(expr.evaluate(self)?),
+            _ => Ok(Token::from(TokenKind::None)),
+        }
+    }
+
+    /// Runs a script file, which becomes the script that `__file__` and relative imports refer to
+    pub fn run_file(&mut self, path: &Path) -> Result<Token, Error> {
+        let source = std::fs::read_to_string(path).map_err(|_| {
+            RuntimeError::new(format!(
+                "IO error: could not open file '{}'.",
+                path.display()
+            ))
+        })?;
+
+        self.set_script_path(path);
+        self.eval(&source)
+    }
+
+    /// Defines a global function implemented by the host, which scripts can call with any number
+    /// of arguments
+    pub fn register_fn<F, R>(&mut self, name: &str, func: F)
+    where
+        F: Fn(&Args) -> Result<R, RuntimeError> + 'static,
+        R: IntoValue,
+    {
+        let func = host::native(name, func);
+
+        self.globals.borrow_mut().define(
+            String::from(name),
+            Token::from(TokenKind::NativeFunction(Rc::new(func))),
+        );
+    }
+
+    /// Makes a host module available to `import`, taking priority over any built in module or
+    /// script file of the same name
+    pub fn register_module(&mut self, module: NativeModule) {
+        let name = String::from(module.name());
+        self.native_modules
+            .insert(name, Rc::new(module.into_module()));
+    }
+
+    pub fn get_global(&self, name: &str) -> Option<Token> {
+        self.globals.borrow().get(name)
+    }
+
+    /// Looks up a global function, class or other callable value, for the host to call
+    pub fn get_function(&self, name: &str) -> Option<Callback> {
+        Callback::from_value(&self.get_global(name)?)
+    }
+
+    /// Defines or replaces a global variable, unless it is a constant
+    pub fn set_global(&mut self, name: &str, value: Token) -> Result<(), Error> {
+        let mut globals = self.globals.borrow_mut();
+
+        if globals.is_constant(name) {
+            return Err(Error::Runtime(RuntimeError::new(format!(
+                "Name error: cannot assign to constant '{}'.",
+                name
+            ))));
+        }
+
+        globals.define(String::from(name), value);
+        Ok(())
+    }
+
+    pub fn define(&mut self, name: String, value: Token) -> Result<(), RuntimeError> {
+        self.check_redefinition(&name)?;
+        self.environment.borrow_mut().define(name, value);
+
+        Ok(())
+    }
+
+    pub fn define_constant(&mut self, name: String, value: Token) -> Result<(), RuntimeError> {
+        self.check_redefinition(&name)?;
+        self.environment.borrow_mut().define_constant(name, value);
+
+        Ok(())
+    }
+
+    /// Constants can be shadowed by inner scopes, but never rebound within their own
+    fn check_redefinition(&self, name: &str) -> Result<(), RuntimeError> {
+        if self.environment.borrow().is_constant(name) {
+            return Err(RuntimeError::new(format!(
+                "Name error: cannot redefine constant '{}'.",
+                name
+            )));
+        }
+
+        Ok(())
+    }
+
+    pub fn lookup(&self, name: &Token) -> Result<Token, RuntimeError> {
+        match self.environment.borrow().get(&name.lexeme) {
+            Some(value) => Ok(value),
+            None => Err(RuntimeError::new(format!(
+                "Name error: undefined variable '{}'.",
+                name.lexeme
+            ))),
+        }
+    }
+
+    pub fn assign(&mut self, name: &Token, value: Token) -> Result<(), RuntimeError> {
+        match self.environment.borrow_mut().assign(&name.lexeme, value) {
+            Ok(()) => Ok(()),
+
+            Err(AssignError::Undefined) => Err(RuntimeError::new(format!(
+                "Name error: cannot assign to undefined variable '{}'.",
+                name.lexeme
+            ))),
+
+            Err(AssignError::Constant) => Err(RuntimeError::new(format!(
+                "Name error: cannot assign to constant '{}'.",
+                name.lexeme
+            ))),
+        }
+    }
+
+    /// Makes `name` refer to the global variable of that name for the rest of the current scope
+    pub fn declare_global(&mut self, name: &Token) -> Result<(), RuntimeError> {
+        let globals = Environment::global_of(&self.environment);
+
+        if !globals.borrow().contains(&name.lexeme) {
+            return Err(RuntimeError::new(format!(
+                "Name error: undefined global variable '{}'.",
+                name.lexeme
+            )));
+        }
+
+        self.environment
+            .borrow_mut()
+            .forward(name.lexeme.clone(), globals);
+
+        Ok(())
+    }
+
+    /// Makes `name` refer to the variable of that name in an enclosing function, skipping over any
+    /// bindings in the current function, for the rest of the current scope
+    pub fn declare_nonlocal(&mut self, name: &Token) -> Result<(), RuntimeError> {
+        let target = Environment::closure_of(&self.environment)
+            .and_then(|closure| Environment::resolve(&closure, &name.lexeme));
+
+        match target {
+            Some(target) if !Rc::ptr_eq(&target, &Environment::global_of(&target)) => {
+                self.environment
+                    .borrow_mut()
+                    .forward(name.lexeme.clone(), target);
+
+                Ok(())
+            }
+
+            _ => Err(RuntimeError::new(format!(
+                "Name error: no enclosing function defines variable '{}'.",
+                name.lexeme
+            ))),
+        }
+    }
+
+    /// Finds, runs and caches the module at a path such as `lib.utils`. Modules implemented in Rust
+    /// take priority, then modules are looked for next to the script containing the import, and
+    /// then in each search path directory.
+    pub fn import_module(&mut self, path: &[Token]) -> Result<Rc<Module>, RuntimeError> {
+        if let [name] = path {
+            if let Some(module) = self.native_modules.get(&name.lexeme) {
+                return Ok(Rc::clone(module));
+            }
+
+            if let Some(module) = builtins::native_module(self, &name.lexeme) {
+                let module = Rc::new(module);
+                self.native_modules
+                    .insert(name.lexeme.clone(), Rc::clone(&module));
+
+                return Ok(module);
+            }
+        }
+        let importer = match self.lookup_file() {
+            Some(file) => file.parent().map(Path::to_path_buf).unwrap_or_default(),
+            None => PathBuf::new(),
+        };
+
+        let mut directories = vec![importer];
+        directories.extend(self.search_path.iter().cloned());
+
+        let name: Vec<&str> = path.iter().map(|part| part.lexeme.as_str()).collect();
+
+        let file = match module::find(path, &directories) {
+            Some(file) => file,
+            None => {
+                return Err(RuntimeError::new(format!(
+                    "Import error: could not find module '{}'.",
+                    name.join(".")
+                )));
+            }
+        };
+
+        let canonical = file.canonicalize().unwrap_or_else(|_| file.clone());
+
+        if let Some(module) = self.modules.get(&canonical) {
+            return Ok(Rc::clone(module));
+        }
+
+        if let Some(start) = self.loading.iter().position(|(path, _)| *path == canonical) {
+            let mut chain: Vec<String> = self.loading[start..]
+                .iter()
+                .map(|(_, display)| display.clone())
+                .collect();
+
+            chain.push(file.display().to_string());
+
+            return Err(RuntimeError::new(format!(
+                "Import error: import cycle detected: {}.",
+                chain.join(" -> ")
+            )));
+        }
+
+        let statements = module::load(&file)?;
+
+        let mut globals = Environment::new();
+        builtins::define_globals(&mut globals);
+        globals.define(
+            String::from("__file__"),
+            Token::from(TokenKind::String(file.display().to_string())),
+        );
+
+        let globals = Rc::new(RefCell::new(globals));
+
+        self.loading
+            .push((canonical.clone(), file.display().to_string()));
+        let result = self.execute_block(&statements, Rc::clone(&globals));
+        self.loading.pop();
+
+        if let Err(Interrupt::Error(error)) = result {
+            return Err(error);
+        }
+
+        let module = Rc::new(Module::new(name.join("."), globals));
+        self.modules.insert(canonical, Rc::clone(&module));
+
+        Ok(module)
+    }
+
+    /// The file of the script the current code belongs to, if it was run from one
+    fn lookup_file(&self) -> Option<PathBuf> {
+        match self.environment.borrow().get("__file__")?.kind {
+            TokenKind::String(path) => Some(PathBuf::from(path)),
+            _ => None,
+        }
+    }
+
+    pub fn current_environment(&self) -> Rc<RefCell<Environment>> {
+        Rc::clone(&self.environment)
+    }
+
+    /// The environment for a function being defined to close over. Local ones are remembered, so
+    /// that those still kept alive by their functions can be cleared when the interpreter goes.
+    pub fn closure_environment(&mut self) -> Rc<RefCell<Environment>> {
+        let environment = self.current_environment();
+
+        let is_last = self
+            .closures
+            .last()
+            .is_some_and(|last| Weak::ptr_eq(last, &Rc::downgrade(&environment)));
+
+        if !Rc::ptr_eq(&environment, &self.globals) && !is_last {
+            // Environments that have been freed are swept out before the list has to grow
+            if self.closures.len() == self.closures.capacity() {
+                self.closures.retain(|closure| closure.strong_count() > 0);
+            }
+
+            self.closures.push(Rc::downgrade(&environment));
+        }
+
+        environment
+    }
+
+    /// Switches to a different environment, returning the one that was previously in use
+    pub fn replace_environment(
+        &mut self,
+        environment: Rc<RefCell<Environment>>,
+    ) -> Rc<RefCell<Environment>> {
+        std::mem::replace(&mut self.environment, environment)
+    }
+
+    pub fn call(&mut self, callee: Token, args: Vec<Token>) -> Result<Token, RuntimeError> {
+        self.call_with_keywords(callee, args, Vec::new())
+    }
+
+    pub fn call_with_keywords(
+        &mut self,
+        callee: Token,
+        args: Vec<Token>,
+        keywords: Vec<(Token, Token)>,
+    ) -> Result<Token, RuntimeError> {
+        match &callee.kind {
+            TokenKind::Function(func) => self.call_function(func, args, keywords),
+            TokenKind::NativeFunction(func) => func.call_with_keywords(self, args, keywords),
+
+            TokenKind::Type(class) => {
+                let instance = Token::from(TokenKind::Instance(Rc::new(Instance::new(Rc::clone(
+                    class,
+                )))));
+
+                match class.find_method("__init__") {
+                    Some(init) => {
+                        self.call_function(&init.bind(instance.clone()), args, keywords)?;
+                    }
+
+                    None => {
+                        if !args.is_empty() || !keywords.is_empty() {
+                            return Err(RuntimeError::new(format!(
+                                "Call error: class {} expected 0 arguments but got {}.",
+                                class.name,
+                                args.len() + keywords.len()
+                            )));
+                        }
+                    }
+                }
+
+                Ok(instance)
+            }
+
+            _ => {
+                if let Some((name, _)) = keywords.first() {
+                    return Err(RuntimeError::new(format!(
+                        "Call error: unexpected keyword argument '{}', {} only accepts positional arguments.",
+                        name.lexeme,
+                        callee.to_string().unwrap_or_default()
+                    )));
+                }
+
+                self.call_positional(callee, args)
+            }
+        }
+    }
+
+    fn call_positional(&mut self, callee: Token, args: Vec<Token>) -> Result<Token, RuntimeError> {
+        match callee.kind {
+            TokenKind::NativeFunction(func) => func.call(self, args),
+
+            TokenKind::Constructor(enumeration, index) => {
+                let fields = enumeration.variant_fields(index).len();
+
+                if args.len() != fields {
+                    return Err(RuntimeError::new(format!(
+                        "Call error: variant {}.{} expected {} arguments but got {}.",
+                        enumeration.name,
+                        enumeration.variant_name(index),
+                        fields,
+                        args.len()
+                    )));
+                }
+
+                let variant = Variant::new(enumeration, index, args);
+                Ok(Token::from(TokenKind::Variant(Rc::new(variant))))
+            }
+
+            kind => Err(RuntimeError::new(format!(
+                "Call error: could not call value of type {:?}.",
+                kind
+            ))),
+        }
+    }
+
+    fn call_function(
+        &mut self,
+        func: &Function,
+        args: Vec<Token>,
+        keywords: Vec<(Token, Token)>,
+    ) -> Result<Token, RuntimeError> {
+        let environment = self.bind_arguments(func, args, keywords)?;
+
+        if func.is_generator {
+            let generator = Generator::new(
+                func.name.lexeme.clone(),
+                Rc::clone(&func.body),
+                environment,
+                Rc::clone(&self.abandoned),
+            );
+            return Ok(Token::from(TokenKind::Generator(Rc::new(RefCell::new(
+                generator,
+            )))));
+        }
+
+        self.enter_call()?;
+        let result = self.execute_block(&func.body, environment);
+        self.leave_call();
+
+        match result {
+            Ok(()) => Ok(Token::from(TokenKind::None)),
+            Err(Interrupt::Return(value)) => Ok(value),
+            Err(Interrupt::Error(error)) => Err(error),
+
+            // The parser rejects loop control statements outside of loops
+            Err(..) => Ok(Token::from(TokenKind::None)),
+        }
+    }
+
+    /// Counts the start of a function call or generator resumption. Runaway recursion raises an
+    /// error scripts can catch, rather than overflowing the stack.
+    pub fn enter_call(&mut self) -> Result<(), RuntimeError> {
+        if self.call_depth >= self.max_call_depth {
+            return Err(RuntimeError::new(format!(
+                "Recursion error: maximum call depth of {} exceeded.",
+                self.max_call_depth
+            )));
+        }
+
+        self.call_depth += 1;
+        Ok(())
+    }
+
+    pub fn leave_call(&mut self) {
+        self.call_depth -= 1;
+    }
+
+    /// Creates the environment for a call, matching the arguments up with the function's parameters
+    fn bind_arguments(
+        &mut self,
+        func: &Function,
+        args: Vec<Token>,
+        keywords: Vec<(Token, Token)>,
+    ) -> Result<Rc<RefCell<Environment>>, RuntimeError> {
+        let name = &func.name.lexeme;
+        let mut slots: Vec<Option<Token>> = vec![None; func.params.len()];
+
+        let arg_count = args.len();
+        let mut positional = func.receiver.iter().cloned().chain(args);
+
+        // Parameters following a variadic one can only be passed by name
+        let variadic = func.params.iter().position(|param| param.variadic);
+        let positional_count = variadic.unwrap_or(func.params.len());
+
+        for slot in slots.iter_mut().take(positional_count) {
+            *slot = positional.next();
+        }
+
+        let rest: Vec<Token> = positional.collect();
+
+        match variadic {
+            Some(index) => {
+                slots[index] = Some(Token::from(TokenKind::List(Rc::new(RefCell::new(rest)))));
+            }
+
+            None => {
+                if !rest.is_empty() {
+                    let expected = positional_count - func.receiver.iter().count();
+
+                    return Err(RuntimeError::new(format!(
+                        "Call error: function {} expected at most {} positional arguments but got {}.",
+                        name, expected, arg_count
+                    )));
+                }
+            }
+        }
+
+        for (keyword, value) in keywords {
+            let index = func
+                .params
+                .iter()
+                .position(|param| !param.variadic && param.name.lexeme == keyword.lexeme);
+
+            match index {
+                Some(index) if slots[index].is_some() => {
+                    return Err(RuntimeError::new(format!(
+                        "Call error: function {} got multiple values for argument '{}'.",
+                        name, keyword.lexeme
+                    )));
+                }
+
+                Some(index) => slots[index] = Some(value),
+
+                None => {
+                    return Err(RuntimeError::new(format!(
+                        "Call error: function {} got an unexpected keyword argument '{}'.",
+                        name, keyword.lexeme
+                    )));
+                }
+            }
+        }
+
+        let environment = Rc::new(RefCell::new(Environment::for_function(Rc::clone(
+            &func.closure,
+        ))));
+
+        // Defaults are evaluated afresh inside the new environment, so they can refer to the
+        // parameters before them
+        let previous = self.replace_environment(Rc::clone(&environment));
+        let mut result = Ok(());
+
+        for (param, slot) in func.params.iter().zip(slots) {
+            let value = match (slot, &param.default) {
+                (Some(value), _) => Ok(value),
+                (None, Some(default)) => default.evaluate(self),
+                (None, None) => Err(RuntimeError::new(format!(
+                    "Call error: function {} missing required argument '{}'.",
+                    name, param.name.lexeme
+                ))),
+            };
+
+            match value {
+                Ok(value) => environment
+                    .borrow_mut()
+                    .define(param.name.lexeme.clone(), value),
+
+                Err(error) => {
+                    result = Err(error);
+                    break;
+                }
+            }
+        }
+
+        self.replace_environment(previous);
+        result.map(|()| environment)
+    }
+
+    pub fn get_property(&mut self, object: Token, name: &Token) -> Result<Token, RuntimeError> {
+        match &object.kind {
+            TokenKind::Instance(instance) => {
+                if let Some(value) = instance.get_field(&name.lexeme) {
+                    return Ok(value);
+                }
+
+                if let Some(method) = instance.class.find_method(&name.lexeme) {
+                    return Ok(Token::from(TokenKind::Function(Rc::new(
+                        method.bind(object.clone()),
+                    ))));
+                }
+
+                Err(RuntimeError::new(format!(
+                    "Attribute error: {} instance has no attribute '{}'.",
+                    instance.class.name, name.lexeme
+                )))
+            }
+
+            TokenKind::Type(class) => match class.find_method(&name.lexeme) {
+                Some(method) => Ok(Token::from(TokenKind::Function(method))),
+                None => Err(RuntimeError::new(format!(
+                    "Attribute error: class {} has no method '{}'.",
+                    class.name, name.lexeme
+                ))),
+            },
+
+            TokenKind::Enumeration(enumeration) => match enumeration.find_variant(&name.lexeme) {
+                // Variants without any fields don't need to be constructed
+                Some(index) if enumeration.variant_fields(index).is_empty() => {
+                    let variant = Variant::new(Rc::clone(enumeration), index, Vec::new());
+                    Ok(Token::from(TokenKind::Variant(Rc::new(variant))))
+                }
+
+                Some(index) => Ok(Token::from(TokenKind::Constructor(
+                    Rc::clone(enumeration),
+                    index,
+                ))),
+
+                None => Err(RuntimeError::new(format!(
+                    "Attribute error: enum {} has no variant '{}'.",
+                    enumeration.name, name.lexeme
+                ))),
+            },
+
+            TokenKind::Date(date) => match builtins::date_field(date, &name.lexeme) {
+                Some(value) => Ok(Token::from(TokenKind::Number(value))),
+                None => Err(RuntimeError::new(format!(
+                    "Attribute error: date has no attribute '{}'.",
+                    name.lexeme
+                ))),
+            },
+
+            TokenKind::Bytes(bytes) => match builtins::bytes_method(bytes, &name.lexeme) {
+                Some(method) => Ok(Token::from(TokenKind::NativeFunction(Rc::new(method)))),
+                None => Err(RuntimeError::new(format!(
+                    "Attribute error: bytes have no method '{}'.",
+                    name.lexeme
+                ))),
+            },
+
+            TokenKind::Set(set) => match builtins::set_method(set, &name.lexeme) {
+                Some(method) => Ok(Token::from(TokenKind::NativeFunction(Rc::new(method)))),
+                None => Err(RuntimeError::new(format!(
+                    "Attribute error: set has no method '{}'.",
+                    name.lexeme
+                ))),
+            },
+
+            TokenKind::Map(map) => match builtins::map_method(map, &name.lexeme) {
+                Some(method) => Ok(Token::from(TokenKind::NativeFunction(Rc::new(method)))),
+                None => Err(RuntimeError::new(format!(
+                    "Attribute error: map has no method '{}'.",
+                    name.lexeme
+                ))),
+            },
+
+            TokenKind::Module(module) => match module.get(&name.lexeme) {
+                Some(value) => Ok(value),
+                None => Err(RuntimeError::new(format!(
+                    "Attribute error: module {} has no attribute '{}'.",
+                    module.name, name.lexeme
+                ))),
+            },
+
+            TokenKind::Object(host_object) => host::property(host_object, &name.lexeme),
+
+            TokenKind::Variant(variant) => match variant.get_field(&name.lexeme) {
+                Some(value) => Ok(value),
+                None => Err(RuntimeError::new(format!(
+                    "Attribute error: variant {}.{} has no field '{}'.",
+                    variant.enumeration.name,
+                    variant.name(),
+                    name.lexeme
+                ))),
+            },
+
+            kind => Err(RuntimeError::new(format!(
+                "Attribute error: value of type {:?} has no attribute '{}'.",
+                kind, name.lexeme
+            ))),
+        }
+    }
+
+    pub fn set_property(
+        &mut self,
+        object: Token,
+        name: &Token,
+        value: Token,
+    ) -> Result<(), RuntimeError> {
+        match &object.kind {
+            TokenKind::Instance(instance) => {
+                instance.set_field(&name.lexeme, value);
+                Ok(())
+            }
+
+            TokenKind::Object(host_object) => host_object.set_property(&name.lexeme, value),
+
+            kind => Err(RuntimeError::new(format!(
+                "Attribute error: could not set attribute '{}' on value of type {:?}.",
+                name.lexeme, kind
+            ))),
+        }
+    }
+
+    pub fn get_index(&mut self, object: Token, index: Token) -> Result<Token, RuntimeError> {
+        match &object.kind {
+            TokenKind::List(list) => {
+                let list = list.borrow();
+                let index = Interpreter::resolve_index(&index, list.len())?;
+
+                Ok(list[index].clone())
+            }
+
+            TokenKind::String(val) => {
+                let chars: Vec<char> = val.chars().collect();
+                let index = Interpreter::resolve_index(&index, chars.len())?;
+
+                Ok(Token::from(TokenKind::String(chars[index].to_string())))
+            }
+
+            TokenKind::Bytes(bytes) => {
+                let index = Interpreter::resolve_index(&index, bytes.len())?;
+                Ok(Token::from(TokenKind::Number(bytes[index] as f64)))
+            }
+
+            TokenKind::Map(map) => match map.borrow().get(&index)? {
+                Some(value) => Ok(value),
+                None => Err(RuntimeError::new(format!(
+                    "Index error: key {} not found in map.",
+                    index.to_string().unwrap_or_default()
+                ))),
+            },
+
+            kind => Err(RuntimeError::new(format!(
+                "Index error: could not index into value of type {:?}.",
+                kind
+            ))),
+        }
+    }
+
+    pub fn set_index(
+        &mut self,
+        object: Token,
+        index: Token,
+        value: Token,
+    ) -> Result<(), RuntimeError> {
+        match &object.kind {
+            TokenKind::List(list) => {
+                let mut list = list.borrow_mut();
+                let index = Interpreter::resolve_index(&index, list.len())?;
+
+                list[index] = value;
+                Ok(())
+            }
+
+            TokenKind::Map(map) => map.borrow_mut().insert(index, value),
+
+            kind => Err(RuntimeError::new(format!(
+                "Index error: could not assign to an index of value of type {:?}.",
+                kind
+            ))),
+        }
+    }
+
+    /// Converts an index into a position within a sequence, counting backwards from the end when
+    /// it is negative
+    pub fn resolve_index(index: &Token, len: usize) -> Result<usize, RuntimeError> {
+        let val = match index.kind {
+            TokenKind::Number(val) if val.fract() == 0.0 => val,
+            _ => {
+                return Err(RuntimeError::new(format!(
+                    "Index error: expected a whole Number as index but got {:?}.",
+                    index.kind
+                )));
+            }
+        };
+
+        let position = if val < 0.0 { val + len as f64 } else { val };
+
+        if position < 0.0 || position >= len as f64 {
+            return Err(RuntimeError::new(format!(
+                "Index error: index {} is out of range for length {}.",
+                val, len
+            )));
+        }
+
+        Ok(position as usize)
+    }
+
+    pub fn iterate(&self, value: Token) -> Result<ValueIterator, RuntimeError> {
+        match value.kind {
+            TokenKind::String(val) => Ok(ValueIterator::Chars(val.chars().collect(), 0)),
+            TokenKind::List(list) => Ok(ValueIterator::List(list, 0)),
+            TokenKind::Bytes(bytes) => {
+                let numbers = bytes
+                    .iter()
+                    .map(|byte| Token::from(TokenKind::Number(*byte as f64)))
+                    .collect();
+
+                Ok(ValueIterator::List(Rc::new(RefCell::new(numbers)), 0))
+            }
+
+            // Maps and sets are iterated over as they were when iteration started
+            TokenKind::Map(map) => {
+                let keys = map.borrow().keys();
+                Ok(ValueIterator::List(Rc::new(RefCell::new(keys)), 0))
+            }
+            TokenKind::Set(set) => {
+                let values = set.borrow().values().to_vec();
+                Ok(ValueIterator::List(Rc::new(RefCell::new(values)), 0))
+            }
+            TokenKind::Generator(generator) => Ok(ValueIterator::Generator(generator)),
+
+            kind => Err(RuntimeError::new(format!(
+                "Iteration error: could not iterate over value of type {:?}.",
+                kind
+            ))),
+        }
+    }
+
+    /// Converts a value to the text printed for it, honouring `__str__` methods on instances
+    pub fn stringify(&mut self, value: &Token) -> Result<String, RuntimeError> {
+        if let TokenKind::Instance(instance) = &value.kind {
+            if let Some(method) = instance.class.find_method("__str__") {
+                let result =
+                    self.call_function(&method.bind(value.clone()), Vec::new(), Vec::new())?;
+
+                return match result.kind {
+                    TokenKind::String(val) => Ok(val),
+                    kind => Err(RuntimeError::new(format!(
+                        "Type error: __str__ must return a String, found {:?}.",
+                        kind
+                    ))),
+                };
+            }
+        }
+
+        match value.to_string() {
+            Some(val) => Ok(val),
+            None => Err(RuntimeError::new(format!(
+                "Conversion error: Could not convert type {:?} to String.",
+                value.kind
+            ))),
+        }
+    }
+
+    /// Compares two values, honouring `__eq__` methods on instances
+    pub fn values_equal(&mut self, lhs: &Token, rhs: &Token) -> Result<bool, RuntimeError> {
+        match self.call_operator(&TokenKind::EqualEqual, lhs, rhs)? {
+            Some(result) => self.truthy(&result),
+            None => Ok(lhs.is_equal(rhs.clone())),
+        }
+    }
+
+    /// Dispatches a binary operator to the matching `__add__`-style method of an instance
+    /// operand, trying the reflected method on the right hand side if the left one has none
+    pub fn call_operator(
+        &mut self,
+        op: &TokenKind,
+        lhs: &Token,
+        rhs: &Token,
+    ) -> Result<Option<Token>, RuntimeError> {
+        let (method, reflected) = match operator_methods(op) {
+            Some(methods) => methods,
+            None => return Ok(None),
+        };
+
+        if let Some(func) = Interpreter::find_instance_method(lhs, method) {
+            let result =
+                self.call_function(&func.bind(lhs.clone()), vec![rhs.clone()], Vec::new())?;
+            return Ok(Some(result));
+        }
+
+        if let Some(func) = Interpreter::find_instance_method(rhs, reflected) {
+            let result =
+                self.call_function(&func.bind(rhs.clone()), vec![lhs.clone()], Vec::new())?;
+            return Ok(Some(result));
+        }
+
+        Ok(None)
+    }
+
+    /// Dispatches a unary operator to the matching method of an instance operand
+    pub fn call_unary_operator(
+        &mut self,
+        op: &TokenKind,
+        rhs: &Token,
+    ) -> Result<Option<Token>, RuntimeError> {
+        let method = match op {
+            TokenKind::Minus => "__neg__",
+            _ => return Ok(None),
+        };
+
+        match Interpreter::find_instance_method(rhs, method) {
+            Some(func) => Ok(Some(self.call_function(
+                &func.bind(rhs.clone()),
+                Vec::new(),
+                Vec::new(),
+            )?)),
+            None => Ok(None),
+        }
+    }
+
+    /// Starts a `with` block over `manager`, returning the value its `as` clause binds. Managers
+    /// must have an `__exit__` method, and may have an `__enter__` method to produce that value.
+    pub fn enter_context(&mut self, manager: &Token) -> Result<Token, RuntimeError> {
+        if Interpreter::find_instance_method(manager, "__exit__").is_none() {
+            return Err(RuntimeError::new(format!(
+                "Type error: {:?} has no __exit__ method, so can't be used in a with block.",
+                manager.kind
+            )));
+        }
+
+        match Interpreter::find_instance_method(manager, "__enter__") {
+            Some(func) => self.call_function(&func.bind(manager.clone()), Vec::new(), Vec::new()),
+            None => Ok(manager.clone()),
+        }
+    }
+
+    /// Runs the exit action of a manager passed to `enter_context`
+    pub fn exit_context(&mut self, manager: &Token) -> Result<(), RuntimeError> {
+        if let Some(func) = Interpreter::find_instance_method(manager, "__exit__") {
+            self.call_function(&func.bind(manager.clone()), Vec::new(), Vec::new())?;
+        }
+
+        Ok(())
+    }
+
+    /// Exits the `with` blocks of generators that were dropped before finishing, innermost first.
+    /// Like errors from a generator nobody is consuming any more, errors from these exits are
+    /// ignored.
+    pub fn close_abandoned(&mut self) {
+        loop {
+            let managers = std::mem::take(&mut *self.abandoned.borrow_mut());
+
+            if managers.is_empty() {
+                return;
+            }
+
+            for manager in managers {
+                let _ = self.exit_context(&manager);
+            }
+        }
+    }
+
+    fn find_instance_method(value: &Token, name: &str) -> Option<Rc<Function>> {
+        match &value.kind {
+            TokenKind::Instance(instance) => instance.class.find_method(name),
+            _ => None,
+        }
+    }
+
+    pub fn truthy(&self, value: &Token) -> Result<bool, RuntimeError> {
+        match value.to_bool() {
+            Some(val) => Ok(val),
+            None => Err(RuntimeError::new(format!(
+                "Conversion error: Could not convert type {:?} to Bool.",
+                value.kind
+            ))),
+        }
+    }
+}
+
+// Functions keep the environment they were defined in alive, and it holds the functions in turn,
+// so these cycles are broken by hand once nothing more can run
+impl Drop for Interpreter {
+    fn drop(&mut self) {
+        // Generators suspended in `with` blocks are closed first, while the globals their exit
+        // actions may use are still there
+        let values = self.modules.values().chain(self.native_modules.values());
+        let mut values: Vec<Token> = values.flat_map(|module| module.values()).collect();
+        values.extend(self.globals.borrow().values());
+
+        for value in values {
+            if let TokenKind::Generator(generator) = value.kind {
+                Generator::close(&generator, self);
+            }
+        }
+
+        // Local environments kept alive by the functions defined in them are freed next
+        for closure in std::mem::take(&mut self.closures) {
+            if let Some(environment) = closure.upgrade() {
+                Environment::clear(&environment);
+            }
+        }
+
+        self.close_abandoned();
+
+        for module in self.modules.values().chain(self.native_modules.values()) {
+            module.clear();
+        }
+
+        Environment::clear(&self.globals);
+        self.close_abandoned();
+    }
+}
+
+impl Default for Interpreter {
+    fn default() -> Interpreter {
+        Interpreter::new()
+    }
+}
+
+/// Returns the method implementing a binary operator, along with the method to try on the right
+/// hand operand when the left one doesn't implement it
+fn operator_methods(op: &TokenKind) -> Option<(&'static str, &'static str)> {
+    let methods = match op {
+        TokenKind::Plus => ("__add__", "__radd__"),
+        TokenKind::Minus => ("__sub__", "__rsub__"),
+        TokenKind::Star => ("__mul__", "__rmul__"),
+        TokenKind::Slash => ("__div__", "__rdiv__"),
+        TokenKind::Percent => ("__mod__", "__rmod__"),
+        TokenKind::Caret => ("__pow__", "__rpow__"),
+
+        // Comparisons reflect onto their mirror image, e.g. `a < b` becomes `b > a`
+        TokenKind::Greater => ("__gt__", "__lt__"),
+        TokenKind::GreaterEqual => ("__ge__", "__le__"),
+        TokenKind::Less => ("__lt__", "__gt__"),
+        TokenKind::LessEqual => ("__le__", "__ge__"),
+        TokenKind::EqualEqual => ("__eq__", "__eq__"),
+        TokenKind::BangEqual => ("__ne__", "__ne__"),
+
+        _ => return None,
+    };
+
+    Some(methods)
 }
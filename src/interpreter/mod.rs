@@ -1,3 +1,9 @@
-pub use self::interpreter::interpret_program;
+pub use self::error::{Error, Interrupt, RuntimeError};
+pub use self::interpreter::{Interpreter, MAX_CALL_DEPTH};
 
+mod error;
+#[allow(clippy::module_inception)]
 pub mod interpreter;
+
+#[cfg(test)]
+mod test;
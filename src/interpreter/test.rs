@@ -0,0 +1,2766 @@
+use std::cell::{Cell, RefCell};
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+
+use crate::host::{self, Args, FromValue, HostObject, IntoValue, NativeModule};
+use crate::interpreter::{Error, Interpreter, RuntimeError};
+use crate::lexer::Lexer;
+use crate::parser::{self, Parser};
+use crate::token::{Token, TokenKind};
+
+fn try_run(source: &str) -> (Interpreter, Result<(), RuntimeError>) {
+    let tokens = Lexer::new(String::from(source)).collect_tokens();
+
+    let mut parser = Parser::new(tokens);
+    let statements = parser.parse_tokens();
+    assert!(!parser.contains_errors);
+
+    let mut interpreter = Interpreter::new();
+    let result = interpreter.interpret(&statements);
+
+    (interpreter, result)
+}
+
+fn contains_parse_errors(source: &str) -> bool {
+    let tokens = Lexer::new(String::from(source)).collect_tokens();
+
+    let mut parser = Parser::new(tokens);
+    parser.parse_tokens();
+    parser.contains_errors
+}
+
+fn run(source: &str) -> Interpreter {
+    let (interpreter, result) = try_run(source);
+    result.unwrap();
+    interpreter
+}
+
+fn global(interpreter: &Interpreter, name: &str) -> TokenKind {
+    interpreter.get_global(name).unwrap().kind
+}
+
+/// The printed form of a global variable
+fn text(interpreter: &mut Interpreter, name: &str) -> String {
+    let value = interpreter.get_global(name).unwrap();
+    interpreter.stringify(&value).unwrap()
+}
+
+fn field(interpreter: &mut Interpreter, name: &str, field: &str) -> TokenKind {
+    let object = interpreter.get_global(name).unwrap();
+    let field = Token::new(
+        TokenKind::Identifier(String::from(field)),
+        String::from(field),
+    );
+
+    interpreter.get_property(object, &field).unwrap().kind
+}
+
+#[test]
+fn interpret_function() {
+    let interpreter = run(r#"
+func fact(n):
+    if n <= 1:
+        return 1
+    return n * fact(n - 1)
+
+define x = fact(5)
+"#);
+
+    assert_eq!(global(&interpreter, "x"), TokenKind::Number(120.0));
+}
+
+#[test]
+fn interpret_class_fields_and_methods() {
+    let interpreter = run(r#"
+class Point:
+    func __init__(self, x, y):
+        self.x = x
+        self.y = y
+
+    func move(self, dx, dy):
+        self.x += dx
+        self.y = self.y + dy
+        return self
+
+define p = Point(1, 2)
+p.move(2, 3).move(1, 1)
+
+define x = p.x
+define y = p.y
+"#);
+
+    assert_eq!(global(&interpreter, "x"), TokenKind::Number(4.0));
+    assert_eq!(global(&interpreter, "y"), TokenKind::Number(6.0));
+}
+
+#[test]
+fn interpret_instance_printing() {
+    let mut interpreter = run(r#"
+class Point:
+    func __init__(self, x, y):
+        self.x = x
+        self.y = y
+
+class Name:
+    func __str__(self):
+        return "prot"
+
+define p = Point(1, 2)
+define n = Name()
+"#);
+
+    let p = interpreter.get_global("p").unwrap();
+    let n = interpreter.get_global("n").unwrap();
+
+    assert_eq!(interpreter.stringify(&p).unwrap(), "Point(x=1, y=2)");
+    assert_eq!(interpreter.stringify(&n).unwrap(), "prot");
+
+    // Instances that refer back to themselves are cut short rather than written forever
+    let mut interpreter = run(r#"
+class Node:
+    func __init__(self, name):
+        self.name = name
+
+define a = Node("a")
+define b = Node("b")
+a.me = a
+a.next = b
+b.next = a
+"#);
+
+    assert_eq!(
+        text(&mut interpreter, "a"),
+        "Node(name=a, me=<...>, next=Node(name=b, next=<...>))"
+    );
+}
+
+#[test]
+fn interpret_instance_equality() {
+    let interpreter = run(r#"
+class Point:
+    func __init__(self, x):
+        self.x = x
+
+class Money:
+    func __init__(self, cents):
+        self.cents = cents
+
+    func __eq__(self, other):
+        return self.cents == other.cents
+
+define p = Point(1)
+define same = p == p
+define different = p == Point(1)
+define money = Money(5) == Money(5)
+"#);
+
+    assert_eq!(global(&interpreter, "same"), TokenKind::Bool(true));
+    assert_eq!(global(&interpreter, "different"), TokenKind::Bool(false));
+    assert_eq!(global(&interpreter, "money"), TokenKind::Bool(true));
+}
+
+#[test]
+fn interpret_missing_attribute() {
+    let (_, result) = try_run(
+        r#"
+class Point:
+    func __init__(self):
+        self.x = 0
+
+define z = Point().z
+"#,
+    );
+
+    assert_eq!(
+        result.unwrap_err().message,
+        "Attribute error: Point instance has no attribute 'z'."
+    );
+}
+
+#[test]
+fn interpret_operator_overloading() {
+    let mut interpreter = run(r#"
+class Vector:
+    func __init__(self, x, y):
+        self.x = x
+        self.y = y
+
+    func __add__(self, other):
+        return Vector(self.x + other.x, self.y + other.y)
+
+    func __mul__(self, scale):
+        return Vector(self.x * scale, self.y * scale)
+
+    func __rmul__(self, scale):
+        return self * scale
+
+    func __neg__(self):
+        return Vector(-self.x, -self.y)
+
+    func __eq__(self, other):
+        return self.x == other.x and self.y == other.y
+
+    func __lt__(self, other):
+        return self.x < other.x
+
+define v = Vector(1, 2) + Vector(3, 4)
+define scaled = 2 * v * 2
+define negated = -v
+define less = Vector(1, 0) < Vector(2, 0)
+define greater = Vector(1, 0) > Vector(2, 0)
+define equal = Vector(1, 2) == Vector(1, 2)
+define not_equal = Vector(1, 2) != Vector(1, 2)
+
+define total = Vector(0, 0)
+total += Vector(5, 5)
+"#);
+
+    assert_eq!(field(&mut interpreter, "v", "x"), TokenKind::Number(4.0));
+    assert_eq!(
+        field(&mut interpreter, "scaled", "y"),
+        TokenKind::Number(24.0)
+    );
+    assert_eq!(
+        field(&mut interpreter, "negated", "x"),
+        TokenKind::Number(-4.0)
+    );
+    assert_eq!(
+        field(&mut interpreter, "total", "y"),
+        TokenKind::Number(5.0)
+    );
+
+    assert_eq!(global(&interpreter, "less"), TokenKind::Bool(true));
+    assert_eq!(global(&interpreter, "greater"), TokenKind::Bool(false));
+    assert_eq!(global(&interpreter, "equal"), TokenKind::Bool(true));
+    assert_eq!(global(&interpreter, "not_equal"), TokenKind::Bool(false));
+}
+
+#[test]
+fn interpret_missing_operator() {
+    let (_, result) = try_run(
+        r#"
+class Empty:
+    func __init__(self):
+        self.x = 0
+
+define e = Empty() + 1
+"#,
+    );
+
+    assert!(result
+        .unwrap_err()
+        .message
+        .starts_with("Expression error: Could not apply operation Plus"));
+}
+
+#[test]
+fn interpret_enum_variants() {
+    let mut interpreter = run(r#"
+enum Shape:
+    Circle(radius)
+    Rect(w, h)
+    Empty
+
+func area(shape):
+    if shape is Shape.Circle:
+        return 3 * shape.radius ^ 2
+    else if shape is Shape.Rect:
+        return shape.w * shape.h
+    return 0
+
+define circle = Shape.Circle(2)
+define rect = Shape.Rect(3, 4)
+define empty = Shape.Empty
+
+define circle_area = area(circle)
+define rect_area = area(rect)
+define empty_area = area(empty)
+
+define same = Shape.Rect(3, 4) == rect
+define different = Shape.Rect(4, 3) == rect
+define unit = Shape.Empty == empty
+
+# Equality compares values only, so it stays transitive
+define loose = Shape.Circle(1) == Shape.Circle
+define checks = [circle is Shape.Circle, circle is Shape.Rect, empty is Shape.Empty, 1 is Shape.Empty]
+"#);
+
+    assert_eq!(global(&interpreter, "circle_area"), TokenKind::Number(12.0));
+    assert_eq!(global(&interpreter, "rect_area"), TokenKind::Number(12.0));
+    assert_eq!(global(&interpreter, "empty_area"), TokenKind::Number(0.0));
+
+    assert_eq!(global(&interpreter, "same"), TokenKind::Bool(true));
+    assert_eq!(global(&interpreter, "different"), TokenKind::Bool(false));
+    assert_eq!(global(&interpreter, "unit"), TokenKind::Bool(true));
+    assert_eq!(global(&interpreter, "loose"), TokenKind::Bool(false));
+    assert_eq!(
+        text(&mut interpreter, "checks"),
+        "[true, false, true, false]"
+    );
+
+    let rect = interpreter.get_global("rect").unwrap();
+    let empty = interpreter.get_global("empty").unwrap();
+
+    assert_eq!(
+        interpreter.stringify(&rect).unwrap(),
+        "Shape.Rect(w=3, h=4)"
+    );
+    assert_eq!(interpreter.stringify(&empty).unwrap(), "Shape.Empty");
+}
+
+#[test]
+fn is_requires_variant() {
+    let (_, result) = try_run("define x = 1 is 1\n");
+
+    assert_eq!(
+        result.unwrap_err().message,
+        "Type error: the right side of 'is' must be an enum variant, found Number(1.0)."
+    );
+}
+
+#[test]
+fn interpret_enum_constructor_arity() {
+    let (_, result) = try_run(
+        r#"
+enum Shape:
+    Circle(radius)
+
+define circle = Shape.Circle(1, 2)
+"#,
+    );
+
+    assert_eq!(
+        result.unwrap_err().message,
+        "Call error: variant Shape.Circle expected 1 arguments but got 2."
+    );
+}
+
+#[test]
+fn interpret_generator() {
+    let interpreter = run(r#"
+func evens(limit):
+    for x in range(limit):
+        if x % 2 == 1:
+            continue
+        yield x
+    yield "done"
+
+define total = 0
+define last = none
+
+for x in evens(10):
+    if x == "done":
+        last = x
+    else:
+        total += x
+"#);
+
+    assert_eq!(global(&interpreter, "total"), TokenKind::Number(20.0));
+    assert_eq!(
+        global(&interpreter, "last"),
+        TokenKind::String(String::from("done"))
+    );
+}
+
+#[test]
+fn interpret_infinite_generator() {
+    let interpreter = run(r#"
+func naturals():
+    define n = 0
+    loop:
+        yield n
+        n += 1
+
+define g = naturals()
+next(g)
+next(g)
+define third = next(g)
+
+func once():
+    yield 1
+
+define h = once()
+next(h)
+define exhausted = next(h, "empty")
+"#);
+
+    assert_eq!(global(&interpreter, "third"), TokenKind::Number(2.0));
+    assert_eq!(
+        global(&interpreter, "exhausted"),
+        TokenKind::String(String::from("empty"))
+    );
+}
+
+#[test]
+fn interpret_generator_error() {
+    let (interpreter, result) = try_run(
+        r#"
+func failing():
+    yield 1
+    yield 1 / none
+
+define g = failing()
+define first = next(g)
+next(g)
+"#,
+    );
+
+    assert_eq!(global(&interpreter, "first"), TokenKind::Number(1.0));
+    assert!(result.unwrap_err().message.starts_with("Expression error"));
+}
+
+#[test]
+fn interpret_parameters() {
+    let mut interpreter = run(r#"
+func connect(host, port = 8080, *rest, timeout = 5):
+    return [host, port, rest, timeout]
+
+define defaults = connect("a")
+define positional = connect("b", 1, 2, 3, timeout = 9)
+define keywords = connect(port = 2, host = "c")
+"#);
+
+    let mut printed = |name: &str| {
+        let value = interpreter.get_global(name).unwrap();
+        interpreter.stringify(&value).unwrap()
+    };
+
+    assert_eq!(printed("defaults"), "[a, 8080, [], 5]");
+    assert_eq!(printed("positional"), "[b, 1, [2, 3], 9]");
+    assert_eq!(printed("keywords"), "[c, 2, [], 5]");
+}
+
+#[test]
+fn interpret_defaults_evaluated_per_call() {
+    let interpreter = run(r#"
+func fresh(items = [0]):
+    items[0] += 1
+    return items[0]
+
+func scale(a, b = a * 2):
+    return b
+
+define first = fresh()
+define second = fresh()
+define scaled = scale(3)
+"#);
+
+    assert_eq!(global(&interpreter, "first"), TokenKind::Number(1.0));
+    assert_eq!(global(&interpreter, "second"), TokenKind::Number(1.0));
+    assert_eq!(global(&interpreter, "scaled"), TokenKind::Number(6.0));
+}
+
+#[test]
+fn interpret_argument_errors() {
+    let error = |source: &str| try_run(source).1.unwrap_err().message;
+
+    let func = "func f(a, b = 1, *rest, c = 2):\n    return a\n";
+
+    assert_eq!(
+        error(&format!("{}f(1, d = 2)\n", func)),
+        "Call error: function f got an unexpected keyword argument 'd'."
+    );
+    assert_eq!(
+        error(&format!("{}f(1, a = 2)\n", func)),
+        "Call error: function f got multiple values for argument 'a'."
+    );
+    assert_eq!(
+        error(&format!("{}f(c = 2, c = 3)\n", func)),
+        "Call error: function f got multiple values for argument 'c'."
+    );
+    assert_eq!(
+        error(&format!("{}f(b = 2)\n", func)),
+        "Call error: function f missing required argument 'a'."
+    );
+    assert_eq!(
+        error("func g(a):\n    return a\ng(1, 2)\n"),
+        "Call error: function g expected at most 1 positional arguments but got 2."
+    );
+}
+
+#[test]
+fn scope_define_shadows_in_block() {
+    let interpreter = run(r#"
+define x = "outer"
+define seen = none
+
+if true:
+    define x = "inner"
+    seen = x
+
+define after = x
+"#);
+
+    assert_eq!(
+        global(&interpreter, "seen"),
+        TokenKind::String(String::from("inner"))
+    );
+    assert_eq!(
+        global(&interpreter, "after"),
+        TokenKind::String(String::from("outer"))
+    );
+}
+
+#[test]
+fn scope_assignment_updates_nearest_binding() {
+    let interpreter = run(r#"
+define x = 1
+
+if true:
+    x = 2
+
+    if true:
+        define x = 10
+        x = 20
+
+define result = x
+"#);
+
+    assert_eq!(global(&interpreter, "result"), TokenKind::Number(2.0));
+}
+
+#[test]
+fn scope_block_variables_do_not_leak() {
+    let (_, result) = try_run(
+        r#"
+if true:
+    define temp = 1
+
+define leaked = temp
+"#,
+    );
+
+    assert_eq!(
+        result.unwrap_err().message,
+        "Name error: undefined variable 'temp'."
+    );
+}
+
+#[test]
+fn scope_assignment_to_undefined_variable() {
+    let (_, result) = try_run("missing = 1\n");
+
+    assert_eq!(
+        result.unwrap_err().message,
+        "Name error: cannot assign to undefined variable 'missing'."
+    );
+}
+
+#[test]
+fn scope_loop_iterations_are_fresh() {
+    let interpreter = run(r#"
+define getters = [none, none, none]
+
+for i in range(3):
+    define captured = i * 10
+
+    func get():
+        return captured
+
+    getters[i] = get
+
+define first = getters[0]()
+define last = getters[2]()
+"#);
+
+    assert_eq!(global(&interpreter, "first"), TokenKind::Number(0.0));
+    assert_eq!(global(&interpreter, "last"), TokenKind::Number(20.0));
+
+    let i = Token::new(TokenKind::Identifier(String::from("i")), String::from("i"));
+    assert!(interpreter.lookup(&i).is_err());
+}
+
+#[test]
+fn scope_closures_share_enclosing_variables() {
+    let interpreter = run(r#"
+func counter():
+    define count = 0
+
+    func increment():
+        count += 1
+        return count
+
+    return increment
+
+define tick = counter()
+tick()
+tick()
+define count = tick()
+"#);
+
+    assert_eq!(global(&interpreter, "count"), TokenKind::Number(3.0));
+}
+
+#[test]
+fn scope_nonlocal_skips_shadowing_binding() {
+    let interpreter = run(r#"
+func outer():
+    define value = "outer"
+
+    func inner():
+        define value = "inner"
+
+        if true:
+            nonlocal value
+            value = "changed"
+
+        return value
+
+    define returned = inner()
+    return [returned, value]
+
+define result = outer()
+define returned = result[0]
+define value = result[1]
+"#);
+
+    assert_eq!(
+        global(&interpreter, "returned"),
+        TokenKind::String(String::from("inner"))
+    );
+    assert_eq!(
+        global(&interpreter, "value"),
+        TokenKind::String(String::from("changed"))
+    );
+}
+
+#[test]
+fn scope_global_declaration() {
+    let interpreter = run(r#"
+define total = 0
+
+func add(total):
+    global total
+    total += 5
+
+add(100)
+"#);
+
+    assert_eq!(global(&interpreter, "total"), TokenKind::Number(5.0));
+}
+
+#[test]
+fn scope_nonlocal_requires_enclosing_function_binding() {
+    let (_, result) = try_run(
+        r#"
+define value = 1
+
+func f():
+    nonlocal value
+    value = 2
+
+f()
+"#,
+    );
+
+    assert_eq!(
+        result.unwrap_err().message,
+        "Name error: no enclosing function defines variable 'value'."
+    );
+}
+
+#[test]
+fn scope_generator_blocks() {
+    let interpreter = run(r#"
+define x = "global"
+
+func gen():
+    for i in range(2):
+        define x = i
+        yield x
+
+    yield x
+
+define g = gen()
+define a = next(g)
+define b = next(g)
+define c = next(g)
+"#);
+
+    assert_eq!(global(&interpreter, "a"), TokenKind::Number(0.0));
+    assert_eq!(global(&interpreter, "b"), TokenKind::Number(1.0));
+    assert_eq!(
+        global(&interpreter, "c"),
+        TokenKind::String(String::from("global"))
+    );
+}
+
+#[test]
+fn const_shadowed_in_block() {
+    let interpreter = run(r#"
+const limit = 3
+define total = 0
+
+if true:
+    define limit = 10
+    limit += 1
+    total = limit
+"#);
+
+    assert_eq!(global(&interpreter, "limit"), TokenKind::Number(3.0));
+    assert_eq!(global(&interpreter, "total"), TokenKind::Number(11.0));
+}
+
+#[test]
+fn const_assignment_rejected_statically() {
+    assert!(contains_parse_errors("const x = 1\nx = 2\n"));
+    assert!(contains_parse_errors("const x = 1\nx += 2\n"));
+    assert!(contains_parse_errors("const x = 1\nconst x = 2\n"));
+    assert!(contains_parse_errors("const x = 1\nfunc f():\n    x = 2\n"));
+    assert!(!contains_parse_errors(
+        "const x = 1\nfunc f(x):\n    x = 2\n"
+    ));
+
+    assert_eq!(
+        parser::parse(String::from("const x = 1\n\nx = 2\n")).unwrap_err(),
+        vec![String::from(
+            "Parsing error (line 3): cannot assign to constant x."
+        )]
+    );
+    assert_eq!(
+        parser::parse(String::from("const x = 1\nconst x = 2\n")).unwrap_err(),
+        vec![String::from(
+            "Parsing error (line 2): cannot redefine constant x."
+        )]
+    );
+}
+
+#[test]
+fn const_assignment_rejected_at_runtime() {
+    let (interpreter, result) = try_run(
+        r#"
+const x = 1
+
+func f():
+    global x
+    x = 2
+
+f()
+"#,
+    );
+
+    assert_eq!(
+        result.unwrap_err().message,
+        "Name error: cannot assign to constant 'x'."
+    );
+    assert_eq!(global(&interpreter, "x"), TokenKind::Number(1.0));
+}
+
+const RESOURCE: &str = r#"
+define log = ""
+
+class Resource:
+    func __init__(self, name):
+        self.name = name
+
+    func __enter__(self):
+        log += "open " + self.name + ";"
+        return self.name
+
+    func __exit__(self):
+        log += "close " + self.name + ";"
+"#;
+
+fn log(interpreter: &mut Interpreter) -> String {
+    text(interpreter, "log")
+}
+
+#[test]
+fn with_exits_normally() {
+    let mut interpreter = run(&format!(
+        "{}{}",
+        RESOURCE,
+        r#"
+with Resource("a") as name:
+    log += "use " + name + ";"
+"#
+    ));
+
+    assert_eq!(log(&mut interpreter), "open a;use a;close a;");
+}
+
+#[test]
+fn with_exits_on_return_and_loop_control() {
+    let mut interpreter = run(&format!(
+        "{}{}",
+        RESOURCE,
+        r#"
+func f():
+    with Resource("a"):
+        return 1
+
+f()
+
+for i in range(2):
+    with Resource("b"):
+        continue
+
+loop:
+    with Resource("c"):
+        break
+"#
+    ));
+
+    assert_eq!(
+        log(&mut interpreter),
+        "open a;close a;open b;close b;open b;close b;open c;close c;"
+    );
+}
+
+#[test]
+fn with_exits_on_error() {
+    let (mut interpreter, result) = try_run(&format!(
+        "{}{}",
+        RESOURCE,
+        r#"
+with Resource("a"):
+    with Resource("b"):
+        missing()
+"#
+    ));
+
+    assert_eq!(
+        result.unwrap_err().message,
+        "Name error: undefined variable 'missing'."
+    );
+    assert_eq!(log(&mut interpreter), "open a;open b;close b;close a;");
+}
+
+#[test]
+fn with_in_generator() {
+    let mut interpreter = run(&format!(
+        "{}{}",
+        RESOURCE,
+        r#"
+func gen():
+    with Resource("a") as name:
+        yield name
+        yield name
+
+define g = gen()
+define first = next(g)
+log += "got " + first + ";"
+next(g)
+next(g, none)
+"#
+    ));
+
+    assert_eq!(log(&mut interpreter), "open a;got a;close a;");
+}
+
+#[test]
+fn with_in_abandoned_generator() {
+    let mut interpreter = run(&format!(
+        "{}{}",
+        RESOURCE,
+        r#"
+func gen(name):
+    with Resource(name):
+        with Resource(name + "2"):
+            for i in range(10):
+                yield i
+
+for value in gen("a"):
+    if value == 1:
+        break
+
+log += "after loop;"
+
+define g = gen("b")
+next(g)
+g = none
+log += "after drop;"
+
+define kept = gen("c")
+next(kept)
+
+func outer(name):
+    with Resource(name):
+        for value in gen(name + "-inner"):
+            yield value
+
+define nested = outer("d")
+next(nested)
+nested = none
+log += "after nested;"
+"#
+    ));
+
+    assert_eq!(
+        log(&mut interpreter),
+        "open a;open a2;close a2;close a;after loop;\
+         open b;open b2;close b2;close b;after drop;open c;open c2;\
+         open d;open d-inner;open d-inner2;close d-inner2;close d-inner;close d;after nested;"
+    );
+
+    // Generators still suspended when the interpreter goes are closed along with it
+    let closed = Rc::new(Cell::new(false));
+    let flag = Rc::clone(&closed);
+
+    let mut interpreter = Interpreter::new();
+    interpreter.register_fn("closed", move |_| {
+        flag.set(true);
+        Ok(())
+    });
+
+    interpreter
+        .eval(
+            r#"
+class Guard:
+    func __exit__(self):
+        closed()
+
+func gen():
+    with Guard():
+        yield 1
+        yield 2
+
+define g = gen()
+next(g)
+"#,
+        )
+        .unwrap();
+
+    assert!(!closed.get());
+    drop(interpreter);
+    assert!(closed.get());
+}
+
+#[test]
+fn with_requires_exit_method() {
+    let (_, result) = try_run("with 1:\n    print 1\n");
+
+    assert_eq!(
+        result.unwrap_err().message,
+        "Type error: Number(1.0) has no __exit__ method, so can't be used in a with block."
+    );
+}
+
+#[test]
+fn labeled_loops() {
+    let mut interpreter = run(r#"
+define found = none
+define visited = 0
+
+outer: for i in range(5):
+    for j in range(5):
+        visited += 1
+
+        if j > i:
+            continue outer
+
+        if i * j == 6:
+            found = [i, j]
+            break outer
+
+define count = 0
+
+outer: loop:
+    inner: while true:
+        count += 1
+        break outer
+"#);
+
+    let found = interpreter.get_global("found").unwrap();
+    assert_eq!(interpreter.stringify(&found), Ok(String::from("[3, 2]")));
+    assert_eq!(global(&interpreter, "visited"), TokenKind::Number(12.0));
+    assert_eq!(global(&interpreter, "count"), TokenKind::Number(1.0));
+}
+
+#[test]
+fn labeled_loops_in_generator() {
+    let interpreter = run(r#"
+func pairs():
+    rows: for i in range(3):
+        for j in range(3):
+            if j > i:
+                continue rows
+
+            yield i * 10 + j
+
+define total = 0
+
+for pair in pairs():
+    total += pair
+"#);
+
+    // 0 + 10 + 11 + 20 + 21 + 22
+    assert_eq!(global(&interpreter, "total"), TokenKind::Number(84.0));
+}
+
+#[test]
+fn unknown_loop_labels_rejected() {
+    assert!(contains_parse_errors(
+        "for i in range(2):\n    break outer\n"
+    ));
+    assert!(contains_parse_errors(
+        "outer: for i in range(2):\n    func f():\n        break outer\n"
+    ));
+    assert!(contains_parse_errors(
+        "outer: loop:\n    outer: loop:\n        break outer\n"
+    ));
+    assert!(contains_parse_errors("outer: print 1\n"));
+}
+
+/// Writes the given files into a fresh directory, returning its path
+fn write_files(name: &str, files: &[(&str, &str)]) -> PathBuf {
+    let directory = std::env::temp_dir().join(format!("prot-{}-{}", name, std::process::id()));
+    let _ = fs::remove_dir_all(&directory);
+
+    for (path, source) in files {
+        let path = directory.join(path);
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, source).unwrap();
+    }
+
+    directory
+}
+
+fn try_run_file(directory: &Path, main: &str) -> (Interpreter, Result<(), RuntimeError>) {
+    let path = directory.join(main);
+    let source = fs::read_to_string(&path).unwrap();
+
+    let mut parser = Parser::new(Lexer::new(source).collect_tokens());
+    let statements = parser.parse_tokens();
+    assert!(!parser.contains_errors);
+
+    let mut interpreter = Interpreter::new();
+    interpreter.set_script_path(&path);
+    let result = interpreter.interpret(&statements);
+
+    (interpreter, result)
+}
+
+#[test]
+fn import_modules() {
+    let directory = write_files(
+        "import",
+        &[
+            (
+                "main.prot",
+                r#"
+import lib.counter
+from lib.counter import increment, count as initial
+import helper as h
+
+increment()
+increment()
+
+define total = counter.count
+define same = h.counter == counter
+define doubled = h.double(21)
+"#,
+            ),
+            (
+                "helper.prot",
+                r#"
+import lib.counter
+
+func double(x):
+    return x * 2
+"#,
+            ),
+            (
+                "lib/counter.prot",
+                r#"
+define count = 0
+
+func increment():
+    global count
+    count += 1
+"#,
+            ),
+        ],
+    );
+
+    let (interpreter, result) = try_run_file(&directory, "main.prot");
+    result.unwrap();
+
+    assert_eq!(global(&interpreter, "total"), TokenKind::Number(2.0));
+    assert_eq!(global(&interpreter, "initial"), TokenKind::Number(0.0));
+    assert_eq!(global(&interpreter, "same"), TokenKind::Bool(true));
+    assert_eq!(global(&interpreter, "doubled"), TokenKind::Number(42.0));
+}
+
+#[test]
+fn import_runs_modules_once() {
+    let directory = write_files(
+        "import-once",
+        &[
+            (
+                "main.prot",
+                "import log\nimport first\nimport second\ndefine runs = log.runs\n",
+            ),
+            (
+                "log.prot",
+                "define runs = 0\n\nfunc record():\n    global runs\n    runs += 1\n",
+            ),
+            ("first.prot", "import shared\n"),
+            ("second.prot", "import shared\n"),
+            ("shared.prot", "import log\nlog.record()\n"),
+        ],
+    );
+
+    let (interpreter, result) = try_run_file(&directory, "main.prot");
+    result.unwrap();
+
+    assert_eq!(global(&interpreter, "runs"), TokenKind::Number(1.0));
+}
+
+#[test]
+fn import_search_path() {
+    let directory = write_files(
+        "import-search",
+        &[
+            ("scripts/main.prot", "from tools import name\n"),
+            ("library/tools.prot", "define name = \"tools\"\n"),
+        ],
+    );
+
+    let path = directory.join("scripts/main.prot");
+    let source = fs::read_to_string(&path).unwrap();
+    let statements = Parser::new(Lexer::new(source).collect_tokens()).parse_tokens();
+
+    let mut interpreter = Interpreter::new();
+    interpreter.set_script_path(&path);
+    assert!(interpreter.interpret(&statements).is_err());
+
+    interpreter.add_search_path(directory.join("library"));
+    interpreter.interpret(&statements).unwrap();
+
+    assert_eq!(
+        global(&interpreter, "name"),
+        TokenKind::String(String::from("tools"))
+    );
+}
+
+#[test]
+fn import_cycles_reported() {
+    let directory = write_files(
+        "import-cycle",
+        &[
+            ("main.prot", "import a\n"),
+            ("a.prot", "import b\n"),
+            ("b.prot", "import a\n"),
+        ],
+    );
+
+    let (_, result) = try_run_file(&directory, "main.prot");
+    let a = directory.join("a.prot").display().to_string();
+    let b = directory.join("b.prot").display().to_string();
+
+    assert_eq!(
+        result.unwrap_err().message,
+        format!(
+            "Import error: import cycle detected: {} -> {} -> {}.",
+            a, b, a
+        )
+    );
+}
+
+#[test]
+fn import_errors() {
+    let directory = write_files(
+        "import-errors",
+        &[("main.prot", "from lib import missing\n"), ("lib.prot", "")],
+    );
+
+    let (_, result) = try_run_file(&directory, "main.prot");
+
+    assert_eq!(
+        result.unwrap_err().message,
+        "Import error: cannot import name 'missing' from module lib."
+    );
+
+    let (_, result) = try_run("import nowhere\n");
+
+    assert_eq!(
+        result.unwrap_err().message,
+        "Import error: could not find module 'nowhere'."
+    );
+}
+
+#[test]
+fn math_module() {
+    let mut interpreter = run(r#"
+import math
+from math import sqrt, pi
+
+define root = sqrt(16)
+define circle = math.round(pi * 100, 1)
+define rounded = [math.floor(-1.5), math.ceil(1.2), math.round(2.5), math.abs(-3)]
+define extremes = [math.min(3, 1, 2), math.max([4, 9, 2])]
+define logs = [math.log(math.e), math.log(8, 2), math.log10(1000)]
+define checks = [math.isnan(math.nan), math.isinf(-math.inf), math.isnan(1)]
+define angle = math.atan2(1, 1) == pi / 4
+define same = math == math
+"#);
+
+    assert_eq!(global(&interpreter, "root"), TokenKind::Number(4.0));
+    assert_eq!(global(&interpreter, "circle"), TokenKind::Number(314.2));
+    assert_eq!(text(&mut interpreter, "rounded"), "[-2, 2, 3, 3]");
+    assert_eq!(text(&mut interpreter, "extremes"), "[1, 9]");
+    assert_eq!(text(&mut interpreter, "logs"), "[1, 3, 3]");
+    assert_eq!(text(&mut interpreter, "checks"), "[true, true, false]");
+    assert_eq!(global(&interpreter, "angle"), TokenKind::Bool(true));
+    assert_eq!(global(&interpreter, "same"), TokenKind::Bool(true));
+}
+
+#[test]
+fn math_module_errors() {
+    let (_, result) = try_run("import math\nmath.min([])\n");
+
+    assert_eq!(
+        result.unwrap_err().message,
+        "Value error: min expected at least one value."
+    );
+
+    let (_, result) = try_run("import math\nmath.max()\n");
+
+    assert_eq!(
+        result.unwrap_err().message,
+        "Call error: function max expected at least 1 arguments but got 0."
+    );
+
+    let (_, result) = try_run("import math\nmath.sqrt(\"4\")\n");
+
+    assert_eq!(
+        result.unwrap_err().message,
+        "Type error: sqrt expected a Number but got String(\"4\")."
+    );
+}
+
+#[test]
+fn try_catch() {
+    let interpreter = run(r#"
+define message = none
+define after = false
+
+try:
+    define x = 1
+    missing()
+    after = true
+catch error:
+    message = error
+
+define quiet = 0
+
+try:
+    quiet = 1
+catch:
+    quiet = 2
+
+define attempts = 0
+
+for i in range(3):
+    try:
+        attempts += 1
+        break
+    catch:
+        attempts = -1
+"#);
+
+    assert_eq!(
+        global(&interpreter, "message"),
+        TokenKind::String(String::from("Name error: undefined variable 'missing'."))
+    );
+    assert_eq!(global(&interpreter, "after"), TokenKind::Bool(false));
+    assert_eq!(global(&interpreter, "quiet"), TokenKind::Number(1.0));
+    assert_eq!(global(&interpreter, "attempts"), TokenKind::Number(1.0));
+}
+
+#[test]
+fn try_catch_in_generator() {
+    let interpreter = run(r#"
+func gen():
+    for i in range(3):
+        try:
+            yield i
+            if i == 1:
+                missing()
+            yield i * 10
+        catch error:
+            yield -1
+
+define total = 0
+define count = 0
+
+for value in gen():
+    total += value
+    count += 1
+"#);
+
+    // 0, 0, 1, -1, 2, 20
+    assert_eq!(global(&interpreter, "total"), TokenKind::Number(22.0));
+    assert_eq!(global(&interpreter, "count"), TokenKind::Number(6.0));
+}
+
+#[test]
+fn fs_module() {
+    let directory = write_files("fs", &[]);
+    fs::create_dir_all(&directory).unwrap();
+
+    let source = format!(
+        r#"
+import fs
+
+define root = "{}"
+
+fs.mkdir(root + "/data/nested")
+fs.write_text(root + "/data/a.txt", "one\ntwo")
+fs.append_text(root + "/data/a.txt", "\nthree")
+fs.rename(root + "/data/a.txt", root + "/data/b.txt")
+
+define text = fs.read_text(root + "/data/b.txt")
+define lines = fs.read_lines(root + "/data/b.txt")
+define entries = fs.list_dir(root + "/data")
+define before = fs.exists(root + "/data/nested")
+
+fs.write_text(root + "/data/nested/c.txt", "")
+define refused = none
+
+try:
+    fs.remove(root + "/data/nested")
+catch message:
+    refused = message
+
+fs.remove(root + "/data/nested/c.txt")
+fs.remove(root + "/data/nested")
+define after = fs.exists(root + "/data/nested")
+
+fs.mkdir(root + "/tree/deep")
+fs.write_text(root + "/tree/deep/d.txt", "")
+fs.remove(root + "/tree", recursive=true)
+define tree_after = fs.exists(root + "/tree")
+
+define error = none
+
+try:
+    fs.read_text(root + "/missing.txt")
+catch message:
+    error = message
+"#,
+        directory.display()
+    );
+
+    let mut interpreter = run(&source);
+
+    assert_eq!(
+        global(&interpreter, "text"),
+        TokenKind::String(String::from("one\ntwo\nthree"))
+    );
+    assert_eq!(text(&mut interpreter, "lines"), "[one, two, three]");
+    assert_eq!(text(&mut interpreter, "entries"), "[b.txt, nested]");
+    assert_eq!(global(&interpreter, "before"), TokenKind::Bool(true));
+    assert_eq!(global(&interpreter, "after"), TokenKind::Bool(false));
+    assert_eq!(global(&interpreter, "tree_after"), TokenKind::Bool(false));
+    assert_eq!(
+        global(&interpreter, "refused"),
+        TokenKind::String(format!(
+            "IO error: could not remove '{}/data/nested'.",
+            directory.display()
+        ))
+    );
+    assert_eq!(
+        global(&interpreter, "error"),
+        TokenKind::String(format!(
+            "IO error: could not open file '{}/missing.txt'.",
+            directory.display()
+        ))
+    );
+}
+
+#[test]
+fn sys_args() {
+    let statements = Parser::new(
+        Lexer::new(String::from(
+            "import sys\ndefine args = sys.args\ndefine count = len(sys.args)\n",
+        ))
+        .collect_tokens(),
+    )
+    .parse_tokens();
+
+    let mut interpreter = Interpreter::new();
+    interpreter.set_script_args(vec![String::from("--verbose"), String::from("input.txt")]);
+    interpreter.interpret(&statements).unwrap();
+
+    assert_eq!(text(&mut interpreter, "args"), "[--verbose, input.txt]");
+    assert_eq!(global(&interpreter, "count"), TokenKind::Number(2.0));
+}
+
+#[test]
+fn exit_is_not_caught() {
+    let (mut interpreter, result) = try_run(&format!(
+        "{}{}",
+        RESOURCE,
+        r#"
+define caught = false
+
+with Resource("a"):
+    try:
+        exit(3)
+    catch:
+        caught = true
+"#
+    ));
+
+    assert_eq!(result.unwrap_err().exit_code, Some(3));
+    assert_eq!(global(&interpreter, "caught"), TokenKind::Bool(false));
+    assert_eq!(log(&mut interpreter), "open a;close a;");
+
+    let (_, result) = try_run("exit(1.5)\n");
+
+    assert_eq!(
+        result.unwrap_err().message,
+        "Value error: exit code must be a whole number from 0 to 255, found 1.5."
+    );
+
+    for code in ["300", "-1"] {
+        let (_, result) = try_run(&format!("exit({})\n", code));
+
+        assert_eq!(
+            result.unwrap_err().message,
+            format!(
+                "Value error: exit code must be a whole number from 0 to 255, found {}.",
+                code
+            )
+        );
+    }
+
+    let (_, result) = try_run("exit(255)\n");
+    assert_eq!(result.unwrap_err().exit_code, Some(255));
+}
+
+#[test]
+fn maps() {
+    let mut interpreter = run(r#"
+define ages = {"ann": 31, "bob": 25}
+ages["cat"] = 40
+ages["bob"] += 1
+
+define names = ""
+define total = 0
+
+for name in ages:
+    names += name
+    total += ages[name]
+"#);
+
+    assert_eq!(
+        text(&mut interpreter, "ages"),
+        "{ann: 31, bob: 26, cat: 40}"
+    );
+    assert_eq!(
+        global(&interpreter, "names"),
+        TokenKind::String(String::from("annbobcat"))
+    );
+    assert_eq!(global(&interpreter, "total"), TokenKind::Number(97.0));
+
+    let mut interpreter = run(r#"
+define m = {
+    1: "one",
+    "two": 2,
+}
+
+define missing = m.get(3)
+define fallback = m.get(3, "none")
+define has = [m.contains(1), m.contains("1")]
+define removed = m.remove(1)
+define size = len(m)
+define same = {"a": [1, 2], "b": none} == {"b": none, "a": [1, 2]}
+define different = {"a": 1} == {"a": 2}
+define keys = m.keys()
+"#);
+
+    assert_eq!(global(&interpreter, "missing"), TokenKind::None);
+    assert_eq!(
+        global(&interpreter, "fallback"),
+        TokenKind::String(String::from("none"))
+    );
+    assert_eq!(text(&mut interpreter, "has"), "[true, false]");
+    assert_eq!(
+        global(&interpreter, "removed"),
+        TokenKind::String(String::from("one"))
+    );
+    assert_eq!(global(&interpreter, "size"), TokenKind::Number(1.0));
+    assert_eq!(global(&interpreter, "same"), TokenKind::Bool(true));
+    assert_eq!(global(&interpreter, "different"), TokenKind::Bool(false));
+    assert_eq!(text(&mut interpreter, "keys"), "[two]");
+}
+
+#[test]
+fn map_errors() {
+    let (_, result) = try_run("define m = {\"a\": 1}\nm[\"b\"]\n");
+
+    assert_eq!(
+        result.unwrap_err().message,
+        "Index error: key b not found in map."
+    );
+
+    let (_, result) = try_run("define m = {[1]: 1}\n");
+
+    assert_eq!(
+        result.unwrap_err().message,
+        "Type error: value of type List(RefCell { value: [Token { kind: Number(1.0), lexeme: \"1\", line: 1 }] }) can't be used as a map key."
+    );
+}
+
+#[test]
+fn self_referencing_collections() {
+    let mut interpreter = run(r#"
+define l = [1]
+l[0] = l
+define same = l == l
+
+define a = [1, 2]
+a[1] = a
+define b = [1, 2]
+b[1] = b
+define alike = a == b
+
+define m = {"name": "m"}
+m["self"] = m
+define n = {"name": "m"}
+n["self"] = n
+define maps_alike = m == n
+
+define c = [1, 2]
+c[1] = c
+c[0] = 3
+define differ = a == c
+"#);
+
+    assert_eq!(text(&mut interpreter, "l"), "[<...>]");
+    assert_eq!(text(&mut interpreter, "a"), "[1, <...>]");
+    assert_eq!(text(&mut interpreter, "m"), "{name: m, self: <...>}");
+
+    assert_eq!(global(&interpreter, "same"), TokenKind::Bool(true));
+    assert_eq!(global(&interpreter, "alike"), TokenKind::Bool(true));
+    assert_eq!(global(&interpreter, "maps_alike"), TokenKind::Bool(true));
+    assert_eq!(global(&interpreter, "differ"), TokenKind::Bool(false));
+}
+
+#[test]
+fn json_parse() {
+    let mut interpreter = run(r#"
+import json
+
+define value = json.parse("{\"name\": \"prot\", \"tags\": [1, 2.5, -3e2], \"ok\": true, \"none\": null}")
+define name = value["name"]
+define escaped = json.parse("\"tab\\t\\u00e9\\ud83d\\ude00\"")
+"#);
+
+    assert_eq!(
+        text(&mut interpreter, "value"),
+        "{name: prot, tags: [1, 2.5, -300], ok: true, none: none}"
+    );
+    assert_eq!(
+        global(&interpreter, "name"),
+        TokenKind::String(String::from("prot"))
+    );
+    assert_eq!(
+        global(&interpreter, "escaped"),
+        TokenKind::String(String::from("tab\t\u{e9}\u{1f600}"))
+    );
+}
+
+#[test]
+fn json_parse_errors() {
+    let (_, result) = try_run("import json\njson.parse(\"{\\n  \\\"a\\\": [1, 2,]\\n}\")\n");
+
+    assert_eq!(
+        result.unwrap_err().message,
+        "Value error: invalid JSON at line 2, column 14: unexpected ']'."
+    );
+
+    let (_, result) = try_run("import json\njson.parse(\"[1] 2\")\n");
+
+    assert_eq!(
+        result.unwrap_err().message,
+        "Value error: invalid JSON at line 1, column 5: unexpected '2' after the end of the value."
+    );
+
+    let (_, result) = try_run("import json\njson.parse(\"01\")\n");
+
+    assert_eq!(
+        result.unwrap_err().message,
+        "Value error: invalid JSON at line 1, column 2: unexpected '1' after the end of the value."
+    );
+
+    // Nesting is limited rather than left to overflow the stack, and the error can be caught
+    let mut interpreter = run(&format!(
+        r#"
+import json
+
+define deepest = json.parse("{}{}")
+
+define message = none
+
+try:
+    json.parse("{}")
+catch error:
+    message = error
+"#,
+        "[".repeat(512),
+        "]".repeat(512),
+        "[".repeat(200000)
+    ));
+
+    assert_eq!(
+        text(&mut interpreter, "message"),
+        "Value error: invalid JSON at line 1, column 513: arrays and objects nested more than 512 deep."
+    );
+    assert!(matches!(
+        global(&interpreter, "deepest"),
+        TokenKind::List(..)
+    ));
+}
+
+#[test]
+fn json_stringify() {
+    let interpreter = run(r#"
+import json
+
+define value = {"name": "a\"b", "list": [1, 2.5, true, none], "empty": [], "nested": {}}
+define compact = json.stringify(value)
+define pretty = json.stringify({"a": [1, {"b": 2}]}, 2)
+define round_trip = json.parse(json.stringify(value)) == value
+"#);
+
+    assert_eq!(
+        global(&interpreter, "compact"),
+        TokenKind::String(String::from(
+            r#"{"name":"a\"b","list":[1,2.5,true,null],"empty":[],"nested":{}}"#
+        ))
+    );
+    assert_eq!(
+        global(&interpreter, "pretty"),
+        TokenKind::String(String::from(
+            "{\n  \"a\": [\n    1,\n    {\n      \"b\": 2\n    }\n  ]\n}"
+        ))
+    );
+    assert_eq!(global(&interpreter, "round_trip"), TokenKind::Bool(true));
+
+    let (_, result) = try_run("import json\ndefine l = [1]\nl[0] = l\njson.stringify(l)\n");
+
+    assert_eq!(
+        result.unwrap_err().message,
+        "Value error: could not convert a value that contains itself to JSON."
+    );
+}
+
+#[test]
+fn os_environment() {
+    let mut interpreter = run(r#"
+import os
+
+define missing = os.getenv("PROT_TEST_UNSET_VARIABLE")
+define fallback = os.getenv("PROT_TEST_UNSET_VARIABLE", "default")
+os.setenv("PROT_TEST_OS_VARIABLE", "set")
+define value = os.getenv("PROT_TEST_OS_VARIABLE")
+define dir = os.cwd()
+os.chdir(dir)
+"#);
+
+    assert_eq!(global(&interpreter, "missing"), TokenKind::None);
+    assert_eq!(text(&mut interpreter, "fallback"), "default");
+    assert_eq!(text(&mut interpreter, "value"), "set");
+
+    // Variables are kept by the interpreter, and passed on to the programs it runs
+    assert!(std::env::var("PROT_TEST_OS_VARIABLE").is_err());
+
+    let mut other = run(r#"
+import os
+
+define unset = os.getenv("PROT_TEST_OS_VARIABLE")
+"#);
+    assert_eq!(global(&other, "unset"), TokenKind::None);
+
+    let result = other
+        .eval("os.run(\"sh\", [\"-c\", \"echo $PROT_TEST_OS_VARIABLE\"])[\"stdout\"]")
+        .unwrap();
+    assert_eq!(result.kind, TokenKind::String(String::from("\n")));
+
+    let result = interpreter
+        .eval("os.run(\"sh\", [\"-c\", \"echo $PROT_TEST_OS_VARIABLE\"])[\"stdout\"]")
+        .unwrap();
+    assert_eq!(result.kind, TokenKind::String(String::from("set\n")));
+
+    assert_eq!(
+        text(&mut interpreter, "dir"),
+        std::env::current_dir().unwrap().display().to_string()
+    );
+
+    let (_, result) = try_run("import os\nos.chdir(\"/no/such/directory\")\n");
+
+    assert_eq!(
+        result.unwrap_err().message,
+        "IO error: could not change directory to '/no/such/directory'."
+    );
+}
+
+#[test]
+fn os_run() {
+    let mut interpreter = run(r#"
+import os
+
+define result = os.run("sh", ["-c", "echo out; echo err 1>&2; exit 3"])
+define streamed = os.run("true", [], true)
+define missing = os.run("prot-test-no-such-command")
+"#);
+
+    assert_eq!(
+        text(&mut interpreter, "result"),
+        "{stdout: out\n, stderr: err\n, status: 3, ok: false, error: none}"
+    );
+    assert_eq!(
+        text(&mut interpreter, "streamed"),
+        "{stdout: , stderr: , status: 0, ok: true, error: none}"
+    );
+
+    let missing = global(&interpreter, "missing");
+
+    match missing {
+        TokenKind::Map(map) => {
+            let map = map.borrow();
+            let get = |name: &str| {
+                map.get(&Token::from(TokenKind::String(String::from(name))))
+                    .unwrap()
+                    .unwrap()
+                    .kind
+            };
+
+            assert_eq!(get("ok"), TokenKind::Bool(false));
+            assert_eq!(get("status"), TokenKind::None);
+            assert!(matches!(get("error"), TokenKind::String(error)
+                if error.starts_with("could not run command 'prot-test-no-such-command'")));
+        }
+        kind => panic!("expected a map but got {:?}", kind),
+    }
+}
+
+#[test]
+fn time_module() {
+    let mut interpreter = run(r#"
+import time
+
+define start = time.monotonic()
+time.sleep(0.01)
+define elapsed = time.monotonic() - start
+define now = time.now()
+
+define date = time.parse("2024-02-29T23:15:30.25+05:30")
+define fields = [date.year, date.month, date.day, date.hour, date.minute, date.second, date.nanosecond, date.offset, date.weekday]
+define timestamp = date.timestamp
+define utc = time.date(timestamp)
+define same = utc == date
+define shifted = time.date(0, -90 * 60)
+define formatted = time.format(86400 * 365)
+define plain = time.parse("1969-12-31")
+"#);
+
+    assert!(matches!(global(&interpreter, "elapsed"), TokenKind::Number(n) if n >= 0.01));
+    assert!(matches!(global(&interpreter, "now"), TokenKind::Number(n) if n > 1.7e9));
+
+    assert_eq!(
+        text(&mut interpreter, "fields"),
+        "[2024, 2, 29, 23, 15, 30, 250000000, 19800, 4]"
+    );
+    assert_eq!(
+        global(&interpreter, "timestamp"),
+        TokenKind::Number(1709228730.25)
+    );
+    assert_eq!(
+        text(&mut interpreter, "date"),
+        "2024-02-29T23:15:30.25+05:30"
+    );
+    assert_eq!(text(&mut interpreter, "utc"), "2024-02-29T17:45:30.25Z");
+    assert_eq!(global(&interpreter, "same"), TokenKind::Bool(true));
+    assert_eq!(
+        text(&mut interpreter, "shifted"),
+        "1969-12-31T22:30:00-01:30"
+    );
+    assert_eq!(text(&mut interpreter, "formatted"), "1971-01-01T00:00:00Z");
+    assert_eq!(text(&mut interpreter, "plain"), "1969-12-31T00:00:00Z");
+}
+
+#[test]
+fn time_module_errors() {
+    let (_, result) = try_run("import time\ntime.parse(\"2023-02-29\")\n");
+
+    assert_eq!(
+        result.unwrap_err().message,
+        "Value error: invalid ISO-8601 timestamp '2023-02-29': day out of range."
+    );
+
+    let (_, result) = try_run("import time\ntime.parse(\"2024-01-01T10:00Zulu\")\n");
+
+    assert_eq!(
+        result.unwrap_err().message,
+        "Value error: invalid ISO-8601 timestamp '2024-01-01T10:00Zulu': unexpected 'u'."
+    );
+
+    let (_, result) = try_run("import time\ntime.date().hours\n");
+
+    assert_eq!(
+        result.unwrap_err().message,
+        "Attribute error: date has no attribute 'hours'."
+    );
+
+    let (_, result) = try_run("import time\ntime.sleep(-1)\n");
+
+    assert_eq!(
+        result.unwrap_err().message,
+        "Value error: sleep expected a non-negative number of seconds but got -1."
+    );
+}
+
+#[test]
+fn random_module() {
+    let mut interpreter = run(r#"
+import random
+
+func draw():
+    define number = random.randint(1, 100)
+    define fraction = random.random()
+    define list = [1, 2, 3, 4, 5]
+    random.shuffle(list)
+    return [number, fraction, list, random.choice(["a", "b", "c"])]
+
+random.seed(42)
+define first = draw()
+random.seed(42)
+define second = draw()
+define repeated = first == second
+
+define in_range = true
+
+for _ in range(200):
+    define value = random.randint(-3, 3)
+    define fraction = random.random()
+
+    if value < -3 or value > 3 or fraction < 0 or fraction >= 1:
+        in_range = false
+"#);
+
+    // The generator is in-tree, so these are the same on every platform
+    assert_eq!(
+        text(&mut interpreter, "first"),
+        "[43, 0.3789802506626686, [3, 1, 4, 2, 5], b]"
+    );
+    assert_eq!(global(&interpreter, "repeated"), TokenKind::Bool(true));
+    assert_eq!(global(&interpreter, "in_range"), TokenKind::Bool(true));
+}
+
+#[test]
+fn random_module_errors() {
+    let (_, result) = try_run("import random\nrandom.choice([])\n");
+
+    assert_eq!(
+        result.unwrap_err().message,
+        "Index error: choice can't pick from an empty list."
+    );
+
+    let (_, result) = try_run("import random\nrandom.randint(5, 1)\n");
+
+    assert_eq!(
+        result.unwrap_err().message,
+        "Value error: randint expected a range with 5 no greater than 1."
+    );
+
+    let (_, result) = try_run("import random\nrandom.randint(1.5, 2)\n");
+
+    assert_eq!(
+        result.unwrap_err().message,
+        "Type error: randint expected a whole number but got 1.5."
+    );
+}
+
+#[test]
+fn regex_matching() {
+    let mut interpreter = run(r#"
+import re
+
+define date = re.search("(?P<year>\d{4})-(\d\d)-(?<day>\d\d)?", "due: 2024-06-")
+define anchored = re.match("\d+", "abc 123")
+define whole = re.match("[a-c]+\s", "abc 123")
+define words = re.find_all("\b\w+\b", "one, two; three")
+define pairs = re.find_all("(\w)=(\d)", "a=1 b=2 c")
+define keys = re.find_all("(\w)=", "a=1 b=2")
+define lazy = re.find_all("<.+?>", "<a><b>")
+define alternatives = re.find_all("cat|dog|(?:bird)s?", "dogs, birds, a cat")
+define nested = re.match("(a*)*b", "aaab")
+"#);
+
+    assert_eq!(
+        text(&mut interpreter, "date"),
+        "{match: 2024-06-, start: 5, end: 13, groups: [2024, 06, none], named: {year: 2024, day: none}}"
+    );
+    assert_eq!(global(&interpreter, "anchored"), TokenKind::None);
+    assert_eq!(
+        text(&mut interpreter, "whole"),
+        "{match: abc , start: 0, end: 4, groups: [], named: {}}"
+    );
+    assert_eq!(text(&mut interpreter, "words"), "[one, two, three]");
+    assert_eq!(text(&mut interpreter, "pairs"), "[[a, 1], [b, 2]]");
+    assert_eq!(text(&mut interpreter, "keys"), "[a, b]");
+    assert_eq!(text(&mut interpreter, "lazy"), "[<a>, <b>]");
+    assert_eq!(text(&mut interpreter, "alternatives"), "[dog, birds, cat]");
+    assert_eq!(
+        text(&mut interpreter, "nested"),
+        "{match: aaab, start: 0, end: 4, groups: [aaa], named: {}}"
+    );
+}
+
+#[test]
+fn regex_replace_and_split() {
+    let mut interpreter = run(r#"
+import re
+
+func shout(found):
+    return found["match"] + "!"
+
+define swapped = re.replace("(\w+)@(?P<host>\w+)", "me@home you@work", "${host}:$1 $$")
+define first = re.replace("o", "foo boo", "0", 1)
+define called = re.replace("[aeiou]", "hello", shout)
+define pieces = re.split(",\s*", "a, b,c,,d")
+define limited = re.split("-", "a-b-c", 1)
+define letters = re.split("", "abc")
+"#);
+
+    assert_eq!(text(&mut interpreter, "swapped"), "home:me $ work:you $");
+    assert_eq!(text(&mut interpreter, "first"), "f0o boo");
+    assert_eq!(text(&mut interpreter, "called"), "he!llo!");
+    assert_eq!(text(&mut interpreter, "pieces"), "[a, b, c, , d]");
+    assert_eq!(text(&mut interpreter, "limited"), "[a, b-c]");
+    assert_eq!(text(&mut interpreter, "letters"), "[, a, b, c, ]");
+}
+
+#[test]
+fn regex_errors() {
+    let (_, result) = try_run("import re\nre.search(\"(ab\", \"ab\")\n");
+
+    assert_eq!(
+        result.unwrap_err().message,
+        "Value error: invalid regular expression '(ab': unbalanced parenthesis at position 0."
+    );
+
+    let (_, result) = try_run("import re\nre.search(\"a|*\", \"a\")\n");
+
+    assert_eq!(
+        result.unwrap_err().message,
+        "Value error: invalid regular expression 'a|*': nothing to repeat at position 2."
+    );
+
+    // Nested counted repetitions multiply, so the compiled size is what's limited
+    let (_, result) = try_run("import re\nre.search(\"(((a{1,1000}){1,1000}){1,1000})\", \"a\")\n");
+
+    assert_eq!(
+        result.unwrap_err().message,
+        "Value error: invalid regular expression '(((a{1,1000}){1,1000}){1,1000})': pattern is too large."
+    );
+
+    let (_, result) = try_run("import re\nre.search(\"a**\", \"a\")\n");
+
+    assert_eq!(
+        result.unwrap_err().message,
+        "Value error: invalid regular expression 'a**': multiple repeat at position 2."
+    );
+
+    let (_, result) = try_run("import re\nre.search(\"a{2}+\", \"aa\")\n");
+
+    assert_eq!(
+        result.unwrap_err().message,
+        "Value error: invalid regular expression 'a{2}+': multiple repeat at position 4."
+    );
+
+    let (_, result) = try_run("import re\nre.search(\"[z-a]\", \"a\")\n");
+
+    assert_eq!(
+        result.unwrap_err().message,
+        "Value error: invalid regular expression '[z-a]': bad character range at position 4."
+    );
+
+    let (_, result) = try_run("import re\nre.replace(\"a\", \"a\", \"$2\")\n");
+
+    assert_eq!(
+        result.unwrap_err().message,
+        "Value error: replacement refers to unknown group '2'."
+    );
+}
+
+#[test]
+fn higher_order_functions() {
+    let mut interpreter = run(r#"
+func double(x):
+    return x * 2
+
+func is_even(x):
+    return x % 2 == 0
+
+func add(a, b):
+    return a + b
+
+func length(word):
+    return len(word)
+
+func numbers():
+    for i in range(1, 5):
+        yield i
+
+define words = ["pear", "fig", "apple", "kiwi"]
+
+define doubled = map(double, numbers())
+define evens = filter(is_even, [1, 2, 3, 4])
+define truthy = filter(none, [0, 1, "", "a", [], [0]])
+define total = reduce(add, [1, 2, 3])
+define joined = reduce(add, "abc", ">")
+define ordered = sorted([3, 1, 2])
+define by_length = sorted(words, key=length)
+define descending = sorted(words, length, true)
+define reversed = sorted(words, reverse=true)
+define pairs = zip([1, 2, 3], "ab")
+define numbered = enumerate(["a", "b"], start=1)
+define checks = [any([0, "", 1]), any([]), all([1, "a"]), all([1, 0])]
+define sums = [sum([1, 2, 3]), sum(numbers(), 10)]
+define smallest = [min([3, 1, 2]), min(4, 2, 8), min(words, key=length), min("b", "a")]
+define largest = [max([3, 1, 2]), max(4, 2, 8), max(words, length), max([[1, 2], [1, 3]])]
+"#);
+
+    assert_eq!(text(&mut interpreter, "doubled"), "[2, 4, 6, 8]");
+    assert_eq!(text(&mut interpreter, "evens"), "[2, 4]");
+    assert_eq!(text(&mut interpreter, "truthy"), "[1, a, [0]]");
+    assert_eq!(text(&mut interpreter, "total"), "6");
+    assert_eq!(text(&mut interpreter, "joined"), ">abc");
+    assert_eq!(text(&mut interpreter, "ordered"), "[1, 2, 3]");
+    assert_eq!(
+        text(&mut interpreter, "by_length"),
+        "[fig, pear, kiwi, apple]"
+    );
+    assert_eq!(
+        text(&mut interpreter, "descending"),
+        "[apple, pear, kiwi, fig]"
+    );
+    assert_eq!(
+        text(&mut interpreter, "reversed"),
+        "[pear, kiwi, fig, apple]"
+    );
+    assert_eq!(text(&mut interpreter, "pairs"), "[[1, a], [2, b]]");
+    assert_eq!(text(&mut interpreter, "numbered"), "[[1, a], [2, b]]");
+    assert_eq!(
+        text(&mut interpreter, "checks"),
+        "[true, false, true, false]"
+    );
+    assert_eq!(text(&mut interpreter, "sums"), "[6, 20]");
+    assert_eq!(text(&mut interpreter, "smallest"), "[1, 2, fig, a]");
+    assert_eq!(text(&mut interpreter, "largest"), "[3, 8, apple, [1, 3]]");
+}
+
+#[test]
+fn sorted_with_inconsistent_comparisons() {
+    let mut interpreter = run(r#"
+import random
+
+class Chaos:
+    func __init__(self, n):
+        self.n = n
+
+    func __lt__(self, other):
+        return random.random() < 0.5
+
+define shuffled = sorted(map(Chaos, range(400)))
+define count = len(shuffled)
+define numbers = sorted([3, 0 / 0, 1, -1])
+"#);
+
+    assert_eq!(global(&interpreter, "count"), TokenKind::Number(400.0));
+    assert_eq!(text(&mut interpreter, "numbers"), "[-1, 1, 3, NaN]");
+
+    let (_, result) = try_run(
+        r#"
+func key(x):
+    if x == 3:
+        return x + none
+    return x
+
+sorted([5, 4, 3, 2, 1], key)
+"#,
+    );
+
+    assert_eq!(
+        result.unwrap_err().message,
+        "Expression error: Could not apply operation Plus to expressions Number(3.0) and None."
+    );
+}
+
+#[test]
+fn higher_order_function_errors() {
+    let (_, result) = try_run("func fail(x):\n    return x + none\n\nmap(fail, [1])\n");
+
+    assert_eq!(
+        result.unwrap_err().message,
+        "Expression error: Could not apply operation Plus to expressions Number(1.0) and None."
+    );
+
+    let (_, result) = try_run("sorted([1, \"a\"])\n");
+
+    assert_eq!(
+        result.unwrap_err().message,
+        "Type error: sorted could not compare String(\"a\") with Number(1.0)."
+    );
+
+    let (_, result) = try_run("reduce(max, [])\n");
+
+    assert_eq!(
+        result.unwrap_err().message,
+        "Value error: reduce of an empty sequence with no initial value."
+    );
+
+    let (_, result) = try_run("sorted([1], order=true)\n");
+
+    assert_eq!(
+        result.unwrap_err().message,
+        "Call error: function sorted got an unexpected keyword argument 'order'."
+    );
+
+    let (_, result) = try_run("len(value=[1])\n");
+
+    assert_eq!(
+        result.unwrap_err().message,
+        "Call error: unexpected keyword argument 'value', <native func len> only accepts positional arguments."
+    );
+}
+
+#[test]
+fn sets() {
+    let mut interpreter = run(r#"
+import json
+
+define a = {3, 1, 2, 3}
+define b = set([2, 3, 4])
+define empty = set()
+define braces = {}
+
+define added = [a.add(5), a.add(1)]
+define removed = [a.remove(5), a.remove(9)]
+define found = [a.contains(1), a.contains(4), len(a)]
+
+define union = a + b
+define intersection = a * b
+define difference = a - b
+define symmetric = a ^ b
+define methods = [a.union("xy"), a.intersection([1]), a.difference(b), a.symmetric_difference(b)]
+
+define same = {1, 2} == {2, 1}
+define visited = ""
+
+for value in {"c", "a", "b"}:
+    visited += value
+
+define encoded = json.stringify({"tags": {"x", "y"}})
+"#);
+
+    assert_eq!(text(&mut interpreter, "a"), "{3, 1, 2}");
+    assert_eq!(text(&mut interpreter, "b"), "{2, 3, 4}");
+    assert_eq!(text(&mut interpreter, "empty"), "set()");
+    assert_eq!(text(&mut interpreter, "braces"), "{}");
+    assert_eq!(text(&mut interpreter, "added"), "[true, false]");
+    assert_eq!(text(&mut interpreter, "removed"), "[true, false]");
+    assert_eq!(text(&mut interpreter, "found"), "[true, false, 3]");
+    assert_eq!(text(&mut interpreter, "union"), "{3, 1, 2, 4}");
+    assert_eq!(text(&mut interpreter, "intersection"), "{3, 2}");
+    assert_eq!(text(&mut interpreter, "difference"), "{1}");
+    assert_eq!(text(&mut interpreter, "symmetric"), "{1, 4}");
+    assert_eq!(
+        text(&mut interpreter, "methods"),
+        "[{3, 1, 2, x, y}, {1}, {1}, {1, 4}]"
+    );
+    assert_eq!(global(&interpreter, "same"), TokenKind::Bool(true));
+    assert_eq!(text(&mut interpreter, "visited"), "cab");
+    assert_eq!(
+        text(&mut interpreter, "encoded"),
+        "{\"tags\":[\"x\",\"y\"]}"
+    );
+}
+
+#[test]
+fn set_errors() {
+    let (_, result) = try_run("{[1]}\n");
+
+    assert_eq!(
+        result.unwrap_err().message,
+        "Type error: value of type List(RefCell { value: [Token { kind: Number(1.0), lexeme: \"1\", line: 1 }] }) can't be a member of a set."
+    );
+
+    let (_, result) = try_run("{1}.append(2)\n");
+
+    assert_eq!(
+        result.unwrap_err().message,
+        "Attribute error: set has no method 'append'."
+    );
+}
+
+#[test]
+fn bytes_values() {
+    let mut interpreter = run(r#"
+define header = b"GIF89a\x01\x00"
+define first = header[0]
+define last = header[-2]
+define size = len(header)
+define magic = header.slice(0, 3)
+define tail = header.slice(-2)
+define hex = header.slice(0, 4).hex()
+define parsed = from_hex("de ad BE EF")
+define text = bytes("héllo").decode()
+define encoded = bytes("é")
+define listed = bytes([104, 105]) + b"!"
+define total = 0
+
+for byte in b"AB":
+    total += byte
+
+define same = b"ab" == bytes("ab")
+define printed = b"a\"b\\\n\xff"
+"#);
+
+    assert_eq!(global(&interpreter, "first"), TokenKind::Number(71.0));
+    assert_eq!(global(&interpreter, "last"), TokenKind::Number(1.0));
+    assert_eq!(global(&interpreter, "size"), TokenKind::Number(8.0));
+    assert_eq!(text(&mut interpreter, "magic"), "b\"GIF\"");
+    assert_eq!(text(&mut interpreter, "tail"), "b\"\\x01\\x00\"");
+    assert_eq!(text(&mut interpreter, "hex"), "47494638");
+    assert_eq!(
+        text(&mut interpreter, "parsed"),
+        "b\"\\xde\\xad\\xbe\\xef\""
+    );
+    assert_eq!(text(&mut interpreter, "text"), "héllo");
+    assert_eq!(text(&mut interpreter, "encoded"), "b\"\\xc3\\xa9\"");
+    assert_eq!(text(&mut interpreter, "listed"), "b\"hi!\"");
+    assert_eq!(global(&interpreter, "total"), TokenKind::Number(131.0));
+    assert_eq!(global(&interpreter, "same"), TokenKind::Bool(true));
+    assert_eq!(text(&mut interpreter, "printed"), "b\"a\\\"b\\\\\\n\\xff\"");
+}
+
+#[test]
+fn bytes_errors() {
+    let (_, result) = try_run("b\"\\xff\\xfe\".decode()\n");
+
+    assert_eq!(
+        result.unwrap_err().message,
+        "Value error: bytes are not valid UTF-8, starting at byte 0."
+    );
+
+    let (_, result) = try_run("from_hex(\"abc\")\n");
+
+    assert_eq!(
+        result.unwrap_err().message,
+        "Value error: invalid hex string 'abc'."
+    );
+
+    let (_, result) = try_run("bytes([1, 256])\n");
+
+    assert_eq!(
+        result.unwrap_err().message,
+        "Value error: bytes expected whole Numbers from 0 to 255 but got Number(256.0)."
+    );
+
+    let (_, result) = try_run("define data = b\"a\"\ndata[0] = 1\n");
+
+    assert!(result
+        .unwrap_err()
+        .message
+        .starts_with("Index error: could not assign to an index of value of type Bytes"));
+}
+
+#[test]
+fn fs_bytes() {
+    let directory = write_files("fs-bytes", &[]);
+    fs::create_dir_all(&directory).unwrap();
+
+    let source = format!(
+        r#"
+import fs
+
+define path = "{}/data.bin"
+fs.write_bytes(path, b"\x00\xff\x10")
+define data = fs.read_bytes(path)
+"#,
+        directory.display()
+    );
+
+    let mut interpreter = run(&source);
+
+    assert_eq!(text(&mut interpreter, "data"), "b\"\\x00\\xff\\x10\"");
+    assert_eq!(
+        fs::read(directory.join("data.bin")).unwrap(),
+        vec![0, 255, 16]
+    );
+
+    let (_, result) = try_run(&format!(
+        "import fs\nfs.read_bytes(\"{}/missing.bin\")\n",
+        directory.display()
+    ));
+    assert_eq!(
+        result.unwrap_err().message,
+        format!(
+            "IO error: could not open file '{}/missing.bin'.",
+            directory.display()
+        )
+    );
+
+    fs::remove_dir_all(&directory).unwrap();
+}
+
+#[test]
+fn embed_eval() {
+    let mut interpreter = Interpreter::new();
+
+    let value = interpreter.eval("define x = 20\nx * 2 + 2").unwrap();
+    assert_eq!(value.kind, TokenKind::Number(42.0));
+    assert_eq!(global(&interpreter, "x"), TokenKind::Number(20.0));
+
+    // State carries over between calls, and statements evaluate to none
+    let value = interpreter.eval("x = x + 1").unwrap();
+    assert_eq!(value.kind, TokenKind::None);
+    assert_eq!(global(&interpreter, "x"), TokenKind::Number(21.0));
+
+    let error = interpreter.eval("undefined_name").unwrap_err();
+    assert!(matches!(error, Error::Runtime(..)));
+}
+
+#[test]
+fn embed_syntax_errors() {
+    let mut interpreter = Interpreter::new();
+
+    match interpreter.eval("define x = (1 +\ndefine y = 2 $") {
+        Err(Error::Syntax(errors)) => {
+            assert!(!errors.is_empty());
+            assert!(errors[0].ends_with('.'));
+        }
+        other => panic!("expected a syntax error, got {:?}", other),
+    }
+
+    // Nothing runs when the source contains errors
+    assert!(interpreter.eval("define y = 1\n)").is_err());
+    assert_eq!(interpreter.get_global("y"), None);
+}
+
+#[test]
+fn embed_globals() {
+    let mut interpreter = Interpreter::new();
+
+    interpreter
+        .set_global("limit", Token::from(TokenKind::Number(3.0)))
+        .unwrap();
+
+    let value = interpreter.eval("limit * 2").unwrap();
+    assert_eq!(value.kind, TokenKind::Number(6.0));
+
+    interpreter.eval("const fixed = 1").unwrap();
+    let error = interpreter
+        .set_global("fixed", Token::from(TokenKind::Number(2.0)))
+        .unwrap_err();
+
+    assert_eq!(
+        error.to_string(),
+        "Name error: cannot assign to constant 'fixed'."
+    );
+}
+
+#[test]
+fn embed_run_file() {
+    let directory = write_files(
+        "embed-run-file",
+        &[
+            (
+                "main.prot",
+                "import helper\ndefine result = helper.double(21)\n",
+            ),
+            ("helper.prot", "func double(n):\n    return n * 2\n"),
+        ],
+    );
+
+    let mut interpreter = Interpreter::new();
+    interpreter.run_file(&directory.join("main.prot")).unwrap();
+    assert_eq!(global(&interpreter, "result"), TokenKind::Number(42.0));
+
+    let error = interpreter
+        .run_file(&directory.join("missing.prot"))
+        .unwrap_err();
+    assert!(error
+        .to_string()
+        .starts_with("IO error: could not open file"));
+
+    fs::remove_dir_all(&directory).unwrap();
+}
+
+#[test]
+fn host_functions() {
+    let sent = Rc::new(RefCell::new(Vec::new()));
+    let log = Rc::clone(&sent);
+
+    let mut interpreter = Interpreter::new();
+    interpreter.register_fn("send_metric", move |args| {
+        let name: String = args.get(0)?;
+        let value: f64 = args.get(1)?;
+        let tags: Option<i64> = args.optional(2)?;
+
+        log.borrow_mut().push((name, value, tags));
+        Ok(())
+    });
+    interpreter.register_fn("total", |args| Ok(args.rest::<f64>(0)?.iter().sum::<f64>()));
+
+    interpreter
+        .eval("send_metric(\"load\", 0.5)\nsend_metric(\"users\", 3, 2)")
+        .unwrap();
+    assert_eq!(
+        *sent.borrow(),
+        vec![
+            (String::from("load"), 0.5, None),
+            (String::from("users"), 3.0, Some(2))
+        ]
+    );
+
+    let value = interpreter.eval("total(1, 2, 3)").unwrap();
+    assert_eq!(value.kind, TokenKind::Number(6.0));
+
+    let error = interpreter
+        .eval("send_metric(\"load\", \"high\")")
+        .unwrap_err();
+    assert_eq!(
+        error.to_string(),
+        "Type error: send_metric expected a Number for argument 2 but got String(\"high\")."
+    );
+
+    let error = interpreter.eval("send_metric(\"load\")").unwrap_err();
+    assert_eq!(
+        error.to_string(),
+        "Call error: function send_metric expected at least 2 arguments but got 1."
+    );
+
+    // Errors raised by the host can be caught like any other
+    interpreter
+        .eval(
+            "define caught = false\ntry:\n    send_metric(1, 2, 2.5)\ncatch:\n    caught = true\n",
+        )
+        .unwrap();
+    assert_eq!(global(&interpreter, "caught"), TokenKind::Bool(true));
+}
+
+#[test]
+fn host_modules() {
+    let mut interpreter = Interpreter::new();
+    interpreter.register_module(
+        NativeModule::new("metrics")
+            .constant("version", 2i64)
+            .constant("prefix", "app.")
+            .function("key", |args| {
+                let name: String = args.get(0)?;
+                Ok(format!("app.{}", name))
+            }),
+    );
+
+    let value = interpreter
+        .eval("import metrics\ndefine version = metrics.version\nmetrics.key(\"load\")")
+        .unwrap();
+    assert_eq!(value.kind, TokenKind::String(String::from("app.load")));
+    assert_eq!(global(&interpreter, "version"), TokenKind::Number(2.0));
+
+    let value = interpreter
+        .eval("from metrics import prefix\nprefix")
+        .unwrap();
+    assert_eq!(value.kind, TokenKind::String(String::from("app.")));
+}
+
+#[test]
+fn host_calls_script_functions() {
+    let directory = write_files(
+        "host-calls",
+        &[(
+            "plugin.prot",
+            r#"
+define seen = 0
+
+func on_event(name, weight):
+    global seen
+    seen += weight
+    return name + "!"
+
+func describe(counts):
+    define total = 0
+    for key in counts.keys():
+        total += counts[key]
+    return {"total": total, "first": counts.keys()[0]}
+"#,
+        )],
+    );
+
+    let mut interpreter = Interpreter::new();
+    interpreter
+        .run_file(&directory.join("plugin.prot"))
+        .unwrap();
+
+    let on_event = interpreter.get_function("on_event").unwrap();
+
+    // The handle stays valid after the script's variable is replaced
+    interpreter.eval("on_event = none").unwrap();
+
+    for weight in 1..=3i64 {
+        let reply: String = on_event.call(&mut interpreter, ("tick", weight)).unwrap();
+        assert_eq!(reply, "tick!");
+    }
+
+    assert_eq!(global(&interpreter, "seen"), TokenKind::Number(6.0));
+
+    let mut counts = HashMap::new();
+    counts.insert(String::from("b"), 2i64);
+    counts.insert(String::from("a"), 5i64);
+
+    let describe = interpreter.get_function("describe").unwrap();
+    let summary: HashMap<String, Token> = describe.call(&mut interpreter, (counts,)).unwrap();
+    assert_eq!(summary["total"].kind, TokenKind::Number(7.0));
+    assert_eq!(summary["first"].kind, TokenKind::String(String::from("a")));
+
+    let error = on_event
+        .call::<_, i64>(&mut interpreter, ("tick", 1i64))
+        .unwrap_err();
+    assert_eq!(
+        error.message,
+        "Type error: <func on_event> returned String(\"tick!\") but the host expected a whole Number."
+    );
+
+    assert!(interpreter.get_function("seen").is_none());
+    assert!(interpreter.get_function("missing").is_none());
+
+    fs::remove_dir_all(&directory).unwrap();
+}
+
+#[test]
+fn host_conversions() {
+    let values = vec![Some(1.5), None].into_value();
+    assert_eq!(
+        Vec::<Option<f64>>::from_value(&values),
+        Some(vec![Some(1.5), None])
+    );
+    assert_eq!(Vec::<f64>::from_value(&values), None);
+    assert_eq!(Vec::<f64>::expected(), "a List of Number");
+
+    let mut interpreter = Interpreter::new();
+    let value = interpreter.eval("{\"x\": [true, false]}").unwrap();
+    let map: HashMap<String, Vec<bool>> = HashMap::from_value(&value).unwrap();
+    assert_eq!(map["x"], vec![true, false]);
+
+    assert_eq!(i64::from_value(&2.5.into_value()), None);
+    assert_eq!(Option::<String>::from_value(&().into_value()), Some(None));
+}
+
+struct Counter {
+    label: String,
+    count: Cell<i64>,
+}
+
+impl HostObject for Counter {
+    fn type_name(&self) -> &str {
+        "Counter"
+    }
+
+    fn get_property(&self, name: &str) -> Option<Token> {
+        match name {
+            "label" => Some(self.label.as_str().into_value()),
+            "count" => Some(self.count.get().into_value()),
+            _ => None,
+        }
+    }
+
+    fn set_property(&self, name: &str, value: Token) -> Result<(), RuntimeError> {
+        match (name, i64::from_value(&value)) {
+            ("count", Some(count)) => {
+                self.count.set(count);
+                Ok(())
+            }
+            _ => Err(RuntimeError::new(format!(
+                "Attribute error: could not set attribute '{}' on Counter object.",
+                name
+            ))),
+        }
+    }
+
+    fn methods(&self) -> &[&str] {
+        &["add"]
+    }
+
+    fn call_method(&self, _: &str, args: &Args) -> Result<Token, RuntimeError> {
+        let amount: i64 = args.optional(0)?.unwrap_or(1);
+        self.count.set(self.count.get() + amount);
+        Ok(self.count.get().into_value())
+    }
+
+    fn display(&self) -> String {
+        format!("<Counter {}>", self.label)
+    }
+}
+
+#[test]
+fn host_objects() {
+    let counter = Rc::new(Counter {
+        label: String::from("hits"),
+        count: Cell::new(0),
+    });
+
+    let mut interpreter = Interpreter::new();
+    interpreter
+        .set_global("counter", Rc::clone(&counter).into_value())
+        .unwrap();
+    interpreter
+        .set_global(
+            "other",
+            host::object(Counter {
+                label: String::from("misses"),
+                count: Cell::new(0),
+            }),
+        )
+        .unwrap();
+    interpreter.register_fn("count_of", |args| {
+        let counter: Rc<Counter> = args.get(0)?;
+        Ok(counter.count.get())
+    });
+
+    interpreter
+        .eval(
+            r#"
+counter.add()
+counter.add(4)
+define label = counter.label
+counter.count = counter.count * 2
+define same = counter == counter
+define different = counter == other
+define total = count_of(counter)
+
+func bump():
+    return counter.add()
+"#,
+        )
+        .unwrap();
+
+    assert_eq!(counter.count.get(), 10);
+    assert_eq!(global(&interpreter, "total"), TokenKind::Number(10.0));
+    assert_eq!(
+        global(&interpreter, "label"),
+        TokenKind::String(String::from("hits"))
+    );
+    assert_eq!(text(&mut interpreter, "counter"), "<Counter hits>");
+    assert_eq!(global(&interpreter, "same"), TokenKind::Bool(true));
+    assert_eq!(global(&interpreter, "different"), TokenKind::Bool(false));
+
+    let error = interpreter.eval("counter.missing").unwrap_err();
+    assert_eq!(
+        error.to_string(),
+        "Attribute error: Counter object has no attribute 'missing'."
+    );
+
+    let error = interpreter.eval("count_of(1)").unwrap_err();
+    assert_eq!(
+        error.to_string(),
+        "Type error: count_of expected a Counter object for argument 1 but got Number(1.0)."
+    );
+
+    // Scripts let go of objects along with their variables, even those functions refer to
+    assert_eq!(
+        interpreter.eval("bump()").unwrap().kind,
+        TokenKind::Number(11.0)
+    );
+
+    // Objects captured by local functions are let go of when the call ends, or along with the
+    // interpreter when the function outlives the call
+    interpreter
+        .eval(
+            r#"
+func use_locally():
+    define held = counter
+
+    func read():
+        return held.count
+
+    return read()
+
+for i in range(1000):
+    use_locally()
+
+func keep():
+    define held = counter
+
+    func read():
+        return held.count
+
+    return read
+
+define kept = keep()
+kept = none
+"#,
+        )
+        .unwrap();
+
+    assert_eq!(Rc::strong_count(&counter), 3);
+    drop(interpreter);
+    assert_eq!(Rc::strong_count(&counter), 1);
+}
+
+#[test]
+fn recursion_limit() {
+    // Plain recursion up to the default limit fits the stack of a program's main thread, which
+    // test threads are smaller than
+    let runner = std::thread::Builder::new()
+        .stack_size(8 * 1024 * 1024)
+        .spawn(|| {
+            let mut interpreter = run(r#"
+func forever(n):
+    return forever(n + 1) + 1
+
+func gen(n):
+    for value in gen(n + 1):
+        yield value
+    yield n
+
+func depth(n):
+    if n == 0:
+        return 0
+    return depth(n - 1) + 1
+
+define message = none
+
+try:
+    forever(0)
+catch error:
+    message = error
+
+define generator_message = none
+
+try:
+    next(gen(0))
+catch error:
+    generator_message = error
+
+define deep = depth(150)
+"#);
+
+            (
+                text(&mut interpreter, "message"),
+                text(&mut interpreter, "generator_message"),
+                text(&mut interpreter, "deep"),
+            )
+        })
+        .unwrap();
+
+    let (message, generator_message, deep) = runner.join().unwrap();
+
+    assert_eq!(
+        message,
+        "Recursion error: maximum call depth of 200 exceeded."
+    );
+    assert_eq!(generator_message, message);
+
+    // Calls that returned, or failed, no longer count
+    assert_eq!(deep, "150");
+}
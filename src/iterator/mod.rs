@@ -0,0 +1,37 @@
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use crate::generator::Generator;
+use crate::interpreter::{Interpreter, RuntimeError};
+use crate::token::{Token, TokenKind};
+
+/// Iteration state for the values that `for` loops and builtins can step through
+pub enum ValueIterator {
+    Chars(Vec<char>, usize),
+    List(Rc<RefCell<Vec<Token>>>, usize),
+    Generator(Rc<RefCell<Generator>>),
+}
+
+impl ValueIterator {
+    pub fn next(&mut self, interpreter: &mut Interpreter) -> Result<Option<Token>, RuntimeError> {
+        match self {
+            ValueIterator::Chars(chars, index) => {
+                let value = chars
+                    .get(*index)
+                    .map(|c| Token::from(TokenKind::String(c.to_string())));
+
+                *index += 1;
+                Ok(value)
+            }
+
+            ValueIterator::List(list, index) => {
+                let value = list.borrow().get(*index).cloned();
+
+                *index += 1;
+                Ok(value)
+            }
+
+            ValueIterator::Generator(generator) => Generator::resume(generator, interpreter),
+        }
+    }
+}
@@ -1,3 +1,5 @@
+use std::rc::Rc;
+
 use crate::token::{Token, TokenKind};
 
 pub struct Lexer {
@@ -12,9 +14,13 @@ pub struct Lexer {
     at_new_line: bool,
     pub contains_errors: bool,
 
+    // Messages describing each error found
+    pub errors: Vec<String>,
+
     indent_stack: Vec<usize>,
     indent_counter: usize,
     bracket_counter: usize,
+    brace_counter: usize,
     paren_counter: usize,
 }
 
@@ -31,10 +37,12 @@ impl Lexer {
 
             at_new_line: true,
             contains_errors: false,
+            errors: Vec::new(),
 
             indent_stack: vec![0],
             indent_counter: 0,
             bracket_counter: 0,
+            brace_counter: 0,
             paren_counter: 0,
         }
     }
@@ -48,10 +56,12 @@ impl Lexer {
 
         self.at_new_line = true;
         self.contains_errors = false;
+        self.errors.clear();
 
         self.indent_stack = vec![0];
         self.indent_counter = 0;
         self.bracket_counter = 0;
+        self.brace_counter = 0;
         self.paren_counter = 0;
     }
 
@@ -77,6 +87,26 @@ impl Lexer {
         c
     }
 
+    fn error(&mut self, message: String) {
+        self.errors.push(message);
+        self.contains_errors = true;
+    }
+
+    fn peek_next(&self) -> char {
+        if self.cursor + 1 >= self.source.len() {
+            return '\0';
+        }
+
+        self.source[self.cursor + 1]
+    }
+
+    fn add_token(&mut self, token: Token) {
+        self.tokens.push(Token {
+            line: self.token_location.0 + 1,
+            ..token
+        });
+    }
+
     fn consume_match(&mut self, case: char) -> bool {
         if case == self.peek() {
             self.advance();
@@ -135,7 +165,10 @@ impl Lexer {
             return;
         } else if spaces > *self.indent_stack.last().unwrap() {
             self.indent_counter += 1;
-            self.tokens.push(Token::from(TokenKind::Indent));
+            self.add_token(Token::from(TokenKind::Indent));
+
+            self.indent_stack.push(spaces);
+            return;
         }
 
         // Go down the indentation stack, keeping track of the lowest indentation level seen that is still greater than
@@ -149,7 +182,7 @@ impl Lexer {
                 break;
             } else if spaces < i && i < lowest {
                 if self.indent_counter == 0 {
-                    eprintln!("Indentation error (line {}): could not find line with matching indentation level within file.\n", self.token_location.0 + 1);
+                    self.error(format!("Indentation error (line {}): could not find line with matching indentation level within file.", self.token_location.0 + 1));
                     return;
                 }
 
@@ -165,7 +198,7 @@ impl Lexer {
 
     fn resolve_indentation_level(&mut self) {
         for _ in 0..self.indent_counter {
-            self.tokens.push(Token::from(TokenKind::Dedent));
+            self.add_token(Token::from(TokenKind::Dedent));
         }
     }
 
@@ -179,10 +212,16 @@ impl Lexer {
         let kind = match lexeme.as_str() {
             "func" => TokenKind::Func,
             "define" => TokenKind::Define,
+            "const" => TokenKind::Const,
             "return" => TokenKind::Return,
+            "yield" => TokenKind::Yield,
+            "global" => TokenKind::Global,
+            "nonlocal" => TokenKind::Nonlocal,
             "continue" => TokenKind::Continue,
             "break" => TokenKind::Break,
             "print" => TokenKind::Print,
+            "class" => TokenKind::Class,
+            "enum" => TokenKind::Enum,
 
             "and" => TokenKind::And,
             "or" => TokenKind::Or,
@@ -191,8 +230,16 @@ impl Lexer {
             "if" => TokenKind::If,
             "else" => TokenKind::Else,
             "for" => TokenKind::For,
+            "in" => TokenKind::In,
+            "is" => TokenKind::Is,
             "while" => TokenKind::While,
             "loop" => TokenKind::Loop,
+            "with" => TokenKind::With,
+            "as" => TokenKind::As,
+            "import" => TokenKind::Import,
+            "from" => TokenKind::From,
+            "try" => TokenKind::Try,
+            "catch" => TokenKind::Catch,
 
             "true" => TokenKind::Bool(true),
             "false" => TokenKind::Bool(false),
@@ -201,7 +248,7 @@ impl Lexer {
             _ => TokenKind::Identifier(lexeme.clone()),
         };
 
-        self.tokens.push(Token::new(kind, lexeme));
+        self.add_token(Token::new(kind, lexeme));
     }
 
     fn get_number(&mut self) {
@@ -222,8 +269,7 @@ impl Lexer {
 
         let number: f64 = lexeme.parse().unwrap();
 
-        self.tokens
-            .push(Token::new(TokenKind::Number(number), lexeme));
+        self.add_token(Token::new(TokenKind::Number(number), lexeme));
     }
 
     fn get_string(&mut self) {
@@ -245,9 +291,10 @@ impl Lexer {
                     'r' => '\r',
                     't' => '\t',
 
-                    _ => {
+                    // Other escapes are kept as written, for patterns like "\d"
+                    other => {
                         lexeme.push('\\');
-                        c
+                        other
                     }
                 };
 
@@ -258,18 +305,100 @@ impl Lexer {
         }
 
         if self.reached_end() {
-            eprintln!(
-                "Lexing error (line {}): found EoF while looking for end of string.\n",
+            self.error(format!(
+                "Lexing error (line {}): found EoF while looking for end of string.",
+                self.token_location.0 + 1
+            ));
+            return;
+        }
+
+        // Skip over trailing quotation mark
+        self.advance();
+
+        self.add_token(Token::new(TokenKind::String(lexeme.clone()), lexeme));
+    }
+
+    /// Reads a bytes literal like `b"GIF\x00"`. Besides the escapes strings have, `\xHH` stands for
+    /// any byte, and other characters must be ASCII.
+    fn get_bytes(&mut self) {
+        // Skip over the prefix and preceding quotation mark
+        self.advance();
+        self.advance();
+
+        let mut lexeme = String::from("b\"");
+        let mut bytes = Vec::new();
+
+        while !self.reached_end() && self.peek() != '"' && self.peek() != '\'' {
+            let c = self.advance();
+            lexeme.push(c);
+
+            let byte = if c == '\\' {
+                let sc = self.advance();
+                lexeme.push(sc);
+
+                match sc {
+                    '"' => b'"',
+                    '\'' => b'\'',
+                    '\\' => b'\\',
+                    'n' => b'\n',
+                    'r' => b'\r',
+                    't' => b'\t',
+                    '0' => 0,
+
+                    'x' => {
+                        let digits: String = (0..2)
+                            .map(|_| self.advance())
+                            .inspect(|digit| lexeme.push(*digit))
+                            .collect();
+
+                        match u8::from_str_radix(&digits, 16) {
+                            Ok(byte) => byte,
+                            Err(..) => {
+                                self.error(format!(
+                                    "Lexing error (line {}): expected two hex digits after '\\x' in bytes literal.",
+                                    self.token_location.0 + 1
+                                ));
+                                continue;
+                            }
+                        }
+                    }
+
+                    other => {
+                        self.error(format!(
+                            "Lexing error (line {}): unknown escape '\\{}' in bytes literal.",
+                            self.token_location.0 + 1,
+                            other
+                        ));
+                        continue;
+                    }
+                }
+            } else if c.is_ascii() {
+                c as u8
+            } else {
+                self.error(format!(
+                    "Lexing error (line {}): bytes literals can only contain ASCII characters, found '{}'.",
+                    self.token_location.0 + 1,
+                    c
+                ));
+                continue;
+            };
+
+            bytes.push(byte);
+        }
+
+        if self.reached_end() {
+            self.error(format!(
+                "Lexing error (line {}): found EoF while looking for end of bytes literal.",
                 self.token_location.0 + 1
-            );
+            ));
             return;
         }
 
         // Skip over trailing quotation mark
         self.advance();
+        lexeme.push('"');
 
-        self.tokens
-            .push(Token::new(TokenKind::String(lexeme.clone()), lexeme));
+        self.add_token(Token::new(TokenKind::Bytes(Rc::new(bytes)), lexeme));
     }
 
     fn get_symbol(&mut self) {
@@ -278,13 +407,12 @@ impl Lexer {
         let kind = match c {
             // Single character tokens
             '(' => {
-                self.bracket_counter += 1;
+                self.paren_counter += 1;
                 Some(TokenKind::LeftParen)
             }
             ')' => {
                 if self.paren_counter == 0 {
-                    eprintln!("Bracket error (line {}): found closing parenthesis without matching opening parenthesis.\n", self.token_location.0 + 1);
-                    self.contains_errors = true;
+                    self.error(format!("Bracket error (line {}): found closing parenthesis without matching opening parenthesis.", self.token_location.0 + 1));
                 } else {
                     self.paren_counter -= 1;
                 }
@@ -297,14 +425,26 @@ impl Lexer {
             }
             ']' => {
                 if self.bracket_counter == 0 {
-                    eprintln!("Bracket error (line {}): found closing bracket without matching opening bracket.\n", self.token_location.0 + 1);
-                    self.contains_errors = true;
+                    self.error(format!("Bracket error (line {}): found closing bracket without matching opening bracket.", self.token_location.0 + 1));
                 } else {
                     self.bracket_counter -= 1;
                 }
 
                 Some(TokenKind::RightBracket)
             }
+            '{' => {
+                self.brace_counter += 1;
+                Some(TokenKind::LeftBrace)
+            }
+            '}' => {
+                if self.brace_counter == 0 {
+                    self.error(format!("Bracket error (line {}): found closing brace without matching opening brace.", self.token_location.0 + 1));
+                } else {
+                    self.brace_counter -= 1;
+                }
+
+                Some(TokenKind::RightBrace)
+            }
 
             ',' => Some(TokenKind::Comma),
             '.' => Some(TokenKind::Period),
@@ -390,10 +530,13 @@ impl Lexer {
                 self.location.0 += 1;
                 self.location.1 = 0;
 
-                // Only add a newline token if the previous token was not another newline token
+                // Only add a newline token if the previous token was not another newline token, and
+                // ignore line breaks within brackets so that expressions can span multiple lines
                 let prev = self.tokens.last();
 
-                if let Some(prev) = prev {
+                if self.bracket_counter > 0 || self.brace_counter > 0 || self.paren_counter > 0 {
+                    None
+                } else if let Some(prev) = prev {
                     if prev.kind == TokenKind::Newline {
                         None
                     } else {
@@ -405,18 +548,17 @@ impl Lexer {
             }
 
             _ => {
-                eprintln!(
-                    "Lexing error (line {}): found unknown symbol '{}' in file.\n",
+                self.error(format!(
+                    "Lexing error (line {}): found unknown symbol '{}' in file.",
                     self.token_location.0 + 1,
                     c
-                );
-                self.contains_errors = true;
+                ));
                 None
             }
         };
 
         if let Some(kind) = kind {
-            self.tokens.push(Token::from(kind));
+            self.add_token(Token::from(kind));
         }
     }
 
@@ -427,6 +569,7 @@ impl Lexer {
             if self.at_new_line
                 && !self.at_empty_line()
                 && self.bracket_counter == 0
+                && self.brace_counter == 0
                 && self.paren_counter == 0
             {
                 self.get_indentation_level();
@@ -437,7 +580,9 @@ impl Lexer {
 
             let c = self.peek();
 
-            if c.is_alphabetic() || c == '_' {
+            if c == 'b' && (self.peek_next() == '"' || self.peek_next() == '\'') {
+                self.get_bytes();
+            } else if c.is_alphabetic() || c == '_' {
                 self.get_identifier();
             } else if c.is_ascii_digit() {
                 self.get_number();
@@ -454,7 +599,7 @@ impl Lexer {
 
         if let Some(prev) = prev {
             if prev.kind != TokenKind::Newline {
-                self.tokens.push(Token::from(TokenKind::Newline));
+                self.add_token(Token::from(TokenKind::Newline));
             }
         }
 
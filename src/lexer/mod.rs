@@ -1,5 +1,6 @@
 pub use lexer::Lexer;
 
+#[allow(clippy::module_inception)]
 mod lexer;
 
 #[cfg(test)]
@@ -1,3 +1,5 @@
+use std::rc::Rc;
+
 use crate::lexer::Lexer;
 use crate::token::{Token, TokenKind};
 
@@ -59,6 +61,7 @@ fn lex_identifier() {
 }
 
 #[test]
+#[allow(clippy::approx_constant)]
 fn lex_number() {
     assert_eq!(
         lex("3.14"),
@@ -87,6 +90,36 @@ fn lex_string() {
     );
 }
 
+#[test]
+fn lex_string_escapes() {
+    // Unknown escapes are kept as written
+    let text = "a\tb\\c\\d";
+
+    assert_eq!(
+        lex("'a\\tb\\\\c\\d'"),
+        vec![
+            Token::new(TokenKind::String(String::from(text)), String::from(text)),
+            Token::from(TokenKind::Newline)
+        ]
+    );
+}
+
+#[test]
+fn lex_bytes() {
+    let tokens = lex("b'GIF\\x00\\n' bytes");
+
+    assert_eq!(
+        tokens[0].kind,
+        TokenKind::Bytes(Rc::new(vec![b'G', b'I', b'F', 0, b'\n']))
+    );
+    assert_eq!(tokens[1].kind, TokenKind::Identifier(String::from("bytes")));
+
+    let mut lexer = Lexer::new(String::from("b'caf\u{e9}' b'\\xg0'"));
+    lexer.collect_tokens();
+
+    assert!(lexer.contains_errors);
+}
+
 #[test]
 fn lex_indentation() {
     assert_eq!(
@@ -107,3 +140,44 @@ if
         ]
     );
 }
+
+#[test]
+fn lex_indentation_after_nested_block() {
+    assert_eq!(
+        lex(r#"
+if
+    if
+        a
+b
+    c
+        "#),
+        vec![
+            Token::new(TokenKind::If, String::from("if")),
+            Token::from(TokenKind::Newline),
+            Token::from(TokenKind::Indent),
+            Token::new(TokenKind::If, String::from("if")),
+            Token::from(TokenKind::Newline),
+            Token::from(TokenKind::Indent),
+            Token::new(TokenKind::Identifier(String::from("a")), String::from("a")),
+            Token::from(TokenKind::Newline),
+            Token::from(TokenKind::Dedent),
+            Token::from(TokenKind::Dedent),
+            Token::new(TokenKind::Identifier(String::from("b")), String::from("b")),
+            Token::from(TokenKind::Newline),
+            Token::from(TokenKind::Indent),
+            Token::new(TokenKind::Identifier(String::from("c")), String::from("c")),
+            Token::from(TokenKind::Newline),
+            Token::from(TokenKind::Dedent),
+        ]
+    );
+}
+
+#[test]
+fn lex_line_numbers() {
+    let lines: Vec<usize> = lex("a\n\nb (\n  c)\n")
+        .iter()
+        .map(|token| token.line)
+        .collect();
+
+    assert_eq!(lines, vec![1, 1, 3, 3, 4, 4, 4]);
+}
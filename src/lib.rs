@@ -0,0 +1,38 @@
+//! The Prot interpreter, which can be embedded in other programs through [`Interpreter`].
+//!
+//! ```
+//! use prot_lang::{Interpreter, TokenKind};
+//!
+//! let mut interpreter = Interpreter::new();
+//! interpreter.eval("define total = 1 + 2").unwrap();
+//!
+//! let total = interpreter.get_global("total").unwrap();
+//! assert_eq!(total.kind, TokenKind::Number(3.0));
+//! ```
+//!
+//! Scripts run on the host's own stack, so recursive ones need a thread with room for them. See
+//! [`interpreter::MAX_CALL_DEPTH`] for how much the default call depth limit takes.
+
+pub use host::{Args, Callback, FromValue, HostObject, IntoArgs, IntoValue, NativeModule};
+pub use interpreter::{Error, Interpreter, RuntimeError};
+pub use token::{Token as Value, TokenKind};
+
+pub mod builtins;
+pub mod class;
+pub mod date;
+pub mod enumeration;
+pub mod environment;
+pub mod expr;
+pub mod function;
+pub mod generator;
+pub mod host;
+pub mod interpreter;
+pub mod iterator;
+pub mod lexer;
+pub mod map;
+pub mod module;
+pub mod parser;
+pub mod regex;
+pub mod set;
+pub mod statement;
+pub mod token;
@@ -1,17 +1,15 @@
 use std::env;
-use std::fs::File;
-use std::io::Read;
+use std::path::Path;
+use std::process;
+use std::thread;
 
-use interpreter::interpret_program;
-use lexer::Lexer;
-use parser::Parser;
+use prot_lang::{Error, Interpreter};
 
-pub mod expr;
-pub mod interpreter;
-pub mod lexer;
-pub mod parser;
-pub mod statement;
-pub mod token;
+const STACK_SIZE: usize = 128 * 1024 * 1024;
+
+// Deep enough for recursive scripts, while leaving debug builds room on the stack above even when
+// every call nests several blocks deep
+const MAX_CALL_DEPTH: usize = 1000;
 
 fn main() {
     // Get file path
@@ -31,45 +29,50 @@ fn main() {
         return;
     }
 
-    // Open file
-    let mut script: File = {
-        let file: File;
-
-        match File::open(script_path) {
-            Ok(f) => {
-                file = f;
-            }
+    // Arguments after the script's path are passed on to it
+    let script_args = args[2..].to_vec();
+    let script_path = script_path.clone();
 
-            Err(..) => {
-                eprintln!("IO error: could not open file '{}'\n", script_path);
-                return;
-            }
-        }
+    // Scripts run on a thread with a bigger stack than the main one, so they can recurse deeper
+    let runner = thread::Builder::new()
+        .stack_size(STACK_SIZE)
+        .spawn(move || run(Path::new(&script_path), script_args))
+        .expect("could not start the interpreter thread");
 
-        file
-    };
+    process::exit(runner.join().unwrap_or(1));
+}
 
-    // Read file contents into string
-    let mut contents = String::new();
+/// Runs a script, returning the exit status the process should end with
+fn run(script_path: &Path, script_args: Vec<String>) -> i32 {
+    let mut interpreter = Interpreter::new();
+    interpreter.set_script_args(script_args);
+    interpreter.set_max_call_depth(MAX_CALL_DEPTH);
 
-    if let Err(..) = script.read_to_string(&mut contents) {
-        eprintln!("IO error: failed to read from file '{}'\n", script_path);
-        return;
+    // Extra directories to find modules in, separated like the platform's PATH variable
+    if let Some(paths) = env::var_os("PROT_PATH") {
+        for directory in env::split_paths(&paths) {
+            interpreter.add_search_path(directory);
+        }
     }
 
-    let mut lexer = Lexer::new(contents);
-    let tokens = lexer.collect_tokens();
+    match interpreter.run_file(script_path) {
+        Ok(..) => 0,
 
-    if lexer.contains_errors || tokens.is_empty() {
-        return;
-    }
+        Err(Error::Syntax(errors)) => {
+            for error in errors {
+                eprintln!("{}\n", error);
+            }
+
+            1
+        }
 
-    let mut parser = Parser::new(tokens);
-    let statements = parser.parse_tokens();
+        Err(error) => match error.exit_code() {
+            Some(code) => code,
 
-    if parser.contains_errors {
-        return;
+            None => {
+                eprintln!("{}\n", error);
+                1
+            }
+        },
     }
-
-    interpret_program(statements);
 }
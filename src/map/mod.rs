@@ -0,0 +1,118 @@
+use std::collections::HashMap;
+
+use crate::interpreter::RuntimeError;
+use crate::token::{Token, TokenKind};
+
+/// The form of a value used to look it up in a map. Only values compared by content can be keys.
+#[derive(Debug, PartialEq, Eq, Hash)]
+pub enum Key {
+    Number(u64),
+    String(String),
+    Bool(bool),
+    Bytes(Vec<u8>),
+    None,
+}
+
+impl Key {
+    pub fn from(value: &Token) -> Result<Key, RuntimeError> {
+        match &value.kind {
+            // Zero and negative zero are equal, so they must be the same key
+            TokenKind::Number(val) if *val == 0.0 => Ok(Key::Number(0)),
+            TokenKind::Number(val) => Ok(Key::Number(val.to_bits())),
+            TokenKind::String(val) => Ok(Key::String(val.clone())),
+            TokenKind::Bool(val) => Ok(Key::Bool(*val)),
+            TokenKind::Bytes(val) => Ok(Key::Bytes(val.to_vec())),
+            TokenKind::None => Ok(Key::None),
+
+            kind => Err(RuntimeError::new(format!(
+                "Type error: value of type {:?} can't be used as a map key.",
+                kind
+            ))),
+        }
+    }
+}
+
+/// A collection of key value pairs, which keeps its entries in the order they were first added
+#[derive(Debug, Default)]
+pub struct Map {
+    entries: Vec<(Token, Token)>,
+    positions: HashMap<Key, usize>,
+}
+
+impl Map {
+    pub fn new() -> Map {
+        Map::default()
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn entries(&self) -> &[(Token, Token)] {
+        &self.entries
+    }
+
+    pub fn keys(&self) -> Vec<Token> {
+        self.entries.iter().map(|(key, _)| key.clone()).collect()
+    }
+
+    pub fn values(&self) -> Vec<Token> {
+        self.entries
+            .iter()
+            .map(|(_, value)| value.clone())
+            .collect()
+    }
+
+    pub fn get(&self, key: &Token) -> Result<Option<Token>, RuntimeError> {
+        let position = self.positions.get(&Key::from(key)?);
+        Ok(position.map(|position| self.entries[*position].1.clone()))
+    }
+
+    pub fn contains(&self, key: &Token) -> Result<bool, RuntimeError> {
+        Ok(self.positions.contains_key(&Key::from(key)?))
+    }
+
+    pub fn insert(&mut self, key: Token, value: Token) -> Result<(), RuntimeError> {
+        let hashed = Key::from(&key)?;
+
+        match self.positions.get(&hashed) {
+            Some(position) => self.entries[*position].1 = value,
+
+            None => {
+                self.positions.insert(hashed, self.entries.len());
+                self.entries.push((key, value));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Removes an entry, returning its value if there was one
+    pub fn remove(&mut self, key: &Token) -> Result<Option<Token>, RuntimeError> {
+        let position = match self.positions.remove(&Key::from(key)?) {
+            Some(position) => position,
+            None => return Ok(None),
+        };
+
+        let (_, value) = self.entries.remove(position);
+
+        // Entries after the removed one have moved back a place
+        for other in self.positions.values_mut() {
+            if *other > position {
+                *other -= 1;
+            }
+        }
+
+        Ok(Some(value))
+    }
+}
+
+impl PartialEq for Map {
+    fn eq(&self, other: &Map) -> bool {
+        self.entries == other.entries
+    }
+}
@@ -0,0 +1,83 @@
+use std::cell::RefCell;
+use std::fmt;
+use std::fs::File;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+
+use crate::environment::Environment;
+use crate::interpreter::RuntimeError;
+use crate::parser;
+use crate::statement::Statement;
+use crate::token::Token;
+
+/// The global environment left behind by running a script, whose variables can be imported
+pub struct Module {
+    pub name: String,
+    globals: Rc<RefCell<Environment>>,
+}
+
+impl Module {
+    pub fn new(name: String, globals: Rc<RefCell<Environment>>) -> Module {
+        Module { name, globals }
+    }
+
+    pub fn get(&self, name: &str) -> Option<Token> {
+        self.globals.borrow().get(name)
+    }
+
+    pub fn values(&self) -> Vec<Token> {
+        self.globals.borrow().values()
+    }
+
+    /// Removes the module's variables, so the functions in it stop keeping them alive
+    pub fn clear(&self) {
+        Environment::clear(&self.globals);
+    }
+}
+
+/// Finds the file for a module path such as `lib.utils`, which refers to `lib/utils.prot` within
+/// the first of `directories` that contains it
+pub fn find(path: &[Token], directories: &[PathBuf]) -> Option<PathBuf> {
+    let mut relative: PathBuf = path.iter().map(|part| part.lexeme.as_str()).collect();
+    relative.set_extension("prot");
+
+    directories
+        .iter()
+        .map(|directory| directory.join(&relative))
+        .find(|file| file.is_file())
+}
+
+/// Reads and parses a module's file. Lexing and parsing errors are included in the error's message.
+pub fn load(file: &Path) -> Result<Vec<Statement>, RuntimeError> {
+    let mut contents = String::new();
+
+    let read = File::open(file).and_then(|mut script| script.read_to_string(&mut contents));
+
+    if read.is_err() {
+        return Err(RuntimeError::new(format!(
+            "IO error: could not open file '{}'.",
+            file.display()
+        )));
+    }
+
+    parser::parse(contents).map_err(|errors| {
+        RuntimeError::new(format!(
+            "Import error: module file '{}' contains errors:\n{}",
+            file.display(),
+            errors.join("\n")
+        ))
+    })
+}
+
+impl fmt::Debug for Module {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "<module {}>", self.name)
+    }
+}
+
+impl PartialEq for Module {
+    fn eq(&self, other: &Module) -> bool {
+        std::ptr::eq(self, other)
+    }
+}
@@ -1,3 +1,30 @@
 pub use parser::Parser;
 
+use crate::lexer::Lexer;
+use crate::statement::Statement;
+
+#[allow(clippy::module_inception)]
 pub mod parser;
+
+/// Lexes and parses a script, giving the messages for any errors found along the way
+pub fn parse(source: String) -> Result<Vec<Statement>, Vec<String>> {
+    let mut lexer = Lexer::new(source);
+    let tokens = lexer.collect_tokens();
+
+    if lexer.contains_errors {
+        return Err(lexer.errors);
+    }
+
+    if tokens.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    let mut parser = Parser::new(tokens);
+    let statements = parser.parse_tokens();
+
+    if parser.contains_errors {
+        return Err(parser.errors);
+    }
+
+    Ok(statements)
+}
@@ -1,11 +1,26 @@
-use crate::{expr::Expr, statement::Statement};
+use std::collections::HashMap;
+use std::rc::Rc;
+
+use crate::function::Parameter;
 use crate::token::{Token, TokenKind};
+use crate::{expr::Expr, statement::Statement};
 
 pub struct Parser {
     tokens: Vec<Token>,
     cursor: usize,
 
     pub contains_errors: bool,
+    // Messages describing each error found
+    pub errors: Vec<String>,
+    panic_mode: bool,
+
+    function_depth: usize,
+    // Labels of the loops enclosing the current statement within its function
+    loops: Vec<Option<String>>,
+    found_yield: bool,
+
+    // The names declared in each enclosing block, mapped to whether they are constant
+    scopes: Vec<HashMap<String, bool>>,
 }
 
 impl Parser {
@@ -15,12 +30,27 @@ impl Parser {
             cursor: 0,
 
             contains_errors: false,
+            errors: Vec::new(),
+            panic_mode: false,
+
+            function_depth: 0,
+            loops: Vec::new(),
+            found_yield: false,
+
+            scopes: vec![HashMap::new()],
         }
     }
 
     pub fn reset(&mut self) {
         self.cursor = 0;
         self.contains_errors = false;
+        self.errors.clear();
+        self.panic_mode = false;
+
+        self.function_depth = 0;
+        self.loops.clear();
+        self.found_yield = false;
+        self.scopes = vec![HashMap::new()];
     }
 
     fn reached_end(&self) -> bool {
@@ -28,9 +58,20 @@ impl Parser {
     }
 
     fn peek(&self) -> Token {
+        if self.reached_end() {
+            return Token::from(TokenKind::Eof);
+        }
+
         self.tokens[self.cursor].clone()
     }
 
+    fn peek_next(&self) -> Token {
+        match self.tokens.get(self.cursor + 1) {
+            Some(token) => token.clone(),
+            None => Token::from(TokenKind::Eof),
+        }
+    }
+
     fn advance(&mut self) -> Token {
         let t = self.peek();
         self.cursor += 1;
@@ -45,19 +86,95 @@ impl Parser {
         }
     }
 
-    fn expect(&mut self, kind: TokenKind) {
-        let tokens = &self.tokens[self.cursor..];
+    fn expect(&mut self, kind: TokenKind) -> Token {
+        if self.is_match(kind.clone()) {
+            return self.advance();
+        }
 
-        for token in tokens {
-            if token.kind == kind {
-                return;
-            }
+        self.error(format!(
+            "expected token of type {:?}, found {:?}",
+            kind,
+            self.peek().kind
+        ));
+
+        Token::from(kind)
+    }
+
+    fn expect_identifier(&mut self) -> Token {
+        if let TokenKind::Identifier(..) = self.peek().kind {
+            return self.advance();
+        }
+
+        self.error(format!("expected identifier, found {:?}", self.peek().kind));
+
+        Token::from(TokenKind::Identifier(String::new()))
+    }
+
+    /// Reports an error that leaves the parser unsure of where it is, skipping ahead to the next
+    /// statement afterwards
+    fn error(&mut self, message: String) {
+        self.report(message);
+        self.panic_mode = true;
+    }
+
+    /// Reports an error in code that was otherwise parsed successfully
+    fn report(&mut self, message: String) {
+        // Only report the first error of each statement, as the rest are usually caused by it
+        if !self.panic_mode {
+            self.errors.push(format!("Parsing error: {}.", message));
         }
 
-        eprintln!("Parsing error: expected token of type {:?}.\n", kind);
         self.contains_errors = true;
     }
 
+    /// Reports an error caused by a particular token, along with the line it was found on
+    fn report_at(&mut self, token: &Token, message: String) {
+        if !self.panic_mode {
+            self.errors
+                .push(format!("Parsing error (line {}): {}.", token.line, message));
+        }
+
+        self.contains_errors = true;
+    }
+
+    fn begin_scope(&mut self) {
+        self.scopes.push(HashMap::new());
+    }
+
+    fn end_scope(&mut self) {
+        self.scopes.pop();
+    }
+
+    /// Records a name declared in the current block, so that assignments to constants can be
+    /// caught before the program runs
+    fn declare(&mut self, name: &Token, constant: bool) {
+        let scope = self.scopes.last_mut().unwrap();
+
+        if scope.insert(name.lexeme.clone(), constant) == Some(true) {
+            self.report_at(name, format!("cannot redefine constant {}", name.lexeme));
+        }
+    }
+
+    fn is_constant(&self, name: &str) -> bool {
+        self.scopes
+            .iter()
+            .rev()
+            .find_map(|scope| scope.get(name))
+            .copied()
+            .unwrap_or(false)
+    }
+
+    fn synchronize(&mut self) {
+        self.panic_mode = false;
+
+        while !self.reached_end() {
+            match self.advance().kind {
+                TokenKind::Newline | TokenKind::Dedent => return,
+                _ => {}
+            }
+        }
+    }
+
     fn parse_primary(&mut self) -> Expr {
         match self.peek().kind {
             // Literals
@@ -65,39 +182,161 @@ impl Parser {
             | TokenKind::Bool(..)
             | TokenKind::Number(..)
             | TokenKind::String(..)
+            | TokenKind::Bytes(..)
             | TokenKind::Identifier(..) => Expr::Literal(self.advance()),
 
             // Groupings
             TokenKind::LeftParen => {
                 let lhs = self.advance();
                 let expr = self.parse_expression();
-                self.expect(TokenKind::RightParen);
+                let rhs = self.expect(TokenKind::RightParen);
 
-                Expr::Grouping(lhs, Box::new(expr), Token::from(TokenKind::RightParen))
+                Expr::Grouping(lhs, Box::new(expr), rhs)
             }
 
+            // Lists
             TokenKind::LeftBracket => {
-                let lhs = self.advance();
-                let expr = self.parse_expression();
+                let bracket = self.advance();
+                let mut elements = Vec::new();
+
+                while !self.is_match(TokenKind::RightBracket) {
+                    elements.push(self.parse_expression());
+
+                    if !self.is_match(TokenKind::Comma) {
+                        break;
+                    }
+
+                    self.advance();
+                }
+
                 self.expect(TokenKind::RightBracket);
+                Expr::List(bracket, elements)
+            }
+
+            // Maps, or sets when the first element isn't followed by a colon. `{}` is an empty map.
+            TokenKind::LeftBrace => {
+                let brace = self.advance();
+
+                if self.is_match(TokenKind::RightBrace) {
+                    self.advance();
+                    return Expr::Map(brace, Vec::new());
+                }
+
+                let first = self.parse_expression();
+
+                if !self.is_match(TokenKind::Colon) {
+                    let mut elements = vec![first];
+
+                    while self.is_match(TokenKind::Comma) {
+                        self.advance();
+
+                        if self.is_match(TokenKind::RightBrace) {
+                            break;
+                        }
+
+                        elements.push(self.parse_expression());
+                    }
+
+                    self.expect(TokenKind::RightBrace);
+                    return Expr::Set(brace, elements);
+                }
+
+                let mut entries = Vec::new();
+                let mut key = first;
+
+                loop {
+                    self.expect(TokenKind::Colon);
+                    let value = self.parse_expression();
 
-                Expr::Grouping(lhs, Box::new(expr), Token::from(TokenKind::RightBracket))
+                    entries.push((key, value));
+
+                    if !self.is_match(TokenKind::Comma) {
+                        break;
+                    }
+
+                    self.advance();
+
+                    if self.is_match(TokenKind::RightBrace) {
+                        break;
+                    }
+
+                    key = self.parse_expression();
+                }
+
+                self.expect(TokenKind::RightBrace);
+                Expr::Map(brace, entries)
             }
 
-            TokenKind::Indent => {
-                let lhs = self.advance();
-                let expr = self.parse_expression();
-                self.expect(TokenKind::Dedent);
+            kind => {
+                self.error(format!("expected expression, found {:?}", kind));
+
+                // Skip over the offending token so that parsing always makes progress
+                if !self.is_match(TokenKind::Newline) {
+                    self.advance();
+                }
+
+                Expr::Literal(Token::from(TokenKind::None))
+            }
+        }
+    }
+
+    fn parse_call(&mut self) -> Expr {
+        let mut expr = self.parse_primary();
+
+        loop {
+            if self.is_match(TokenKind::LeftParen) {
+                let paren = self.advance();
+                let mut args = Vec::new();
+
+                if !self.is_match(TokenKind::RightParen) {
+                    loop {
+                        args.push(self.parse_argument());
+
+                        if !self.is_match(TokenKind::Comma) {
+                            break;
+                        }
+
+                        self.advance();
+                    }
+                }
+
+                self.expect(TokenKind::RightParen);
+                expr = Expr::Call(Box::new(expr), paren, args);
+            } else if self.is_match(TokenKind::LeftBracket) {
+                let bracket = self.advance();
+                let index = self.parse_expression();
+                self.expect(TokenKind::RightBracket);
+
+                expr = Expr::Index(Box::new(expr), bracket, Box::new(index));
+            } else if self.is_match(TokenKind::Period) {
+                self.advance();
 
-                Expr::Grouping(lhs, Box::new(expr), Token::from(TokenKind::Dedent))
+                let name = self.expect_identifier();
+                expr = Expr::Get(Box::new(expr), name);
+            } else {
+                break;
             }
+        }
+
+        expr
+    }
+
+    /// Parses a call argument, which may be passed by name as `name=value`
+    fn parse_argument(&mut self) -> (Option<Token>, Expr) {
+        if let TokenKind::Identifier(..) = self.peek().kind {
+            if self.peek_next().kind == TokenKind::Equal {
+                let name = self.advance();
+                self.advance();
 
-            _ => Expr::Literal(Token::from(TokenKind::Eof)),
+                return (Some(name), self.parse_expression());
+            }
         }
+
+        (None, self.parse_expression())
     }
 
     fn parse_unary(&mut self) -> Expr {
-        while self.is_match(TokenKind::Bang)
+        if self.is_match(TokenKind::Bang)
             || self.is_match(TokenKind::Not)
             || self.is_match(TokenKind::Minus)
         {
@@ -107,7 +346,7 @@ impl Parser {
             return Expr::Unary(op, Box::new(rhs));
         }
 
-        self.parse_primary()
+        self.parse_call()
     }
 
     fn parse_exponent(&mut self) -> Expr {
@@ -168,7 +407,10 @@ impl Parser {
     fn parse_equality(&mut self) -> Expr {
         let mut expr = self.parse_comparison();
 
-        while self.is_match(TokenKind::EqualEqual) || self.is_match(TokenKind::BangEqual) {
+        while self.is_match(TokenKind::EqualEqual)
+            || self.is_match(TokenKind::BangEqual)
+            || self.is_match(TokenKind::Is)
+        {
             let op = self.advance();
             let rhs = self.parse_comparison();
             expr = Expr::Binary(Box::new(expr), op, Box::new(rhs));
@@ -177,21 +419,642 @@ impl Parser {
         expr
     }
 
+    fn parse_and(&mut self) -> Expr {
+        let mut expr = self.parse_equality();
+
+        while self.is_match(TokenKind::And) {
+            let op = self.advance();
+            let rhs = self.parse_equality();
+            expr = Expr::Logical(Box::new(expr), op, Box::new(rhs));
+        }
+
+        expr
+    }
+
+    fn parse_or(&mut self) -> Expr {
+        let mut expr = self.parse_and();
+
+        while self.is_match(TokenKind::Or) {
+            let op = self.advance();
+            let rhs = self.parse_and();
+            expr = Expr::Logical(Box::new(expr), op, Box::new(rhs));
+        }
+
+        expr
+    }
+
     fn parse_expression(&mut self) -> Expr {
-        self.parse_equality()
+        self.parse_or()
+    }
+
+    fn parse_block(&mut self) -> Vec<Statement> {
+        self.expect(TokenKind::Colon);
+        self.expect(TokenKind::Newline);
+        self.expect(TokenKind::Indent);
+        self.begin_scope();
+
+        let mut statements = Vec::new();
+
+        while !self.reached_end() && !self.is_match(TokenKind::Dedent) {
+            statements.push(self.parse_statement());
+
+            if self.panic_mode {
+                self.synchronize();
+            }
+        }
+
+        self.end_scope();
+        self.expect(TokenKind::Dedent);
+        statements
+    }
+
+    fn parse_loop_body(&mut self, label: &Option<Token>) -> Vec<Statement> {
+        self.loops
+            .push(label.as_ref().map(|label| label.lexeme.clone()));
+        let body = self.parse_block();
+        self.loops.pop();
+
+        body
     }
 
     fn parse_print(&mut self) -> Statement {
+        self.advance();
+
         let expr = self.parse_expression();
         self.expect(TokenKind::Newline);
         Statement::Print(expr)
     }
 
+    fn parse_define(&mut self) -> Statement {
+        self.advance();
+
+        let name = self.expect_identifier();
+
+        let value = if self.is_match(TokenKind::Equal) {
+            self.advance();
+            self.parse_expression()
+        } else {
+            Expr::Literal(Token::from(TokenKind::None))
+        };
+
+        self.expect(TokenKind::Newline);
+        self.declare(&name, false);
+
+        Statement::Define(name, value)
+    }
+
+    fn parse_const(&mut self) -> Statement {
+        self.advance();
+
+        let name = self.expect_identifier();
+        self.expect(TokenKind::Equal);
+
+        let value = self.parse_expression();
+        self.expect(TokenKind::Newline);
+        self.declare(&name, true);
+
+        Statement::Const(name, value)
+    }
+
+    fn parse_if(&mut self) -> Statement {
+        self.advance();
+
+        let condition = self.parse_expression();
+        let then_branch = self.parse_block();
+
+        let else_branch = if self.is_match(TokenKind::Else) {
+            self.advance();
+
+            if self.is_match(TokenKind::If) {
+                vec![self.parse_if()]
+            } else {
+                self.parse_block()
+            }
+        } else {
+            Vec::new()
+        };
+
+        Statement::If(condition, then_branch, else_branch)
+    }
+
+    /// Parses a loop preceded by a label such as `outer:`, which `break` and `continue` statements
+    /// within it can name
+    fn parse_labeled(&mut self) -> Statement {
+        let label = self.advance();
+        self.advance();
+
+        let label_name = Some(label.lexeme.clone());
+
+        if self.loops.contains(&label_name) {
+            self.report_at(
+                &label,
+                format!(
+                    "label {} is already used by an enclosing loop",
+                    label.lexeme
+                ),
+            );
+        }
+
+        let label = Some(label);
+
+        match self.peek().kind {
+            TokenKind::While => self.parse_while(label),
+            TokenKind::Loop => self.parse_loop(label),
+            TokenKind::For => self.parse_for(label),
+
+            kind => {
+                self.error(format!("expected loop after label, found {:?}", kind));
+                Statement::Expr(Expr::Literal(Token::from(TokenKind::None)))
+            }
+        }
+    }
+
+    fn parse_while(&mut self, label: Option<Token>) -> Statement {
+        self.advance();
+
+        let condition = self.parse_expression();
+        let body = self.parse_loop_body(&label);
+
+        Statement::While(condition, body, label)
+    }
+
+    fn parse_loop(&mut self, label: Option<Token>) -> Statement {
+        self.advance();
+
+        let body = self.parse_loop_body(&label);
+        Statement::Loop(body, label)
+    }
+
+    fn parse_for(&mut self, label: Option<Token>) -> Statement {
+        self.advance();
+
+        let name = self.expect_identifier();
+        self.expect(TokenKind::In);
+
+        let iterable = self.parse_expression();
+
+        self.begin_scope();
+        self.declare(&name, false);
+        let body = self.parse_loop_body(&label);
+        self.end_scope();
+
+        Statement::For(name, iterable, body, label)
+    }
+
+    fn parse_with(&mut self) -> Statement {
+        self.advance();
+
+        let manager = self.parse_expression();
+        let name = self.parse_alias();
+
+        self.begin_scope();
+
+        if let Some(name) = &name {
+            self.declare(name, false);
+        }
+
+        let body = self.parse_block();
+        self.end_scope();
+
+        Statement::With(manager, name, body)
+    }
+
+    fn parse_try(&mut self) -> Statement {
+        self.advance();
+
+        let body = self.parse_block();
+        self.expect(TokenKind::Catch);
+
+        let name = match self.peek().kind {
+            TokenKind::Identifier(..) => Some(self.advance()),
+            _ => None,
+        };
+
+        self.begin_scope();
+
+        if let Some(name) = &name {
+            self.declare(name, false);
+        }
+
+        let handler = self.parse_block();
+        self.end_scope();
+
+        Statement::Try(body, name, handler)
+    }
+
+    fn parse_loop_control(&mut self) -> Statement {
+        let keyword = self.advance();
+
+        if self.loops.is_empty() {
+            self.report(format!("found {:?} outside of a loop", keyword.kind));
+        }
+
+        let label = match self.peek().kind {
+            TokenKind::Identifier(..) => Some(self.advance()),
+            _ => None,
+        };
+
+        if let Some(label) = &label {
+            if !self.loops.contains(&Some(label.lexeme.clone())) {
+                self.report_at(label, format!("unknown loop label {}", label.lexeme));
+            }
+        }
+
+        self.expect(TokenKind::Newline);
+
+        if keyword.kind == TokenKind::Break {
+            Statement::Break(label)
+        } else {
+            Statement::Continue(label)
+        }
+    }
+
+    fn parse_scope_declaration(&mut self) -> Statement {
+        let keyword = self.advance();
+
+        if self.function_depth == 0 {
+            self.report(format!("found {:?} outside of a function", keyword.kind));
+        }
+
+        let mut names = vec![self.expect_identifier()];
+
+        while self.is_match(TokenKind::Comma) {
+            self.advance();
+            names.push(self.expect_identifier());
+        }
+
+        // Whether the binding these refer to is constant is only known at runtime
+        for name in &names {
+            self.scopes
+                .last_mut()
+                .unwrap()
+                .insert(name.lexeme.clone(), false);
+        }
+
+        self.expect(TokenKind::Newline);
+
+        if keyword.kind == TokenKind::Global {
+            Statement::Global(names)
+        } else {
+            Statement::Nonlocal(names)
+        }
+    }
+
+    /// Parses `import lib.utils` or `import lib.utils as utils`, which binds the module to the last
+    /// part of its path unless given another name
+    fn parse_import(&mut self) -> Statement {
+        self.advance();
+
+        let path = self.parse_module_path();
+        let alias = self.parse_alias();
+
+        self.expect(TokenKind::Newline);
+
+        let name = alias.as_ref().or(path.last()).cloned();
+
+        if let Some(name) = name {
+            self.declare(&name, false);
+        }
+
+        Statement::Import(path, alias)
+    }
+
+    /// Parses `from lib.utils import helper, other as alias`
+    fn parse_from_import(&mut self) -> Statement {
+        self.advance();
+
+        let path = self.parse_module_path();
+        self.expect(TokenKind::Import);
+
+        let mut names = Vec::new();
+
+        loop {
+            let name = self.expect_identifier();
+            let alias = self.parse_alias();
+
+            self.declare(alias.as_ref().unwrap_or(&name), false);
+            names.push((name, alias));
+
+            if !self.is_match(TokenKind::Comma) {
+                break;
+            }
+
+            self.advance();
+        }
+
+        self.expect(TokenKind::Newline);
+        Statement::FromImport(path, names)
+    }
+
+    fn parse_module_path(&mut self) -> Vec<Token> {
+        let mut path = vec![self.expect_identifier()];
+
+        while self.is_match(TokenKind::Period) {
+            self.advance();
+            path.push(self.expect_identifier());
+        }
+
+        path
+    }
+
+    fn parse_alias(&mut self) -> Option<Token> {
+        if self.is_match(TokenKind::As) {
+            self.advance();
+            Some(self.expect_identifier())
+        } else {
+            None
+        }
+    }
+
+    fn parse_func(&mut self) -> Statement {
+        self.advance();
+
+        let name = self.expect_identifier();
+        self.expect(TokenKind::LeftParen);
+
+        let params = self.parse_parameters(&name);
+        self.expect(TokenKind::RightParen);
+
+        // Declared before the body is parsed so that the function can refer to itself
+        self.declare(&name, false);
+        self.begin_scope();
+
+        for param in &params {
+            self.declare(&param.name, false);
+        }
+
+        // Loop control statements can't reach through a function body into an enclosing loop
+        let loops = std::mem::take(&mut self.loops);
+        let found_yield = std::mem::replace(&mut self.found_yield, false);
+        self.function_depth += 1;
+
+        let body = self.parse_block();
+        let is_generator = self.found_yield;
+        self.end_scope();
+
+        self.function_depth -= 1;
+        self.loops = loops;
+        self.found_yield = found_yield;
+
+        Statement::Func(name, params, Rc::new(body), is_generator)
+    }
+
+    /// Parses a parameter list such as `host, port = 8080, *rest, timeout = 5`. Parameters after
+    /// the variadic one can only be passed by name.
+    fn parse_parameters(&mut self, func: &Token) -> Vec<Parameter> {
+        let mut params: Vec<Parameter> = Vec::new();
+
+        while !self.is_match(TokenKind::RightParen) {
+            let variadic = if self.is_match(TokenKind::Star) {
+                self.advance();
+                true
+            } else {
+                false
+            };
+
+            let name = self.expect_identifier();
+
+            let default = if !variadic && self.is_match(TokenKind::Equal) {
+                self.advance();
+                Some(self.parse_expression())
+            } else {
+                None
+            };
+
+            let after_variadic = params.iter().any(|param| param.variadic);
+
+            if params.iter().any(|param| param.name.lexeme == name.lexeme) {
+                self.report(format!(
+                    "duplicate parameter {} in function {}",
+                    name.lexeme, func.lexeme
+                ));
+            } else if variadic && after_variadic {
+                self.report(format!(
+                    "function {} has more than one variadic parameter",
+                    func.lexeme
+                ));
+            } else if !variadic
+                && !after_variadic
+                && default.is_none()
+                && params.iter().any(|param| param.default.is_some())
+            {
+                self.report(format!(
+                    "parameter {} without a default follows a parameter with one in function {}",
+                    name.lexeme, func.lexeme
+                ));
+            }
+
+            params.push(Parameter {
+                name,
+                default,
+                variadic,
+            });
+
+            if !self.is_match(TokenKind::Comma) {
+                break;
+            }
+
+            self.advance();
+        }
+
+        params
+    }
+
+    fn parse_return(&mut self) -> Statement {
+        self.advance();
+
+        if self.function_depth == 0 {
+            self.report(String::from("found Return outside of a function"));
+        }
+
+        let value = if self.is_match(TokenKind::Newline) {
+            None
+        } else {
+            Some(self.parse_expression())
+        };
+
+        self.expect(TokenKind::Newline);
+        Statement::Return(value)
+    }
+
+    fn parse_yield(&mut self) -> Statement {
+        self.advance();
+
+        if self.function_depth == 0 {
+            self.report(String::from("found Yield outside of a function"));
+        }
+
+        self.found_yield = true;
+
+        let value = self.parse_expression();
+        self.expect(TokenKind::Newline);
+
+        Statement::Yield(value)
+    }
+
+    fn parse_class(&mut self) -> Statement {
+        self.advance();
+
+        let name = self.expect_identifier();
+        self.declare(&name, false);
+
+        self.expect(TokenKind::Colon);
+        self.expect(TokenKind::Newline);
+        self.expect(TokenKind::Indent);
+
+        // Methods are looked up through the class, so their names don't leak into the block
+        self.begin_scope();
+        let mut methods = Vec::new();
+
+        while !self.reached_end() && !self.is_match(TokenKind::Dedent) {
+            if self.is_match(TokenKind::Func) {
+                methods.push(self.parse_func());
+            } else {
+                self.error(format!(
+                    "expected method definition in class {}, found {:?}",
+                    name.lexeme,
+                    self.peek().kind
+                ));
+            }
+
+            if self.panic_mode {
+                self.synchronize();
+            }
+        }
+
+        self.end_scope();
+        self.expect(TokenKind::Dedent);
+        Statement::Class(name, methods)
+    }
+
+    fn parse_enum(&mut self) -> Statement {
+        self.advance();
+
+        let name = self.expect_identifier();
+        self.declare(&name, false);
+
+        self.expect(TokenKind::Colon);
+        self.expect(TokenKind::Newline);
+        self.expect(TokenKind::Indent);
+
+        let mut variants: Vec<(Token, Vec<Token>)> = Vec::new();
+
+        while !self.reached_end() && !self.is_match(TokenKind::Dedent) {
+            let variant = self.expect_identifier();
+            let mut fields = Vec::new();
+
+            if self.is_match(TokenKind::LeftParen) {
+                self.advance();
+
+                loop {
+                    fields.push(self.expect_identifier());
+
+                    if !self.is_match(TokenKind::Comma) {
+                        break;
+                    }
+
+                    self.advance();
+                }
+
+                self.expect(TokenKind::RightParen);
+            }
+
+            self.expect(TokenKind::Newline);
+
+            if variants
+                .iter()
+                .any(|(other, _)| other.lexeme == variant.lexeme)
+            {
+                self.report(format!(
+                    "duplicate variant {} in enum {}",
+                    variant.lexeme, name.lexeme
+                ));
+            }
+
+            variants.push((variant, fields));
+
+            if self.panic_mode {
+                self.synchronize();
+            }
+        }
+
+        self.expect(TokenKind::Dedent);
+        Statement::Enum(name, variants)
+    }
+
+    fn parse_expression_statement(&mut self) -> Statement {
+        let expr = self.parse_expression();
+
+        let statement = match self.peek().kind {
+            TokenKind::Equal
+            | TokenKind::PlusEqual
+            | TokenKind::MinusEqual
+            | TokenKind::StarEqual
+            | TokenKind::SlashEqual
+            | TokenKind::PercentEqual
+            | TokenKind::CaretEqual => {
+                let op = self.advance();
+                let value = self.parse_expression();
+
+                match &expr {
+                    Expr::Literal(
+                        name @ Token {
+                            kind: TokenKind::Identifier(..),
+                            ..
+                        },
+                    ) => {
+                        if self.is_constant(&name.lexeme) {
+                            self.report_at(
+                                name,
+                                format!("cannot assign to constant {}", name.lexeme),
+                            );
+                        }
+                    }
+
+                    Expr::Get(..) | Expr::Index(..) => {}
+
+                    _ => self.report(String::from("invalid assignment target")),
+                }
+
+                Statement::Assign(expr, op, value)
+            }
+
+            _ => Statement::Expr(expr),
+        };
+
+        self.expect(TokenKind::Newline);
+        statement
+    }
+
     fn parse_statement(&mut self) -> Statement {
-        match self.advance().kind {
+        match self.peek().kind {
             TokenKind::Print => self.parse_print(),
+            TokenKind::Define => self.parse_define(),
+            TokenKind::Const => self.parse_const(),
+
+            TokenKind::If => self.parse_if(),
+            TokenKind::While => self.parse_while(None),
+            TokenKind::Loop => self.parse_loop(None),
+            TokenKind::For => self.parse_for(None),
+            TokenKind::With => self.parse_with(),
+            TokenKind::Try => self.parse_try(),
+            TokenKind::Break | TokenKind::Continue => self.parse_loop_control(),
+
+            TokenKind::Identifier(..) if self.peek_next().kind == TokenKind::Colon => {
+                self.parse_labeled()
+            }
+
+            TokenKind::Global | TokenKind::Nonlocal => self.parse_scope_declaration(),
+            TokenKind::Import => self.parse_import(),
+            TokenKind::From => self.parse_from_import(),
 
-            _ => Statement::Expr(self.parse_expression()),
+            TokenKind::Func => self.parse_func(),
+            TokenKind::Return => self.parse_return(),
+            TokenKind::Yield => self.parse_yield(),
+            TokenKind::Class => self.parse_class(),
+            TokenKind::Enum => self.parse_enum(),
+
+            _ => self.parse_expression_statement(),
         }
     }
 
@@ -199,9 +1062,19 @@ impl Parser {
         self.reset();
         let mut statements = Vec::new();
 
-        while self.cursor < self.tokens.len() - 1 {
+        while !self.reached_end() {
+            // Skip over blank lines left behind by error recovery
+            if self.is_match(TokenKind::Newline) {
+                self.advance();
+                continue;
+            }
+
             let statement = self.parse_statement();
             statements.push(statement);
+
+            if self.panic_mode {
+                self.synchronize();
+            }
         }
 
         statements
@@ -0,0 +1,772 @@
+use std::collections::{HashMap, HashSet};
+
+/// Limit on the copies made of a counted repetition, so that patterns like `a{1000000}` can't
+/// exhaust memory
+const MAX_REPEAT: usize = 1000;
+
+/// Limit on the instructions in a compiled pattern, which nested repetitions multiply
+const MAX_PROGRAM: usize = 100_000;
+
+/// A compiled regular expression, matched by a backtracking machine over characters.
+///
+/// Supported syntax: literals, `.`, classes like `[a-z_]` and `[^0-9]`, the escapes `\d \w \s`
+/// (and their negations `\D \W \S`), the anchors `^ $ \b \B`, groups `(...)`, non-capturing
+/// groups `(?:...)`, named groups `(?P<name>...)` or `(?<name>...)`, alternation `|`, and the
+/// quantifiers `* + ? {n} {n,} {n,m}`, each of which can be made lazy with a trailing `?`.
+#[derive(Debug)]
+pub struct Regex {
+    program: Vec<Inst>,
+
+    // Number of groups, including the whole match as group 0
+    groups: usize,
+    names: HashMap<String, usize>,
+}
+
+/// The positions of a match and its groups, as character indices into the searched text
+#[derive(Debug, Clone, PartialEq)]
+pub struct Captures {
+    slots: Vec<Option<usize>>,
+}
+
+impl Captures {
+    /// The start and end of a group, or `None` if it didn't take part in the match
+    pub fn get(&self, group: usize) -> Option<(usize, usize)> {
+        match (self.slots.get(group * 2), self.slots.get(group * 2 + 1)) {
+            (Some(Some(start)), Some(Some(end))) => Some((*start, *end)),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug)]
+enum Inst {
+    Char(char),
+    Any,
+    Class(Class),
+    Assert(Assertion),
+
+    // Tries the first target, then the second if that fails
+    Split(usize, usize),
+    Jump(usize),
+    Save(usize),
+    Match,
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Assertion {
+    Start,
+    End,
+    WordBoundary,
+    NotWordBoundary,
+}
+
+#[derive(Debug, Clone)]
+struct Class {
+    negated: bool,
+    items: Vec<ClassItem>,
+}
+
+#[derive(Debug, Clone)]
+enum ClassItem {
+    Range(char, char),
+    Digit(bool),
+    Word(bool),
+    Space(bool),
+}
+
+#[derive(Debug, Clone)]
+enum Node {
+    Empty,
+    Char(char),
+    Any,
+    Class(Class),
+    Assert(Assertion),
+    Group(Box<Node>, Option<usize>),
+    Concat(Vec<Node>),
+    Alternate(Vec<Node>),
+    Repeat {
+        node: Box<Node>,
+        min: usize,
+        max: Option<usize>,
+        greedy: bool,
+    },
+}
+
+impl Regex {
+    /// Compiles a pattern, or describes what's wrong with it
+    pub fn new(pattern: &str) -> Result<Regex, String> {
+        let mut parser = Parser {
+            chars: pattern.chars().collect(),
+            position: 0,
+            groups: 1,
+            names: HashMap::new(),
+        };
+
+        let node = parser.parse_alternation()?;
+
+        if parser.position < parser.chars.len() {
+            return Err(format!(
+                "unbalanced parenthesis at position {}",
+                parser.position
+            ));
+        }
+
+        if program_size(&node) > MAX_PROGRAM {
+            return Err(String::from("pattern is too large"));
+        }
+
+        let mut program = vec![Inst::Save(0)];
+        compile(&node, &mut program);
+        program.push(Inst::Save(1));
+        program.push(Inst::Match);
+
+        Ok(Regex {
+            program,
+            groups: parser.groups,
+            names: parser.names,
+        })
+    }
+
+    pub fn groups(&self) -> usize {
+        self.groups
+    }
+
+    /// The group numbers of named groups
+    pub fn names(&self) -> &HashMap<String, usize> {
+        &self.names
+    }
+
+    /// Matches the pattern at exactly `start`
+    pub fn match_at(&self, text: &[char], start: usize) -> Option<Captures> {
+        self.run(text, start, &mut HashSet::new())
+    }
+
+    /// Finds the leftmost match starting at or after `start`
+    pub fn search(&self, text: &[char], start: usize) -> Option<Captures> {
+        // A state that failed from one starting point fails from all of them, so the visited
+        // states are shared between attempts
+        let mut visited = HashSet::new();
+
+        (start..=text.len()).find_map(|start| self.run(text, start, &mut visited))
+    }
+
+    /// Every match that doesn't overlap an earlier one, from left to right
+    pub fn find_all(&self, text: &[char]) -> Vec<Captures> {
+        let mut matches = Vec::new();
+        let mut start = 0;
+
+        while start <= text.len() {
+            let captures = match self.search(text, start) {
+                Some(captures) => captures,
+                None => break,
+            };
+
+            let (begin, end) = captures.get(0).unwrap();
+
+            // Empty matches would be found again in the same place, so move past them
+            start = if end == begin { end + 1 } else { end };
+            matches.push(captures);
+        }
+
+        matches
+    }
+
+    fn run(&self, text: &[char], start: usize, visited: &mut HashSet<usize>) -> Option<Captures> {
+        enum Job {
+            Explore(usize, usize),
+            Restore(usize, Option<usize>),
+        }
+
+        let mut slots = vec![None; self.groups * 2];
+        let mut jobs = vec![Job::Explore(0, start)];
+
+        while let Some(job) = jobs.pop() {
+            let (mut pc, mut pos) = match job {
+                Job::Explore(pc, pos) => (pc, pos),
+                Job::Restore(slot, value) => {
+                    slots[slot] = value;
+                    continue;
+                }
+            };
+
+            loop {
+                // Captures don't affect whether the rest of a match succeeds, so a state that was
+                // already explored can't lead anywhere new
+                let state = pc * (text.len() + 1) + pos;
+
+                if !visited.insert(state) {
+                    break;
+                }
+
+                match &self.program[pc] {
+                    Inst::Char(c) => match text.get(pos) {
+                        Some(next) if next == c => pos += 1,
+                        _ => break,
+                    },
+
+                    Inst::Any => match text.get(pos) {
+                        Some(next) if *next != '\n' => pos += 1,
+                        _ => break,
+                    },
+
+                    Inst::Class(class) => match text.get(pos) {
+                        Some(next) if class.matches(*next) => pos += 1,
+                        _ => break,
+                    },
+
+                    Inst::Assert(assertion) => {
+                        if !assertion.holds(text, pos) {
+                            break;
+                        }
+                    }
+
+                    Inst::Split(first, second) => {
+                        jobs.push(Job::Explore(*second, pos));
+                        pc = *first;
+                        continue;
+                    }
+
+                    Inst::Jump(target) => {
+                        pc = *target;
+                        continue;
+                    }
+
+                    Inst::Save(slot) => {
+                        jobs.push(Job::Restore(*slot, slots[*slot]));
+                        slots[*slot] = Some(pos);
+                    }
+
+                    Inst::Match => return Some(Captures { slots }),
+                }
+
+                pc += 1;
+            }
+        }
+
+        None
+    }
+}
+
+impl Class {
+    fn matches(&self, c: char) -> bool {
+        let found = self.items.iter().any(|item| match item {
+            ClassItem::Range(low, high) => (*low..=*high).contains(&c),
+            ClassItem::Digit(negated) => c.is_ascii_digit() != *negated,
+            ClassItem::Word(negated) => is_word(c) != *negated,
+            ClassItem::Space(negated) => c.is_whitespace() != *negated,
+        });
+
+        found != self.negated
+    }
+}
+
+impl Assertion {
+    fn holds(self, text: &[char], pos: usize) -> bool {
+        let boundary = || {
+            let before = pos > 0 && is_word(text[pos - 1]);
+            let after = pos < text.len() && is_word(text[pos]);
+            before != after
+        };
+
+        match self {
+            Assertion::Start => pos == 0,
+            Assertion::End => pos == text.len(),
+            Assertion::WordBoundary => boundary(),
+            Assertion::NotWordBoundary => !boundary(),
+        }
+    }
+}
+
+fn is_word(c: char) -> bool {
+    c.is_alphanumeric() || c == '_'
+}
+
+/// The number of instructions `compile` would produce for a node, saturating rather than
+/// overflowing for huge repetitions
+fn program_size(node: &Node) -> usize {
+    match node {
+        Node::Empty => 0,
+        Node::Char(..) | Node::Any | Node::Class(..) | Node::Assert(..) => 1,
+
+        Node::Group(node, Some(..)) => program_size(node).saturating_add(2),
+        Node::Group(node, None) => program_size(node),
+
+        Node::Concat(nodes) => nodes
+            .iter()
+            .fold(0, |total, node| total.saturating_add(program_size(node))),
+
+        // Every alternative but the last adds a split and a jump
+        Node::Alternate(nodes) => nodes.iter().fold(0, |total, node| {
+            total.saturating_add(program_size(node).saturating_add(2))
+        }),
+
+        Node::Repeat { node, min, max, .. } => {
+            let size = program_size(node);
+            let required = size.saturating_mul(*min);
+
+            let optional = match max {
+                None => size.saturating_add(2),
+                Some(max) => size.saturating_add(1).saturating_mul(max - min),
+            };
+
+            required.saturating_add(optional)
+        }
+    }
+}
+
+fn compile(node: &Node, program: &mut Vec<Inst>) {
+    match node {
+        Node::Empty => {}
+        Node::Char(c) => program.push(Inst::Char(*c)),
+        Node::Any => program.push(Inst::Any),
+        Node::Class(class) => program.push(Inst::Class(class.clone())),
+        Node::Assert(assertion) => program.push(Inst::Assert(*assertion)),
+
+        Node::Group(node, index) => match index {
+            Some(index) => {
+                program.push(Inst::Save(index * 2));
+                compile(node, program);
+                program.push(Inst::Save(index * 2 + 1));
+            }
+            None => compile(node, program),
+        },
+
+        Node::Concat(nodes) => {
+            for node in nodes {
+                compile(node, program);
+            }
+        }
+
+        // Each alternative but the last tries itself first, then falls through to the next
+        Node::Alternate(nodes) => {
+            let mut jumps = Vec::new();
+
+            for (index, node) in nodes.iter().enumerate() {
+                if index == nodes.len() - 1 {
+                    compile(node, program);
+                    break;
+                }
+
+                let split = program.len();
+                program.push(Inst::Split(split + 1, 0));
+                compile(node, program);
+                jumps.push(program.len());
+                program.push(Inst::Jump(0));
+
+                let next = program.len();
+                program[split] = Inst::Split(split + 1, next);
+            }
+
+            let end = program.len();
+
+            for jump in jumps {
+                program[jump] = Inst::Jump(end);
+            }
+        }
+
+        Node::Repeat {
+            node,
+            min,
+            max,
+            greedy,
+        } => {
+            for _ in 0..*min {
+                compile(node, program);
+            }
+
+            match max {
+                // Any number more, looping back to the split after each one
+                None => {
+                    let split = program.len();
+                    program.push(Inst::Split(0, 0));
+                    compile(node, program);
+                    program.push(Inst::Jump(split));
+                    program[split] = split_to(split + 1, program.len(), *greedy);
+                }
+
+                // Up to `max - min` more, each only tried if the one before matched
+                Some(max) => {
+                    let mut splits = Vec::new();
+
+                    for _ in *min..*max {
+                        splits.push(program.len());
+                        program.push(Inst::Split(0, 0));
+                        compile(node, program);
+                    }
+
+                    let end = program.len();
+
+                    for split in splits {
+                        program[split] = split_to(split + 1, end, *greedy);
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// A split into more repetitions at `more` or on past them at `done`, preferring more if greedy
+fn split_to(more: usize, done: usize, greedy: bool) -> Inst {
+    if greedy {
+        Inst::Split(more, done)
+    } else {
+        Inst::Split(done, more)
+    }
+}
+
+struct Parser {
+    chars: Vec<char>,
+    position: usize,
+    groups: usize,
+    names: HashMap<String, usize>,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.position).copied()
+    }
+
+    fn accept(&mut self, expected: char) -> bool {
+        if self.peek() == Some(expected) {
+            self.position += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn error<T>(&self, message: &str) -> Result<T, String> {
+        Err(format!("{} at position {}", message, self.position))
+    }
+
+    fn parse_alternation(&mut self) -> Result<Node, String> {
+        let mut alternatives = vec![self.parse_concat()?];
+
+        while self.accept('|') {
+            alternatives.push(self.parse_concat()?);
+        }
+
+        if alternatives.len() == 1 {
+            Ok(alternatives.pop().unwrap())
+        } else {
+            Ok(Node::Alternate(alternatives))
+        }
+    }
+
+    fn parse_concat(&mut self) -> Result<Node, String> {
+        let mut nodes = Vec::new();
+
+        while let Some(c) = self.peek() {
+            if c == '|' || c == ')' {
+                break;
+            }
+
+            let atom = self.parse_atom()?;
+            nodes.push(self.parse_repeat(atom)?);
+        }
+
+        match nodes.len() {
+            0 => Ok(Node::Empty),
+            1 => Ok(nodes.pop().unwrap()),
+            _ => Ok(Node::Concat(nodes)),
+        }
+    }
+
+    fn parse_repeat(&mut self, node: Node) -> Result<Node, String> {
+        let start = self.position;
+
+        let (min, max) = match self.parse_quantifier()? {
+            Some(count) => count,
+            None => return Ok(node),
+        };
+
+        if let Node::Assert(..) | Node::Empty = node {
+            self.position = start;
+            return self.error("nothing to repeat");
+        }
+
+        let greedy = !self.accept('?');
+
+        // A quantifier can't itself be repeated, as in `a**`, without a group around it
+        let next = self.position;
+
+        if self.parse_quantifier()?.is_some() {
+            self.position = next;
+            return self.error("multiple repeat");
+        }
+
+        Ok(Node::Repeat {
+            node: Box::new(node),
+            min,
+            max,
+            greedy,
+        })
+    }
+
+    /// Reads a quantifier's bounds, or nothing if there isn't a quantifier next
+    fn parse_quantifier(&mut self) -> Result<Option<(usize, Option<usize>)>, String> {
+        let count = match self.peek() {
+            Some('*') => (0, None),
+            Some('+') => (1, None),
+            Some('?') => (0, Some(1)),
+            Some('{') => return self.parse_count(),
+            _ => return Ok(None),
+        };
+
+        self.position += 1;
+        Ok(Some(count))
+    }
+
+    /// Reads a counted repetition like `{2}`, `{2,}` or `{2,5}`. A brace that doesn't start one
+    /// is left to be read as a literal character.
+    fn parse_count(&mut self) -> Result<Option<(usize, Option<usize>)>, String> {
+        let start = self.position;
+        self.position += 1;
+
+        let min = self.parse_number();
+        let max = if self.accept(',') {
+            self.parse_number()
+        } else {
+            min
+        };
+
+        let count = match (min, self.accept('}')) {
+            (Some(min), true) => (min, max),
+            _ => {
+                self.position = start;
+                return Ok(None);
+            }
+        };
+
+        if count.1.is_some_and(|max| max < count.0) {
+            self.position = start;
+            return self.error("repetition range is backwards");
+        }
+
+        if count.0 > MAX_REPEAT || count.1.is_some_and(|max| max > MAX_REPEAT) {
+            self.position = start;
+            return self.error("repetition count is too large");
+        }
+
+        Ok(Some(count))
+    }
+
+    fn parse_number(&mut self) -> Option<usize> {
+        let start = self.position;
+
+        while self.peek().is_some_and(|c| c.is_ascii_digit()) {
+            self.position += 1;
+        }
+
+        let digits: String = self.chars[start..self.position].iter().collect();
+        digits.parse().ok()
+    }
+
+    fn parse_atom(&mut self) -> Result<Node, String> {
+        let c = self.peek().unwrap();
+
+        match c {
+            '(' => self.parse_group(),
+            '[' => self.parse_class(),
+
+            '*' | '+' | '?' => self.error("nothing to repeat"),
+
+            '.' => {
+                self.position += 1;
+                Ok(Node::Any)
+            }
+            '^' => {
+                self.position += 1;
+                Ok(Node::Assert(Assertion::Start))
+            }
+            '$' => {
+                self.position += 1;
+                Ok(Node::Assert(Assertion::End))
+            }
+
+            '\\' => {
+                self.position += 1;
+
+                match self.peek() {
+                    Some('b') => {
+                        self.position += 1;
+                        Ok(Node::Assert(Assertion::WordBoundary))
+                    }
+                    Some('B') => {
+                        self.position += 1;
+                        Ok(Node::Assert(Assertion::NotWordBoundary))
+                    }
+
+                    _ => match self.parse_escape()? {
+                        ClassItem::Range(c, _) => Ok(Node::Char(c)),
+                        item => Ok(Node::Class(Class {
+                            negated: false,
+                            items: vec![item],
+                        })),
+                    },
+                }
+            }
+
+            _ => {
+                self.position += 1;
+                Ok(Node::Char(c))
+            }
+        }
+    }
+
+    fn parse_group(&mut self) -> Result<Node, String> {
+        let start = self.position;
+        self.position += 1;
+
+        let index = if self.accept('?') {
+            if self.accept(':') {
+                None
+            } else if (self.accept('P') && self.accept('<')) || self.accept('<') {
+                let name = self.parse_group_name()?;
+
+                if self.names.contains_key(&name) {
+                    return self.error(&format!("redefinition of group name '{}'", name));
+                }
+
+                self.names.insert(name, self.groups);
+                Some(self.next_group())
+            } else {
+                return self.error("unknown group extension");
+            }
+        } else {
+            Some(self.next_group())
+        };
+
+        let node = self.parse_alternation()?;
+
+        if !self.accept(')') {
+            self.position = start;
+            return self.error("unbalanced parenthesis");
+        }
+
+        Ok(Node::Group(Box::new(node), index))
+    }
+
+    fn next_group(&mut self) -> usize {
+        self.groups += 1;
+        self.groups - 1
+    }
+
+    fn parse_group_name(&mut self) -> Result<String, String> {
+        let start = self.position;
+
+        while self.peek().is_some_and(is_word) {
+            self.position += 1;
+        }
+
+        let name: String = self.chars[start..self.position].iter().collect();
+
+        if name.is_empty() || name.starts_with(|c: char| c.is_ascii_digit()) {
+            return self.error("bad group name");
+        }
+
+        if !self.accept('>') {
+            return self.error("expected '>' after group name");
+        }
+
+        Ok(name)
+    }
+
+    fn parse_class(&mut self) -> Result<Node, String> {
+        let start = self.position;
+        self.position += 1;
+
+        let negated = self.accept('^');
+        let mut items = Vec::new();
+
+        loop {
+            let c = match self.peek() {
+                Some(c) => c,
+                None => {
+                    self.position = start;
+                    return self.error("unterminated character class");
+                }
+            };
+
+            // A bracket straight after the opening one is part of the class rather than its end
+            if c == ']' && !items.is_empty() {
+                self.position += 1;
+                break;
+            }
+
+            let item = self.parse_class_item()?;
+
+            // A dash between two characters makes a range, and is otherwise literal
+            let low = match item {
+                ClassItem::Range(low, _)
+                    if self.peek() == Some('-')
+                        && !matches!(self.chars.get(self.position + 1), None | Some(']')) =>
+                {
+                    low
+                }
+                item => {
+                    items.push(item);
+                    continue;
+                }
+            };
+
+            self.position += 1;
+
+            let high = match self.parse_class_item()? {
+                ClassItem::Range(high, _) => high,
+                _ => return self.error("bad character range"),
+            };
+
+            if high < low {
+                return self.error("bad character range");
+            }
+
+            items.push(ClassItem::Range(low, high));
+        }
+
+        Ok(Node::Class(Class { negated, items }))
+    }
+
+    fn parse_class_item(&mut self) -> Result<ClassItem, String> {
+        let c = self.peek().unwrap();
+        self.position += 1;
+
+        if c == '\\' {
+            self.parse_escape()
+        } else {
+            Ok(ClassItem::Range(c, c))
+        }
+    }
+
+    /// Reads what follows a backslash, as a single character range or a class shorthand
+    fn parse_escape(&mut self) -> Result<ClassItem, String> {
+        let c = match self.peek() {
+            Some(c) => c,
+            None => return self.error("pattern ends with a backslash"),
+        };
+
+        self.position += 1;
+
+        let item = match c {
+            'd' => ClassItem::Digit(false),
+            'D' => ClassItem::Digit(true),
+            'w' => ClassItem::Word(false),
+            'W' => ClassItem::Word(true),
+            's' => ClassItem::Space(false),
+            'S' => ClassItem::Space(true),
+
+            'n' => ClassItem::Range('\n', '\n'),
+            'r' => ClassItem::Range('\r', '\r'),
+            't' => ClassItem::Range('\t', '\t'),
+
+            c if c.is_alphanumeric() => {
+                self.position -= 1;
+                return self.error(&format!("bad escape '\\{}'", c));
+            }
+
+            c => ClassItem::Range(c, c),
+        };
+
+        Ok(item)
+    }
+}
@@ -0,0 +1,139 @@
+use std::collections::HashMap;
+
+use crate::interpreter::RuntimeError;
+use crate::map::Key;
+use crate::token::{Token, TokenKind};
+
+/// A collection of distinct values, which keeps them in the order they were first added. Only
+/// values that can be map keys can be members.
+#[derive(Debug, Default)]
+pub struct Set {
+    values: Vec<Token>,
+    positions: HashMap<Key, usize>,
+}
+
+impl Set {
+    pub fn new() -> Set {
+        Set::default()
+    }
+
+    pub fn len(&self) -> usize {
+        self.values.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.values.is_empty()
+    }
+
+    pub fn values(&self) -> &[Token] {
+        &self.values
+    }
+
+    pub fn contains(&self, value: &Token) -> Result<bool, RuntimeError> {
+        Ok(self.positions.contains_key(&key(value)?))
+    }
+
+    /// Adds a value, returning whether it wasn't already there
+    pub fn insert(&mut self, value: Token) -> Result<bool, RuntimeError> {
+        let hashed = key(&value)?;
+
+        if self.positions.contains_key(&hashed) {
+            return Ok(false);
+        }
+
+        self.positions.insert(hashed, self.values.len());
+        self.values.push(value);
+        Ok(true)
+    }
+
+    /// Removes a value, returning whether it was there
+    pub fn remove(&mut self, value: &Token) -> Result<bool, RuntimeError> {
+        let position = match self.positions.remove(&key(value)?) {
+            Some(position) => position,
+            None => return Ok(false),
+        };
+
+        self.values.remove(position);
+
+        // Values after the removed one have moved back a place
+        for other in self.positions.values_mut() {
+            if *other > position {
+                *other -= 1;
+            }
+        }
+
+        Ok(true)
+    }
+
+    /// The values in either set
+    pub fn union(&self, other: &Set) -> Result<Set, RuntimeError> {
+        self.combine(other, |in_self, in_other| in_self || in_other)
+    }
+
+    /// The values in both sets
+    pub fn intersection(&self, other: &Set) -> Result<Set, RuntimeError> {
+        self.combine(other, |in_self, in_other| in_self && in_other)
+    }
+
+    /// The values in this set but not the other
+    pub fn difference(&self, other: &Set) -> Result<Set, RuntimeError> {
+        self.combine(other, |in_self, in_other| in_self && !in_other)
+    }
+
+    /// The values in exactly one of the sets
+    pub fn symmetric_difference(&self, other: &Set) -> Result<Set, RuntimeError> {
+        self.combine(other, |in_self, in_other| in_self != in_other)
+    }
+
+    /// Applies a set operator, or gives `None` if it isn't one. `+` is union, `-` difference, `*`
+    /// intersection and `^` symmetric difference.
+    pub fn operation(&self, op: &TokenKind, other: &Set) -> Result<Option<Set>, RuntimeError> {
+        let result = match op {
+            TokenKind::Plus => self.union(other)?,
+            TokenKind::Minus => self.difference(other)?,
+            TokenKind::Star => self.intersection(other)?,
+            TokenKind::Caret => self.symmetric_difference(other)?,
+            _ => return Ok(None),
+        };
+
+        Ok(Some(result))
+    }
+
+    /// The values of both sets, this one's first, which `keep` accepts given whether they are in
+    /// each set
+    fn combine<F>(&self, other: &Set, keep: F) -> Result<Set, RuntimeError>
+    where
+        F: Fn(bool, bool) -> bool,
+    {
+        let mut result = Set::new();
+
+        for value in &self.values {
+            if keep(true, other.contains(value)?) {
+                result.insert(value.clone())?;
+            }
+        }
+
+        for value in &other.values {
+            if keep(self.contains(value)?, true) {
+                result.insert(value.clone())?;
+            }
+        }
+
+        Ok(result)
+    }
+}
+
+fn key(value: &Token) -> Result<Key, RuntimeError> {
+    Key::from(value).map_err(|_| {
+        RuntimeError::new(format!(
+            "Type error: value of type {:?} can't be a member of a set.",
+            value.kind
+        ))
+    })
+}
+
+impl PartialEq for Set {
+    fn eq(&self, other: &Set) -> bool {
+        self.values == other.values
+    }
+}
@@ -1,21 +1,403 @@
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::rc::Rc;
+
+use crate::class::Class;
+use crate::enumeration::Enumeration;
+use crate::environment::Environment;
 use crate::expr::Expr;
+use crate::function::{Function, Parameter};
+use crate::interpreter::{Interpreter, Interrupt, RuntimeError};
+use crate::token::{Token, TokenKind};
 
 #[derive(Debug)]
 pub enum Statement {
     Print(Expr),
     Expr(Expr),
+
+    Define(Token, Expr),
+    Const(Token, Expr),
+    Assign(Expr, Token, Expr),
+
+    // Loops end with their label, if they have one
+    If(Expr, Vec<Statement>, Vec<Statement>),
+    While(Expr, Vec<Statement>, Option<Token>),
+    Loop(Vec<Statement>, Option<Token>),
+    For(Token, Expr, Vec<Statement>, Option<Token>),
+    With(Expr, Option<Token>, Vec<Statement>),
+    Try(Vec<Statement>, Option<Token>, Vec<Statement>),
+    Break(Option<Token>),
+    Continue(Option<Token>),
+
+    Global(Vec<Token>),
+    Nonlocal(Vec<Token>),
+
+    // Module paths are made of the identifiers between their periods
+    Import(Vec<Token>, Option<Token>),
+    FromImport(Vec<Token>, Vec<(Token, Option<Token>)>),
+
+    Func(Token, Vec<Parameter>, Rc<Vec<Statement>>, bool),
+    Return(Option<Expr>),
+    Yield(Expr),
+    Class(Token, Vec<Statement>),
+    Enum(Token, Vec<(Token, Vec<Token>)>),
 }
 
 impl Statement {
-    pub fn evaluate(&mut self) {
+    pub fn evaluate(&self, interpreter: &mut Interpreter) -> Result<(), Interrupt> {
+        let result = self.execute(interpreter);
+
+        // Generators the statement let go of while suspended in `with` blocks are closed here
+        interpreter.close_abandoned();
+        result
+    }
+
+    fn execute(&self, interpreter: &mut Interpreter) -> Result<(), Interrupt> {
         match self {
             Statement::Print(expr) => {
-                println!("{}", expr.evaluate().to_string().unwrap());
+                let value = expr.evaluate(interpreter)?;
+                println!("{}", interpreter.stringify(&value)?);
             }
 
             Statement::Expr(expr) => {
-                expr.evaluate();
+                expr.evaluate(interpreter)?;
+            }
+
+            Statement::Define(name, expr) => {
+                let value = expr.evaluate(interpreter)?;
+                interpreter.define(name.lexeme.clone(), value)?;
+            }
+
+            Statement::Const(name, expr) => {
+                let value = expr.evaluate(interpreter)?;
+                interpreter.define_constant(name.lexeme.clone(), value)?;
+            }
+
+            Statement::Assign(target, op, expr) => {
+                let value = expr.evaluate(interpreter)?;
+                Statement::evaluate_assign(interpreter, target, op, value)?;
+            }
+
+            Statement::If(condition, then_branch, else_branch) => {
+                let condition = condition.evaluate(interpreter)?;
+
+                let branch = if interpreter.truthy(&condition)? {
+                    then_branch
+                } else {
+                    else_branch
+                };
+
+                interpreter.execute_scoped(branch)?;
+            }
+
+            Statement::While(condition, body, label) => loop {
+                let value = condition.evaluate(interpreter)?;
+
+                if !interpreter.truthy(&value)? {
+                    break;
+                }
+
+                if !Statement::evaluate_loop_body(interpreter, body, label, None)? {
+                    break;
+                }
+            },
+
+            Statement::Loop(body, label) => loop {
+                if !Statement::evaluate_loop_body(interpreter, body, label, None)? {
+                    break;
+                }
+            },
+
+            Statement::For(name, iterable, body, label) => {
+                let iterable = iterable.evaluate(interpreter)?;
+                let mut iterator = interpreter.iterate(iterable)?;
+
+                while let Some(value) = iterator.next(interpreter)? {
+                    let binding = Some((name, value));
+
+                    if !Statement::evaluate_loop_body(interpreter, body, label, binding)? {
+                        break;
+                    }
+                }
+            }
+
+            Statement::With(expr, name, body) => {
+                let manager = expr.evaluate(interpreter)?;
+                let value = interpreter.enter_context(&manager)?;
+
+                let mut environment = Environment::from(interpreter.current_environment());
+
+                if let Some(name) = name {
+                    environment.define(name.lexeme.clone(), value);
+                }
+
+                let result = interpreter.execute_block(body, Rc::new(RefCell::new(environment)));
+
+                // The exit action runs however the block was left, before any interrupt carries on
+                interpreter.exit_context(&manager)?;
+                result?;
+            }
+
+            // The handler gets the error's message, bound to the name after `catch` if there is one
+            Statement::Try(body, name, handler) => match interpreter.execute_scoped(body) {
+                Err(Interrupt::Error(error)) if error.is_catchable() => {
+                    let mut environment = Environment::from(interpreter.current_environment());
+
+                    if let Some(name) = name {
+                        let message = Token::from(TokenKind::String(error.message));
+                        environment.define(name.lexeme.clone(), message);
+                    }
+
+                    interpreter.execute_block(handler, Rc::new(RefCell::new(environment)))?;
+                }
+
+                result => result?,
+            },
+
+            Statement::Break(label) => {
+                return Err(Interrupt::Break(label.as_ref().map(|l| l.lexeme.clone())));
+            }
+
+            Statement::Continue(label) => {
+                return Err(Interrupt::Continue(
+                    label.as_ref().map(|l| l.lexeme.clone()),
+                ));
+            }
+
+            Statement::Global(names) => {
+                for name in names {
+                    interpreter.declare_global(name)?;
+                }
+            }
+
+            Statement::Nonlocal(names) => {
+                for name in names {
+                    interpreter.declare_nonlocal(name)?;
+                }
+            }
+
+            Statement::Import(path, alias) => {
+                let module = interpreter.import_module(path)?;
+                let name = alias.as_ref().unwrap_or(path.last().unwrap());
+
+                interpreter.define(name.lexeme.clone(), Token::from(TokenKind::Module(module)))?;
+            }
+
+            Statement::FromImport(path, names) => {
+                let module = interpreter.import_module(path)?;
+
+                for (name, alias) in names {
+                    let value = match module.get(&name.lexeme) {
+                        Some(value) => value,
+                        None => {
+                            return Err(Interrupt::Error(RuntimeError::new(format!(
+                                "Import error: cannot import name '{}' from module {}.",
+                                name.lexeme, module.name
+                            ))));
+                        }
+                    };
+
+                    let name = alias.as_ref().unwrap_or(name);
+                    interpreter.define(name.lexeme.clone(), value)?;
+                }
+            }
+
+            Statement::Func(name, params, body, is_generator) => {
+                let func = Function::new(
+                    name.clone(),
+                    params.clone(),
+                    Rc::clone(body),
+                    interpreter.closure_environment(),
+                    *is_generator,
+                );
+
+                interpreter.define(
+                    name.lexeme.clone(),
+                    Token::from(TokenKind::Function(Rc::new(func))),
+                )?;
+            }
+
+            Statement::Return(expr) => {
+                let value = match expr {
+                    Some(expr) => expr.evaluate(interpreter)?,
+                    None => Token::from(TokenKind::None),
+                };
+
+                return Err(Interrupt::Return(value));
+            }
+
+            // Generators run their own bodies, so this is only reached by a misplaced yield
+            Statement::Yield(..) => {
+                return Err(Interrupt::Error(RuntimeError::new(String::from(
+                    "Yield error: found yield outside of a generator.",
+                ))));
+            }
+
+            Statement::Class(name, body) => {
+                let mut methods = HashMap::new();
+
+                for statement in body {
+                    if let Statement::Func(method, params, body, is_generator) = statement {
+                        let func = Function::new(
+                            method.clone(),
+                            params.clone(),
+                            Rc::clone(body),
+                            interpreter.closure_environment(),
+                            *is_generator,
+                        );
+
+                        methods.insert(method.lexeme.clone(), Rc::new(func));
+                    }
+                }
+
+                let class = Class::new(name.lexeme.clone(), methods);
+
+                interpreter.define(
+                    name.lexeme.clone(),
+                    Token::from(TokenKind::Type(Rc::new(class))),
+                )?;
+            }
+
+            Statement::Enum(name, variants) => {
+                let variants = variants
+                    .iter()
+                    .map(|(variant, fields)| {
+                        let fields = fields.iter().map(|field| field.lexeme.clone()).collect();
+                        (variant.lexeme.clone(), fields)
+                    })
+                    .collect();
+
+                let enumeration = Enumeration::new(name.lexeme.clone(), variants);
+
+                interpreter.define(
+                    name.lexeme.clone(),
+                    Token::from(TokenKind::Enumeration(Rc::new(enumeration))),
+                )?;
             }
         }
+
+        Ok(())
+    }
+
+    /// Whether running this statement might suspend a generator. Nested function declarations are
+    /// not counted, as a yield within them makes that function a generator instead.
+    pub fn contains_yield(&self) -> bool {
+        match self {
+            Statement::Yield(..) => true,
+
+            Statement::If(_, then_branch, else_branch)
+            | Statement::Try(then_branch, _, else_branch) => then_branch
+                .iter()
+                .chain(else_branch)
+                .any(Statement::contains_yield),
+
+            Statement::While(_, body, _)
+            | Statement::Loop(body, _)
+            | Statement::For(_, _, body, _)
+            | Statement::With(_, _, body) => body.iter().any(Statement::contains_yield),
+
+            _ => false,
+        }
+    }
+
+    /// Runs one iteration of a loop body in a fresh scope, with the loop variable of a `for` loop
+    /// bound in it, returning whether the loop should go round again
+    fn evaluate_loop_body(
+        interpreter: &mut Interpreter,
+        body: &[Statement],
+        label: &Option<Token>,
+        binding: Option<(&Token, Token)>,
+    ) -> Result<bool, Interrupt> {
+        let mut environment = Environment::from(interpreter.current_environment());
+
+        if let Some((name, value)) = binding {
+            environment.define(name.lexeme.clone(), value);
+        }
+
+        match interpreter.execute_block(body, Rc::new(RefCell::new(environment))) {
+            Ok(()) => Ok(true),
+            Err(Interrupt::Continue(target)) if targets_loop(label, &target) => Ok(true),
+            Err(Interrupt::Break(target)) if targets_loop(label, &target) => Ok(false),
+            Err(interrupt) => Err(interrupt),
+        }
+    }
+
+    fn evaluate_assign(
+        interpreter: &mut Interpreter,
+        target: &Expr,
+        op: &Token,
+        value: Token,
+    ) -> Result<(), RuntimeError> {
+        // Compound assignments apply their operator to the target's current value first
+        let binary_op = match op.kind {
+            TokenKind::PlusEqual => Some(TokenKind::Plus),
+            TokenKind::MinusEqual => Some(TokenKind::Minus),
+            TokenKind::StarEqual => Some(TokenKind::Star),
+            TokenKind::SlashEqual => Some(TokenKind::Slash),
+            TokenKind::PercentEqual => Some(TokenKind::Percent),
+            TokenKind::CaretEqual => Some(TokenKind::Caret),
+
+            _ => None,
+        };
+
+        match target {
+            Expr::Literal(name) => {
+                let value = match binary_op {
+                    Some(kind) => {
+                        let current = interpreter.lookup(name)?;
+                        Expr::binary_operation(interpreter, &Token::from(kind), current, value)?
+                    }
+
+                    None => value,
+                };
+
+                interpreter.assign(name, value)
+            }
+
+            Expr::Index(object, _, index) => {
+                let object = object.evaluate(interpreter)?;
+                let index = index.evaluate(interpreter)?;
+
+                let value = match binary_op {
+                    Some(kind) => {
+                        let current = interpreter.get_index(object.clone(), index.clone())?;
+                        Expr::binary_operation(interpreter, &Token::from(kind), current, value)?
+                    }
+
+                    None => value,
+                };
+
+                interpreter.set_index(object, index, value)
+            }
+
+            Expr::Get(object, name) => {
+                let object = object.evaluate(interpreter)?;
+
+                let value = match binary_op {
+                    Some(kind) => {
+                        let current = interpreter.get_property(object.clone(), name)?;
+                        Expr::binary_operation(interpreter, &Token::from(kind), current, value)?
+                    }
+
+                    None => value,
+                };
+
+                interpreter.set_property(object, name, value)
+            }
+
+            _ => Err(RuntimeError::new(format!(
+                "Assignment error: invalid assignment target {:?}.",
+                target
+            ))),
+        }
+    }
+}
+
+/// Whether a `break` or `continue` naming `target` applies to a loop with the given label. Those
+/// without a target always apply to the innermost loop.
+pub fn targets_loop(label: &Option<Token>, target: &Option<String>) -> bool {
+    match target {
+        Some(target) => label.as_ref().is_some_and(|label| &label.lexeme == target),
+        None => true,
     }
 }
@@ -1,3 +1,61 @@
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use crate::class::{Class, Instance};
+use crate::date::Date;
+use crate::enumeration::{Enumeration, Variant};
+use crate::function::{Function, NativeFunction};
+use crate::generator::Generator;
+use crate::host::HostObject;
+use crate::map::Map;
+use crate::module::Module;
+use crate::set::Set;
+
+thread_local! {
+    // The lists, maps and instances being written out, so that ones containing themselves are
+    // written as `<...>` rather than recursing forever
+    static WRITING: RefCell<Vec<*const ()>> = const { RefCell::new(Vec::new()) };
+
+    // The pairs of lists or maps being compared, which are taken to be equal if they are reached
+    // again while comparing their own elements
+    static COMPARING: RefCell<Vec<(*const (), *const ())>> = const { RefCell::new(Vec::new()) };
+}
+
+/// Writes out a value that may contain itself, giving `<...>` if it is already being written
+pub fn write_once<T: ?Sized>(value: &T, write: impl FnOnce() -> String) -> String {
+    let address = value as *const T as *const ();
+
+    if WRITING.with(|writing| writing.borrow().contains(&address)) {
+        return String::from("<...>");
+    }
+
+    WRITING.with(|writing| writing.borrow_mut().push(address));
+    let written = write();
+    WRITING.with(|writing| writing.borrow_mut().pop());
+
+    written
+}
+
+/// Compares two values that may contain themselves, assuming they are equal if they are already
+/// being compared further up
+fn compare_once<T: ?Sized>(lhs: &Rc<T>, rhs: &Rc<T>, compare: impl FnOnce() -> bool) -> bool {
+    if Rc::ptr_eq(lhs, rhs) {
+        return true;
+    }
+
+    let pair = (Rc::as_ptr(lhs) as *const (), Rc::as_ptr(rhs) as *const ());
+
+    if COMPARING.with(|comparing| comparing.borrow().contains(&pair)) {
+        return true;
+    }
+
+    COMPARING.with(|comparing| comparing.borrow_mut().push(pair));
+    let equal = compare();
+    COMPARING.with(|comparing| comparing.borrow_mut().pop());
+
+    equal
+}
+
 #[derive(Debug, PartialEq, Clone)]
 pub enum TokenKind {
     // Single character tokens
@@ -5,6 +63,8 @@ pub enum TokenKind {
     RightParen,
     LeftBracket,
     RightBracket,
+    LeftBrace,
+    RightBrace,
 
     Comma,
     Period,
@@ -38,11 +98,34 @@ pub enum TokenKind {
     String(String),
     Number(f64),
     Bool(bool),
+    Bytes(Rc<Vec<u8>>),
+
+    // Runtime values
+    List(Rc<RefCell<Vec<Token>>>),
+    Map(Rc<RefCell<Map>>),
+    Set(Rc<RefCell<Set>>),
+    Date(Date),
+    Function(Rc<Function>),
+    NativeFunction(Rc<NativeFunction>),
+    Generator(Rc<RefCell<Generator>>),
+    Type(Rc<Class>),
+    Instance(Rc<Instance>),
+    Enumeration(Rc<Enumeration>),
+    Constructor(Rc<Enumeration>, usize),
+    Variant(Rc<Variant>),
+    Module(Rc<Module>),
+    Object(Rc<dyn HostObject>),
 
     // Keywords
     Func,
+    Class,
+    Enum,
     Define,
+    Const,
     Return,
+    Yield,
+    Global,
+    Nonlocal,
     Continue,
     Break,
     Print,
@@ -54,8 +137,16 @@ pub enum TokenKind {
     If,
     Else,
     For,
+    In,
+    Is,
     While,
     Loop,
+    With,
+    As,
+    Import,
+    From,
+    Try,
+    Catch,
 
     None,
 
@@ -67,15 +158,22 @@ pub enum TokenKind {
     Eof,
 }
 
-#[derive(Debug, PartialEq, Clone)]
+#[derive(Debug, Clone)]
 pub struct Token {
     pub kind: TokenKind,
     pub lexeme: String,
+
+    // Line of the source file the token was found on, or 0 for tokens made at runtime
+    pub line: usize,
 }
 
 impl Token {
     pub fn new(kind: TokenKind, lexeme: String) -> Token {
-        Token { kind, lexeme }
+        Token {
+            kind,
+            lexeme,
+            line: 0,
+        }
     }
 
     pub fn to_string(&self) -> Option<String> {
@@ -83,6 +181,66 @@ impl Token {
             TokenKind::String(val) => Some(val),
             TokenKind::Number(val) => Some(val.to_string()),
             TokenKind::Bool(val) => Some(val.to_string()),
+            TokenKind::Bytes(bytes) => Some(bytes_literal(&bytes)),
+
+            TokenKind::List(list) => Some(write_once(list.as_ref(), || {
+                let elements: Vec<String> = list
+                    .borrow()
+                    .iter()
+                    .map(|element| element.to_string().unwrap_or_default())
+                    .collect();
+
+                format!("[{}]", elements.join(", "))
+            })),
+
+            TokenKind::Map(map) => Some(write_once(map.as_ref(), || {
+                let entries: Vec<String> = map
+                    .borrow()
+                    .entries()
+                    .iter()
+                    .map(|(key, value)| {
+                        format!(
+                            "{}: {}",
+                            key.to_string().unwrap_or_default(),
+                            value.to_string().unwrap_or_default()
+                        )
+                    })
+                    .collect();
+
+                format!("{{{}}}", entries.join(", "))
+            })),
+
+            // Empty sets are written as a call, since `{}` is an empty map
+            TokenKind::Set(set) if set.borrow().is_empty() => Some(String::from("set()")),
+            TokenKind::Set(set) => {
+                let values: Vec<String> = set
+                    .borrow()
+                    .values()
+                    .iter()
+                    .map(|value| value.to_string().unwrap_or_default())
+                    .collect();
+
+                Some(format!("{{{}}}", values.join(", ")))
+            }
+
+            TokenKind::Date(date) => Some(date.to_string()),
+
+            TokenKind::Function(func) => Some(format!("<func {}>", func.name.lexeme)),
+            TokenKind::NativeFunction(func) => Some(format!("<native func {}>", func.name)),
+            TokenKind::Generator(generator) => {
+                Some(format!("<generator {}>", generator.borrow().name))
+            }
+            TokenKind::Type(class) => Some(format!("<class {}>", class.name)),
+            TokenKind::Instance(instance) => Some(instance.to_string()),
+            TokenKind::Enumeration(enumeration) => Some(format!("<enum {}>", enumeration.name)),
+            TokenKind::Constructor(enumeration, index) => Some(format!(
+                "<constructor {}.{}>",
+                enumeration.name,
+                enumeration.variant_name(index)
+            )),
+            TokenKind::Variant(variant) => Some(variant.to_string()),
+            TokenKind::Module(module) => Some(format!("<module {}>", module.name)),
+            TokenKind::Object(object) => Some(object.display()),
 
             TokenKind::None => Some(String::from("none")),
 
@@ -111,31 +269,134 @@ impl Token {
             }
 
             TokenKind::Bool(val) => Some(val),
+            TokenKind::Bytes(bytes) => Some(!bytes.is_empty()),
+            TokenKind::List(list) => Some(!list.borrow().is_empty()),
+            TokenKind::Map(map) => Some(!map.borrow().is_empty()),
+            TokenKind::Set(set) => Some(!set.borrow().is_empty()),
             TokenKind::None => Some(false),
 
+            TokenKind::Date(..)
+            | TokenKind::Function(..)
+            | TokenKind::NativeFunction(..)
+            | TokenKind::Generator(..)
+            | TokenKind::Type(..)
+            | TokenKind::Instance(..)
+            | TokenKind::Enumeration(..)
+            | TokenKind::Constructor(..)
+            | TokenKind::Variant(..)
+            | TokenKind::Module(..)
+            | TokenKind::Object(..) => Some(true),
+
             _ => None,
         }
     }
 
     pub fn is_equal(&self, rhs: Token) -> bool {
-        // TODO: Make less ugly
-        if self.to_number().is_some() && rhs.to_number().is_some() {
-            self.to_number().unwrap() == rhs.to_number().unwrap()
-        } else if self.to_bool().is_some() && rhs.to_bool().is_some() {
-            self.to_bool().unwrap() == rhs.to_bool().unwrap()
-        } else if self.to_string().is_some() && self.to_string().is_some() {
-            self.to_string().unwrap() == rhs.to_string().unwrap()
-        } else {
-            false
+        match (&self.kind, &rhs.kind) {
+            (TokenKind::Number(lval), TokenKind::Number(rval)) => lval == rval,
+            (TokenKind::String(lval), TokenKind::String(rval)) => lval == rval,
+            (TokenKind::Bool(lval), TokenKind::Bool(rval)) => lval == rval,
+            (TokenKind::Bytes(lval), TokenKind::Bytes(rval)) => lval == rval,
+            (TokenKind::None, TokenKind::None) => true,
+
+            (TokenKind::List(lval), TokenKind::List(rval)) => compare_once(lval, rval, || {
+                let (lval, rval) = (lval.borrow(), rval.borrow());
+
+                lval.len() == rval.len()
+                    && lval
+                        .iter()
+                        .zip(rval.iter())
+                        .all(|(lhs, rhs)| lhs.is_equal(rhs.clone()))
+            }),
+
+            // Maps are equal when they have the same entries, whatever order they were added in
+            (TokenKind::Map(lval), TokenKind::Map(rval)) => compare_once(lval, rval, || {
+                let (lval, rval) = (lval.borrow(), rval.borrow());
+
+                lval.len() == rval.len()
+                    && lval
+                        .entries()
+                        .iter()
+                        .all(|(key, value)| match rval.get(key) {
+                            Ok(Some(other)) => value.is_equal(other),
+                            _ => false,
+                        })
+            }),
+
+            // Sets are equal when they have the same members, whatever order they were added in
+            (TokenKind::Set(lval), TokenKind::Set(rval)) => {
+                let (lval, rval) = (lval.borrow(), rval.borrow());
+
+                lval.len() == rval.len()
+                    && lval
+                        .values()
+                        .iter()
+                        .all(|value| rval.contains(value).unwrap_or(false))
+            }
+
+            // Dates are equal when they are the same moment, whatever their offsets
+            (TokenKind::Date(lval), TokenKind::Date(rval)) => lval.timestamp() == rval.timestamp(),
+
+            // Host objects decide for themselves
+            (TokenKind::Object(lval), TokenKind::Object(rval)) => lval.equals(rval.as_ref()),
+
+            // Objects are compared by identity
+            (TokenKind::Function(lval), TokenKind::Function(rval)) => Rc::ptr_eq(lval, rval),
+            (TokenKind::NativeFunction(lval), TokenKind::NativeFunction(rval)) => {
+                Rc::ptr_eq(lval, rval)
+            }
+            (TokenKind::Generator(lval), TokenKind::Generator(rval)) => Rc::ptr_eq(lval, rval),
+            (TokenKind::Type(lval), TokenKind::Type(rval)) => Rc::ptr_eq(lval, rval),
+            (TokenKind::Instance(lval), TokenKind::Instance(rval)) => Rc::ptr_eq(lval, rval),
+            (TokenKind::Enumeration(lval), TokenKind::Enumeration(rval)) => Rc::ptr_eq(lval, rval),
+            (TokenKind::Constructor(lval, lindex), TokenKind::Constructor(rval, rindex)) => {
+                Rc::ptr_eq(lval, rval) && lindex == rindex
+            }
+            (TokenKind::Module(lval), TokenKind::Module(rval)) => Rc::ptr_eq(lval, rval),
+
+            // Variants are compared by value. Checking which variant a value is, whatever its data,
+            // is done with `is` instead.
+            (TokenKind::Variant(lval), TokenKind::Variant(rval)) => lval == rval,
+
+            _ => false,
         }
     }
 }
 
+/// Writes bytes the way they would be written in a literal, escaping any that aren't printable
+fn bytes_literal(bytes: &[u8]) -> String {
+    let mut literal = String::from("b\"");
+
+    for byte in bytes {
+        match byte {
+            b'"' => literal.push_str("\\\""),
+            b'\'' => literal.push_str("\\'"),
+            b'\\' => literal.push_str("\\\\"),
+            b'\n' => literal.push_str("\\n"),
+            b'\r' => literal.push_str("\\r"),
+            b'\t' => literal.push_str("\\t"),
+            b' '..=b'~' => literal.push(*byte as char),
+            _ => literal.push_str(&format!("\\x{:02x}", byte)),
+        }
+    }
+
+    literal.push('"');
+    literal
+}
+
 impl From<TokenKind> for Token {
     fn from(kind: TokenKind) -> Token {
         Token {
             kind,
             lexeme: String::new(),
+            line: 0,
         }
     }
 }
+
+// Tokens are equal when they hold the same thing, wherever they were found
+impl PartialEq for Token {
+    fn eq(&self, other: &Token) -> bool {
+        self.kind == other.kind && self.lexeme == other.lexeme
+    }
+}